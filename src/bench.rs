@@ -0,0 +1,182 @@
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicI64};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use anyhow::{Context, Result, anyhow};
+use serde::{Deserialize, Serialize};
+
+use rcrd::ffmpeg::{PCM_TAP_RATE, build_decode_command};
+use rcrd::process::tool;
+use rcrd::sync::lock;
+use rcrd::transcript::{TranscriberStatus, Windowing, start_transcriber, vulkan_available};
+
+/// Bytes per second of the mono s16le stream the transcriber reads.
+const PCM_BYTES_PER_SEC: f64 = PCM_TAP_RATE as f64 * 2.0;
+/// Length of the generated clip used when no `--clip` is given.
+const SYNTH_SECS: u32 = 30;
+
+#[derive(Serialize, Deserialize)]
+struct BenchResult {
+    backend: String,
+    audio_secs: f64,
+    wall_secs: f64,
+    segments: usize,
+    /// Peak resident set of the largest child process, in KiB: whisper-cli,
+    /// or one of the ffmpeg runs that generate and decode the clip.
+    max_rss_kib: i64,
+}
+
+/// `rcrd bench`: transcribes the same clip with each available backend and
+/// prints a comparison. Each backend runs in a child rcrd process so its
+/// peak memory can be measured on its own.
+pub fn run_bench(model: &Path, clip: Option<&Path>, threads: usize) -> Result<()> {
+    let mut backends = vec!["openblas"];
+    if vulkan_available() {
+        backends.insert(0, "vulkan");
+    } else {
+        eprintln!("No usable Vulkan GPU; benchmarking openblas only");
+    }
+
+    let exe = std::env::current_exe().context("locating rcrd executable")?;
+    let mut results = Vec::new();
+    for backend in backends {
+        eprintln!("Running {backend}...");
+        let mut cmd = Command::new(&exe);
+        cmd.args(["bench", "--model"]).arg(model).args([
+            "--threads",
+            &threads.to_string(),
+            "--run-one",
+            backend,
+        ]);
+        if let Some(clip) = clip {
+            cmd.arg("--clip").arg(clip);
+        }
+        let output = cmd.stderr(Stdio::inherit()).output()?;
+        if !output.status.success() {
+            eprintln!("{backend}: failed ({})", output.status);
+            continue;
+        }
+        results.push(serde_json::from_slice::<BenchResult>(&output.stdout)?);
+    }
+
+    println!(
+        "{:<10} {:>9} {:>9} {:>7} {:>9} {:>10}",
+        "backend", "audio s", "wall s", "RTF", "segments", "child MiB"
+    );
+    for r in &results {
+        println!(
+            "{:<10} {:>9.1} {:>9.1} {:>7.2} {:>9} {:>10.0}",
+            r.backend,
+            r.audio_secs,
+            r.wall_secs,
+            r.wall_secs / r.audio_secs,
+            r.segments,
+            r.max_rss_kib as f64 / 1024.0
+        );
+    }
+    Ok(())
+}
+
+/// Child side of `run_bench`: one backend, result as JSON on stdout.
+pub fn run_one(model: &Path, clip: Option<&Path>, backend: &str, threads: usize) -> Result<()> {
+    let pcm = decode_clip(clip)?;
+    let audio_secs = pcm.len() as f64 / PCM_BYTES_PER_SEC;
+
+    let transcript = Arc::new(Mutex::new(Vec::new()));
+    let status = Arc::new(Mutex::new(TranscriberStatus::Loading));
+    let started = Instant::now();
+    let thread = start_transcriber(
+        Cursor::new(pcm),
+        model.to_path_buf(),
+        Arc::new(Mutex::new("en".to_string())),
+        transcript.clone(),
+        Arc::new(AtomicBool::new(true)),
+        Arc::new(AtomicBool::new(false)),
+        backend.to_string(),
+        Arc::new(AtomicI64::new(0)),
+        Arc::new(AtomicBool::new(false)),
+        threads,
+        Arc::new(Mutex::new(Vec::new())),
+        None,
+        None,
+        status.clone(),
+        Windowing::default(),
+        None,
+        None,
+        Arc::new(Mutex::new(Vec::new())),
+    );
+    let _ = thread.join();
+    let wall_secs = started.elapsed().as_secs_f64();
+    if let TranscriberStatus::Error(err) = &*lock(&status) {
+        return Err(anyhow!("{backend}: {err}"));
+    }
+
+    let result = BenchResult {
+        backend: backend.to_string(),
+        audio_secs,
+        wall_secs,
+        segments: lock(&transcript).len(),
+        max_rss_kib: children_max_rss(),
+    };
+    println!("{}", serde_json::to_string(&result)?);
+    Ok(())
+}
+
+/// Decodes `clip` (or a generated tone when none is given) to transcriber PCM.
+fn decode_clip(clip: Option<&Path>) -> Result<Vec<u8>> {
+    let synth;
+    let path = match clip {
+        Some(clip) => clip,
+        None => {
+            synth = synth_clip()?;
+            &synth
+        }
+    };
+    let output = build_decode_command(path)
+        .stderr(Stdio::inherit())
+        .output()?;
+    if clip.is_none() {
+        let _ = std::fs::remove_file(path);
+    }
+    if !output.status.success() || output.stdout.is_empty() {
+        return Err(anyhow!("ffmpeg could not decode {}", path.display()));
+    }
+    Ok(output.stdout)
+}
+
+/// Without a clip there is no speech to bundle, so a tone stands in; it
+/// still measures how fast each backend gets through the same audio.
+fn synth_clip() -> Result<PathBuf> {
+    let path = std::env::temp_dir().join(format!("rcrd-bench-{}.wav", std::process::id()));
+    let status = tool("ffmpeg")
+        .args([
+            "-hide_banner",
+            "-nostdin",
+            "-loglevel",
+            "error",
+            "-y",
+            "-f",
+            "lavfi",
+        ])
+        .arg(format!("sine=frequency=440:duration={SYNTH_SECS}"))
+        .arg(&path)
+        .status()
+        .context("failed to run ffmpeg")?;
+    if !status.success() {
+        return Err(anyhow!("ffmpeg could not generate a test clip"));
+    }
+    Ok(path)
+}
+
+/// `ru_maxrss` of `RUSAGE_CHILDREN`: the largest peak of any child waited
+/// for so far, not only whisper-cli's.
+fn children_max_rss() -> i64 {
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    if unsafe { libc::getrusage(libc::RUSAGE_CHILDREN, &mut usage) } != 0 {
+        return 0;
+    }
+    usage.ru_maxrss
+}
@@ -0,0 +1,173 @@
+//! `rcrd markers apply`: embeds saved markers as chapters into a finished
+//! file, such as one edited after the recording, so players can jump
+//! between them.
+
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{Context, Result, anyhow};
+
+use crate::Marker;
+use crate::probe::probe;
+use crate::process::{ProcessRunner, tool};
+
+/// What ffmpeg reads the chapters from, next to the file.
+fn chapters_path(file: &Path) -> PathBuf {
+    file.with_extension("chapters.txt")
+}
+
+/// Where the file with chapters is written before it replaces the original.
+/// It keeps the extension so ffmpeg picks the same container.
+fn tagged_path(file: &Path) -> PathBuf {
+    let ext = file.extension().map(|e| e.to_string_lossy().into_owned());
+    file.with_extension(match ext {
+        Some(ext) => format!("chaptering.{ext}"),
+        None => "chaptering".into(),
+    })
+}
+
+/// Escapes what ffmetadata files treat specially: `=`, `;`, `#`, `\` and
+/// line breaks.
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '=' | ';' | '#' | '\\' | '\n') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// An ffmetadata file with one chapter per marker, in order of time, each
+/// running until the next one or the end of the file. Chapters are titled
+/// with the marker's note, or `Marker N` without one.
+pub fn ffmetadata(markers: &[Marker], duration_secs: f64) -> String {
+    let mut starts: Vec<(f64, &str)> = markers
+        .iter()
+        .map(|m| (m.timestamp, m.note.as_str()))
+        .collect();
+    starts.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let to_ms = |secs: f64| (secs * 1000.0).round() as i64;
+    let mut out = String::from(";FFMETADATA1\n");
+    for (i, &(start, note)) in starts.iter().enumerate() {
+        let end = starts.get(i + 1).map_or(duration_secs, |next| next.0);
+        let title = match note.trim() {
+            "" => format!("Marker {}", i + 1),
+            note => note.to_string(),
+        };
+        let _ = write!(
+            out,
+            "[CHAPTER]\nTIMEBASE=1/1000\nSTART={}\nEND={}\ntitle={}\n",
+            to_ms(start),
+            to_ms(end),
+            escape(&title)
+        );
+    }
+    out
+}
+
+pub fn build_chapters_command(input: &Path, chapters: &Path, output: &Path) -> Command {
+    let mut cmd = tool("ffmpeg");
+    cmd.args(["-hide_banner", "-nostdin", "-y", "-loglevel", "error", "-i"])
+        .arg(input);
+    cmd.args(["-f", "ffmetadata", "-i"]).arg(chapters);
+    cmd.args(["-map", "0", "-map_metadata", "0", "-map_chapters", "1"]);
+    cmd.args(["-c", "copy"]).arg(output);
+    cmd
+}
+
+/// Replaces any chapters in `file` with `markers`, without re-encoding.
+/// Every marker must fall within the file's duration as ffprobe reports it;
+/// otherwise nothing is written.
+pub fn apply_markers(runner: &dyn ProcessRunner, file: &Path, markers: &[Marker]) -> Result<()> {
+    let duration = probe(runner, file)?.duration_secs;
+    let outside: Vec<String> = markers
+        .iter()
+        .filter(|m| !(0.0..=duration).contains(&m.timestamp))
+        .map(|m| format!("{:.3}s ({})", m.timestamp, m.note))
+        .collect();
+    if !outside.is_empty() {
+        return Err(anyhow!(
+            "{} is {duration:.3}s long, but markers fall outside it: {}",
+            file.display(),
+            outside.join(", ")
+        ));
+    }
+
+    let chapters = chapters_path(file);
+    std::fs::write(&chapters, ffmetadata(markers, duration))
+        .with_context(|| format!("writing {}", chapters.display()))?;
+    let tmp = tagged_path(file);
+    let res = runner
+        .output(&mut build_chapters_command(file, &chapters, &tmp))
+        .context("failed to run ffmpeg");
+    let _ = std::fs::remove_file(&chapters);
+    let out = res?;
+    if !out.status.success() {
+        let _ = std::fs::remove_file(&tmp);
+        return Err(anyhow!(
+            "adding chapters with ffmpeg exited with {}: {}",
+            out.status,
+            String::from_utf8_lossy(&out.stderr).trim()
+        ));
+    }
+    std::fs::rename(&tmp, file)
+        .with_context(|| format!("replacing {} with the chaptered file", file.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::mock::MockRunner;
+
+    fn marker(timestamp: f64, note: &str) -> Marker {
+        Marker {
+            timestamp,
+            wall_clock: String::new(),
+            note: note.into(),
+        }
+    }
+
+    #[test]
+    fn chapters_run_from_marker_to_marker() {
+        let markers = [
+            marker(75.5, "Q&A; a=b"),
+            marker(12.0, ""),
+            marker(0.0, "intro"),
+        ];
+        assert_eq!(
+            ffmetadata(&markers, 90.25),
+            ";FFMETADATA1\n\
+             [CHAPTER]\nTIMEBASE=1/1000\nSTART=0\nEND=12000\ntitle=intro\n\
+             [CHAPTER]\nTIMEBASE=1/1000\nSTART=12000\nEND=75500\ntitle=Marker 2\n\
+             [CHAPTER]\nTIMEBASE=1/1000\nSTART=75500\nEND=90250\ntitle=Q&A\\; a\\=b\n"
+        );
+        let cmd = build_chapters_command(
+            Path::new("call.ogg"),
+            Path::new("call.chapters.txt"),
+            &tagged_path(Path::new("call.ogg")),
+        );
+        let args = crate::process::argv(&cmd).join(" ");
+        assert!(
+            args.ends_with(
+                "-f ffmetadata -i call.chapters.txt -map 0 -map_metadata 0 -map_chapters 1 \
+                 -c copy call.chaptering.ogg"
+            ),
+            "{args}"
+        );
+    }
+
+    #[test]
+    fn markers_past_the_end_are_rejected_before_writing() {
+        let runner = MockRunner::with_stdout(r#"{"streams": [], "format": {"duration": "60.0"}}"#);
+        let file = Path::new("edited.ogg");
+        let err =
+            apply_markers(&runner, file, &[marker(10.0, "a"), marker(61.5, "late")]).unwrap_err();
+        assert!(err.to_string().contains("61.500s (late)"), "{err}");
+        // Only ffprobe ran.
+        assert_eq!(runner.calls.lock().unwrap().len(), 1);
+        assert!(!chapters_path(file).exists());
+    }
+}
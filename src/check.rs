@@ -0,0 +1,44 @@
+use std::path::Path;
+
+use anyhow::{Result, anyhow};
+use rcrd::probe::check_file;
+use rcrd::process::SystemRunner;
+use rcrd::transcript::export::format_timecode;
+
+/// `rcrd check`: decodes `file` in full and reports its duration, decoding
+/// errors and the silence at its ends. Fails if it is broken or silent.
+pub fn run_check(file: &Path, threshold_db: f32, min_silence_secs: f64) -> Result<()> {
+    let check = check_file(&SystemRunner, file, threshold_db, min_silence_secs)?;
+    println!("{}", file.display());
+    if let Some(probe) = &check.probe {
+        println!(
+            "  duration: {} ({})",
+            format_timecode((probe.duration_secs * 1000.0).round() as i64),
+            probe.summary()
+        );
+    }
+    if check.errors.is_empty() {
+        println!("  decodes cleanly");
+    } else {
+        for err in &check.errors {
+            println!("  error: {err}");
+        }
+    }
+    match check.silence {
+        Some(silence) if silence.all_silent() => {
+            println!("  silent throughout (nothing above {threshold_db} dB)");
+        }
+        Some(silence) => println!(
+            "  silence: {:.1}s at the start, {:.1}s at the end",
+            silence.leading, silence.trailing
+        ),
+        None => {}
+    }
+    if check.is_ok() {
+        Ok(())
+    } else if check.errors.is_empty() {
+        Err(anyhow!("{} holds no audio", file.display()))
+    } else {
+        Err(anyhow!("{} is broken", file.display()))
+    }
+}
@@ -0,0 +1,121 @@
+//! Joining the pieces of a paused recording: every resume writes a new
+//! file, and they are put back together, without re-encoding and without
+//! the paused time, once recording stops.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{Context, Result, anyhow};
+
+use crate::ffmpeg::AudioFormat;
+use crate::process::{ProcessRunner, tool};
+
+/// List [`join_pieces`] hands to ffmpeg's concat demuxer.
+fn list_path(output: &Path) -> PathBuf {
+    output.with_extension("concat.txt")
+}
+
+/// `file '…'` lines for the concat demuxer, which unquotes `'\''` like a shell.
+fn concat_list(pieces: &[PathBuf]) -> String {
+    pieces
+        .iter()
+        .map(|piece| {
+            let path = std::path::absolute(piece).unwrap_or_else(|_| piece.clone());
+            format!(
+                "file '{}'\n",
+                path.display().to_string().replace('\'', r"'\''")
+            )
+        })
+        .collect()
+}
+
+pub fn build_concat_command(list: &Path, output: &Path, format: AudioFormat) -> Command {
+    let mut cmd = tool("ffmpeg");
+    cmd.args(["-hide_banner", "-nostdin", "-y", "-loglevel", "error"]);
+    cmd.args(["-f", "concat", "-safe", "0", "-i"]).arg(list);
+    cmd.args(["-map_metadata", "0", "-c", "copy", "-f", format.muxer()])
+        .arg(output);
+    cmd
+}
+
+/// Joins `pieces` in order into the first of them and deletes the others.
+/// On failure every piece is left as it was.
+pub fn join_pieces(
+    runner: &dyn ProcessRunner,
+    pieces: &[PathBuf],
+    format: AudioFormat,
+) -> Result<()> {
+    let Some(first) = pieces.first() else {
+        return Ok(());
+    };
+    if pieces.len() == 1 {
+        return Ok(());
+    }
+    let list = list_path(first);
+    std::fs::write(&list, concat_list(pieces))
+        .with_context(|| format!("writing {}", list.display()))?;
+    let joined = first.with_extension(format!("joining.{}", format.extension()));
+    let res = runner
+        .output(&mut build_concat_command(&list, &joined, format))
+        .context("failed to run ffmpeg");
+    let _ = std::fs::remove_file(&list);
+    let out = res?;
+    if !out.status.success() {
+        let _ = std::fs::remove_file(&joined);
+        return Err(anyhow!(
+            "joining with ffmpeg exited with {}: {}",
+            out.status,
+            String::from_utf8_lossy(&out.stderr).trim()
+        ));
+    }
+    std::fs::rename(&joined, first)
+        .with_context(|| format!("replacing {} with the joined file", first.display()))?;
+    for piece in &pieces[1..] {
+        let _ = std::fs::remove_file(piece);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::mock::MockRunner;
+
+    #[test]
+    fn lists_pieces_quoted_for_the_concat_demuxer() {
+        let list = concat_list(&["/rec/call.ogg".into(), "/rec/it's-part2.ogg".into()]);
+        assert_eq!(
+            list,
+            "file '/rec/call.ogg'\nfile '/rec/it'\\''s-part2.ogg'\n"
+        );
+        let cmd = build_concat_command(
+            Path::new("call.concat.txt"),
+            Path::new("call.joining.ogg"),
+            AudioFormat::Opus,
+        );
+        let args = crate::process::argv(&cmd).join(" ");
+        assert!(
+            args.contains("-f concat -safe 0 -i call.concat.txt -map_metadata 0 -c copy"),
+            "{args}"
+        );
+    }
+
+    #[test]
+    fn failed_join_keeps_every_piece() {
+        let dir = std::env::temp_dir().join(format!("rcrd-concat-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let pieces = [dir.join("call.ogg"), dir.join("call-part2.ogg")];
+        for piece in &pieces {
+            std::fs::write(piece, b"audio").unwrap();
+        }
+        let failing = MockRunner {
+            exit_code: 1,
+            ..MockRunner::default()
+        };
+        assert!(join_pieces(&failing, &pieces, AudioFormat::Opus).is_err());
+        assert!(pieces.iter().all(|p| p.exists()));
+        assert!(!list_path(&pieces[0]).exists());
+        assert!(join_pieces(&failing, &pieces[..1], AudioFormat::Opus).is_ok());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
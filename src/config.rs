@@ -1,8 +1,10 @@
+use std::collections::BTreeMap;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, anyhow};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
 fn default_prefix() -> String {
     "rcrd-call-".into()
@@ -21,26 +23,160 @@ fn default_backend() -> String {
 pub struct Config {
     /// Prefix used for generated output filenames (datetime appended).
     pub file_prefix: String,
+    /// Output filename template with `{date}`, `{time}`, `{sink}`, `{host}` placeholders.
+    /// Defaults to `<file_prefix>{date}-{time}`; the extension is appended automatically.
+    pub filename_template: Option<String>,
+    /// Use UTC instead of local time for generated filenames and metadata.
+    pub use_utc: bool,
     /// Path to whisper.cpp model file (ggml/gguf).
     pub whisper_model: Option<PathBuf>,
     /// Default transcription language (e.g., "en", "fr").
     pub language: Option<String>,
+    /// Initial prompt for whisper: names and terms it should expect.
+    pub transcribe_prompt: Option<String>,
     /// Whisper backend: "vulkan" (GPU) or "openblas" (CPU).
     pub backend: String,
+    /// Labels for the remote and local speakers when recording with split channels.
+    pub speaker_labels: [String; 2],
+    /// Seconds after which a monitor that produced no audio triggers a warning (0 disables).
+    pub silence_check_secs: u64,
+    /// Keep retrying `pw-dump` for up to this many seconds while PipeWire starts (0 disables).
+    pub wait_for_audio_secs: u64,
+    /// Stop any recording after this many seconds, even without `--duration` (0 disables).
+    pub max_recording_seconds: u64,
+    /// ffmpeg binary to run instead of the one on `PATH`; an `ffprobe` next to it is used too.
+    pub ffmpeg_path: Option<PathBuf>,
+    /// pw-dump binary to run instead of the one on `PATH`.
+    pub pw_dump_path: Option<PathBuf>,
+    /// Shell command run after recording; `{file}`, `{transcript}`, `{markers}` are substituted.
+    pub post_command: Option<String>,
+    /// Channels in the recorded file: 2 (default) or 1 for mono.
+    pub channels: u8,
+    /// Save the transcript CSV after every session, as if `--save-transcript` were passed.
+    pub always_save_transcript: bool,
+    /// Write markers to `<file>.json` after every session (on by default).
+    pub always_save_markers: bool,
+    /// Sink from the last session run with `--remember-devices`; preferred over the default sink.
+    pub last_sink: Option<String>,
+    /// Source from the last session run with `--remember-devices`; preferred over the default source.
+    pub last_source: Option<String>,
+    /// TUI colors: `default`, `solarized`, `mono`, or a map of roles to colors.
+    pub theme: Option<ThemeSetting>,
+    /// Profile applied when `--profile` isn't given.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default_profile: Option<String>,
+    /// Named presets; each one overrides any of the fields above.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, Value>,
+    /// Name of the profile this config was loaded with.
+    #[serde(skip)]
+    pub active_profile: Option<String>,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
             file_prefix: default_prefix(),
+            filename_template: None,
+            use_utc: false,
             whisper_model: None,
             language: Some(default_language()),
+            transcribe_prompt: None,
             backend: default_backend(),
+            speaker_labels: ["them".into(), "me".into()],
+            silence_check_secs: 5,
+            wait_for_audio_secs: 0,
+            max_recording_seconds: 8 * 3600,
+            ffmpeg_path: None,
+            pw_dump_path: None,
+            post_command: None,
+            channels: 2,
+            always_save_transcript: false,
+            always_save_markers: true,
+            last_sink: None,
+            last_source: None,
+            theme: None,
+            default_profile: None,
+            profiles: BTreeMap::new(),
+            active_profile: None,
         }
     }
 }
 
-pub fn load_config() -> Result<Config> {
+/// The `theme` config value: a built-in theme name, or colors per role
+/// (`title`, `recording`, `on-air`, `muted`, `transcript`, `log`, ...) applied
+/// over the built-in theme named by an optional `base` key.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum ThemeSetting {
+    Named(String),
+    Custom(BTreeMap<String, String>),
+}
+
+/// System-wide defaults, read before the user's config.
+pub const SYSTEM_CONFIG_PATH: &str = "/etc/rcrd/config.json";
+
+/// Loads the config with `profile` (or else its `default_profile`) overlaid.
+/// The system config, the user's config and `extra` (`--config PATH`) are
+/// merged in that order, each overriding the fields it sets; the first two
+/// may be missing, `extra` may not.
+pub fn load_config(profile: Option<&str>, extra: Option<&Path>) -> Result<Config> {
+    let layers = [PathBuf::from(SYSTEM_CONFIG_PATH), config_path()];
+    let value = load_layers(&layers, extra)?;
+    resolve_profile(value, profile).context("applying config")
+}
+
+/// The config files [`load_config`] reads, in order: those of the system
+/// and the user that exist, then `extra`.
+pub fn config_sources(extra: Option<&Path>) -> Vec<PathBuf> {
+    let mut sources: Vec<PathBuf> = [PathBuf::from(SYSTEM_CONFIG_PATH), config_path()]
+        .into_iter()
+        .filter(|path| path.exists())
+        .collect();
+    sources.extend(extra.map(Path::to_path_buf));
+    sources
+}
+
+/// Merges the files in `optional` that exist, then `required`.
+fn load_layers(optional: &[PathBuf], required: Option<&Path>) -> Result<Value> {
+    let mut merged = Value::Object(Default::default());
+    let present = optional.iter().filter(|path| path.exists());
+    for path in present.map(PathBuf::as_path).chain(required) {
+        let data = fs::read_to_string(path)
+            .with_context(|| format!("reading config {}", path.display()))?;
+        let layer: Value = serde_json::from_str(&data)
+            .with_context(|| format!("parsing config {}", path.display()))?;
+        if !layer.is_object() {
+            return Err(anyhow!("config {} must be a JSON object", path.display()));
+        }
+        merge_layer(&mut merged, layer);
+    }
+    Ok(merged)
+}
+
+/// Overlays `layer` onto `base` field by field: objects (`profiles`, a
+/// custom `theme`) are merged key by key, anything else, `null` included,
+/// replaces what `base` had. [`resolve_profile`] then resets the fields
+/// left `null` to their defaults.
+fn merge_layer(base: &mut Value, layer: Value) {
+    match (base, layer) {
+        (Value::Object(base), Value::Object(layer)) => {
+            for (key, val) in layer {
+                match base.get_mut(&key) {
+                    Some(old) => merge_layer(old, val),
+                    None => {
+                        base.insert(key, val);
+                    }
+                }
+            }
+        }
+        (base, layer) => *base = layer,
+    }
+}
+
+/// Loads the user's config as stored, without the other layers or any
+/// profile; use this for configs that are saved back.
+pub fn load_base_config() -> Result<Config> {
     let path = config_path();
     if !path.exists() {
         return Ok(Config::default());
@@ -52,6 +188,40 @@ pub fn load_config() -> Result<Config> {
     Ok(cfg)
 }
 
+/// Overlays the fields of the chosen profile onto the base config in
+/// `value`; fields that end up `null` take their default, whatever their
+/// type.
+fn resolve_profile(mut value: Value, profile: Option<&str>) -> Result<Config> {
+    let base = value
+        .as_object_mut()
+        .ok_or_else(|| anyhow!("the config must be a JSON object"))?;
+    let name = match profile {
+        Some(name) => Some(name.to_string()),
+        None => base
+            .get("default_profile")
+            .and_then(Value::as_str)
+            .map(str::to_string),
+    };
+    if let Some(name) = &name {
+        let overrides = base
+            .get("profiles")
+            .and_then(|profiles| profiles.get(name))
+            .ok_or_else(|| anyhow!("no profile named `{name}`"))?
+            .as_object()
+            .ok_or_else(|| anyhow!("profile `{name}` must be a JSON object"))?
+            .clone();
+        for (key, val) in overrides {
+            if key != "profiles" && key != "default_profile" {
+                base.insert(key, val);
+            }
+        }
+    }
+    base.retain(|_, val| !val.is_null());
+    let mut cfg: Config = serde_json::from_value(value)?;
+    cfg.active_profile = name;
+    Ok(cfg)
+}
+
 pub fn save_config(cfg: &Config) -> Result<()> {
     let path = config_path();
     if let Some(parent) = path.parent() {
@@ -68,3 +238,125 @@ pub fn config_path() -> PathBuf {
         .join("rcrd")
         .join("config.json")
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn config() -> Value {
+        json!({
+            "file_prefix": "call-",
+            "channels": 2,
+            "default_profile": "meeting",
+            "profiles": {
+                "meeting": { "language": "fr" },
+                "podcast": { "file_prefix": "pod-", "channels": 1, "always_save_transcript": true }
+            }
+        })
+    }
+
+    #[test]
+    fn profile_fields_override_the_base() {
+        let cfg = resolve_profile(config(), Some("podcast")).unwrap();
+        assert_eq!(cfg.active_profile.as_deref(), Some("podcast"));
+        assert_eq!(cfg.file_prefix, "pod-");
+        assert_eq!(cfg.channels, 1);
+        assert!(cfg.always_save_transcript);
+        // Untouched fields keep the base value, or else the default.
+        assert_eq!(cfg.language.as_deref(), Some("en"));
+        assert_eq!(cfg.profiles.len(), 2);
+    }
+
+    #[test]
+    fn default_profile_applies_without_a_flag() {
+        let cfg = resolve_profile(config(), None).unwrap();
+        assert_eq!(cfg.active_profile.as_deref(), Some("meeting"));
+        assert_eq!(cfg.language.as_deref(), Some("fr"));
+        assert_eq!(cfg.file_prefix, "call-");
+
+        let plain = resolve_profile(json!({ "file_prefix": "x-" }), None).unwrap();
+        assert_eq!(plain.active_profile, None);
+        assert_eq!(plain.file_prefix, "x-");
+    }
+
+    #[test]
+    fn later_layers_override_the_fields_they_set() {
+        let mut merged = json!({
+            "file_prefix": "site-",
+            "language": "de",
+            "speaker_labels": ["remote", "local"],
+            "profiles": { "meeting": { "language": "fr" } },
+            "theme": { "base": "solarized", "title": "blue" }
+        });
+        merge_layer(
+            &mut merged,
+            json!({
+                "language": "en",
+                "whisper_model": "/models/base.bin",
+                "speaker_labels": ["them", "me"],
+                "profiles": { "podcast": { "channels": 1 } },
+                "theme": { "title": "red" }
+            }),
+        );
+        merge_layer(&mut merged, json!({ "language": null, "channels": 1 }));
+        assert_eq!(
+            merged,
+            json!({
+                "file_prefix": "site-",
+                "language": null,
+                "whisper_model": "/models/base.bin",
+                "speaker_labels": ["them", "me"],
+                "channels": 1,
+                "profiles": { "meeting": { "language": "fr" }, "podcast": { "channels": 1 } },
+                "theme": { "base": "solarized", "title": "red" }
+            })
+        );
+        merge_layer(&mut merged, json!({ "channels": null, "backend": null }));
+        let cfg = resolve_profile(merged, None).unwrap();
+        assert_eq!(cfg.language.as_deref(), Some("en"));
+        assert_eq!(cfg.channels, 2);
+        assert_eq!(cfg.backend, "openblas");
+        assert_eq!(cfg.file_prefix, "site-");
+    }
+
+    #[test]
+    fn layers_load_in_order_and_only_the_explicit_one_is_required() {
+        let dir = std::env::temp_dir().join(format!("rcrd-config-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (system, user, extra) = (
+            dir.join("system.json"),
+            dir.join("user.json"),
+            dir.join("extra.json"),
+        );
+        fs::write(
+            &system,
+            r#"{"file_prefix": "sys-", "channels": 1, "use_utc": true}"#,
+        )
+        .unwrap();
+        fs::write(&user, r#"{"file_prefix": "me-", "channels": 2}"#).unwrap();
+        fs::write(&extra, r#"{"channels": 1}"#).unwrap();
+
+        let value = load_layers(&[system.clone(), user.clone()], Some(&extra)).unwrap();
+        let cfg = resolve_profile(value, None).unwrap();
+        assert_eq!(cfg.file_prefix, "me-");
+        assert_eq!(cfg.channels, 1);
+        assert!(cfg.use_utc);
+
+        let missing = dir.join("missing.json");
+        let value = load_layers(&[missing.clone(), user.clone()], None).unwrap();
+        assert_eq!(value, json!({"file_prefix": "me-", "channels": 2}));
+        let err = load_layers(&[system], Some(&missing)).unwrap_err();
+        assert!(err.to_string().contains("missing.json"), "{err}");
+        fs::write(&user, "[1]").unwrap();
+        assert!(load_layers(&[user], None).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn unknown_profile_is_an_error() {
+        let err = resolve_profile(config(), Some("radio")).unwrap_err();
+        assert!(err.to_string().contains("radio"));
+    }
+}
@@ -0,0 +1,127 @@
+//! Markers as a CUE sheet (`--marker-format cue`), which audio and video
+//! editors import as track or cue points: one track per marker, titled with
+//! its note.
+
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, anyhow};
+
+use crate::Marker;
+
+/// CUE times count frames of 1/75 s, as on an audio CD.
+const FRAMES_PER_SEC: f64 = 75.0;
+/// A sheet holds tracks 01 to 99.
+const MAX_TRACKS: usize = 99;
+
+/// `MM:SS:FF` for `secs`, rounded to the nearest frame. Minutes go past 99
+/// for long recordings rather than wrapping.
+pub fn cue_time(secs: f64) -> String {
+    let frames = (secs.max(0.0) * FRAMES_PER_SEC).round() as u64;
+    format!(
+        "{:02}:{:02}:{:02}",
+        frames / (75 * 60),
+        frames / 75 % 60,
+        frames % 75
+    )
+}
+
+/// Quotes a CUE string; the format has no escapes, so `"` becomes `'` and
+/// line breaks become spaces.
+fn quote(value: &str) -> String {
+    let value: String = value
+        .chars()
+        .map(|c| match c {
+            '"' => '\'',
+            c if c.is_control() => ' ',
+            c => c,
+        })
+        .collect();
+    format!("\"{value}\"")
+}
+
+/// The sheet for `audio`, referenced by file name so it can be moved along
+/// with the recording. Tracks are in order of time and named `Marker N`
+/// when the marker has no note.
+pub fn cue_sheet(markers: &[Marker], audio: &Path) -> Result<String> {
+    if markers.len() > MAX_TRACKS {
+        return Err(anyhow!(
+            "a CUE sheet holds at most {MAX_TRACKS} tracks, but there are {} markers",
+            markers.len()
+        ));
+    }
+    let mut sorted: Vec<&Marker> = markers.iter().collect();
+    sorted.sort_by(|a, b| a.timestamp.total_cmp(&b.timestamp));
+    let name = audio.file_name().map_or_else(
+        || audio.display().to_string(),
+        |n| n.to_string_lossy().into_owned(),
+    );
+    let title = audio
+        .file_stem()
+        .map_or_else(|| name.clone(), |s| s.to_string_lossy().into_owned());
+    let mut out = format!("TITLE {}\nFILE {} WAVE\n", quote(&title), quote(&name));
+    for (i, marker) in sorted.iter().enumerate() {
+        let note = match marker.note.trim() {
+            "" => format!("Marker {}", i + 1),
+            note => note.to_string(),
+        };
+        let _ = write!(
+            out,
+            "  TRACK {:02} AUDIO\n    TITLE {}\n    INDEX 01 {}\n",
+            i + 1,
+            quote(&note),
+            cue_time(marker.timestamp)
+        );
+    }
+    Ok(out)
+}
+
+/// Writes `<file>.cue` next to `outfile`.
+pub fn save_cue(markers: &[Marker], outfile: &Path) -> Result<PathBuf> {
+    let path = outfile.with_extension("cue");
+    std::fs::write(&path, cue_sheet(markers, outfile)?)
+        .with_context(|| format!("creating cue sheet {}", path.display()))?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn times_round_to_the_nearest_frame() {
+        assert_eq!(cue_time(0.0), "00:00:00");
+        assert_eq!(cue_time(1.0), "00:01:00");
+        // 0.495 and 0.525 frames.
+        assert_eq!(cue_time(0.0066), "00:00:00");
+        assert_eq!(cue_time(0.007), "00:00:01");
+        // 74.9 frames carry into the next second, and 4499.9 into the next minute.
+        assert_eq!(cue_time(0.9987), "00:01:00");
+        assert_eq!(cue_time(59.999), "01:00:00");
+        // Half a frame rounds up.
+        assert_eq!(cue_time(3600.5), "60:00:38");
+        assert_eq!(cue_time(-0.2), "00:00:00");
+    }
+
+    #[test]
+    fn one_track_per_marker_in_order() {
+        let marker = |timestamp, note: &str| Marker {
+            timestamp,
+            wall_clock: String::new(),
+            note: note.into(),
+        };
+        let sheet = cue_sheet(
+            &[marker(75.5, "say \"hi\""), marker(2.0, "")],
+            Path::new("/rec/call.ogg"),
+        )
+        .unwrap();
+        assert_eq!(
+            sheet,
+            "TITLE \"call\"\nFILE \"call.ogg\" WAVE\n\
+             \x20 TRACK 01 AUDIO\n    TITLE \"Marker 1\"\n    INDEX 01 00:02:00\n\
+             \x20 TRACK 02 AUDIO\n    TITLE \"say 'hi'\"\n    INDEX 01 01:15:38\n"
+        );
+        let many: Vec<Marker> = (0..100).map(|i| marker(f64::from(i), "")).collect();
+        assert!(cue_sheet(&many, Path::new("call.ogg")).is_err());
+    }
+}
@@ -1,70 +1,432 @@
 use anyhow::{Context, Result, anyhow};
+use serde::de::{Deserializer as _, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::process::Command;
+use std::fmt;
+use std::os::unix::fs::MetadataExt;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-#[derive(Default, Clone)]
+use crate::process::{ProcessRunner, SystemRunner, tool};
+use crate::sync::lock;
+
+/// How often `watch_node` re-reads the PipeWire graph.
+const WATCH_INTERVAL: Duration = Duration::from_secs(2);
+
+/// How long detected defaults are reused by back-to-back launches.
+const CACHE_TTL: Duration = Duration::from_secs(10);
+
+/// First pause between `pw-dump` attempts while waiting for PipeWire; it
+/// doubles after every failure up to [`MAX_RETRY_DELAY`].
+const FIRST_RETRY_DELAY: Duration = Duration::from_millis(250);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(2);
+
+#[derive(Default, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Defaults {
     pub sink: Option<String>,
     pub source: Option<String>,
+    /// Capture name for the default sink's monitor (see [`parse_defaults`]).
+    pub monitor: Option<String>,
+}
+
+/// Monitor input ffmpeg should open for `sink`: pipewire-pulse's
+/// `<sink>.monitor` unless `detected` found something else.
+pub fn monitor_for(sink: &str, detected: &Defaults) -> String {
+    detected
+        .monitor
+        .clone()
+        .filter(|_| detected.sink.as_deref() == Some(sink))
+        .unwrap_or_else(|| format!("{sink}.monitor"))
+}
+
+/// Defaults saved by a recent launch, valid for one PipeWire instance.
+#[derive(Serialize, Deserialize)]
+struct CachedDefaults {
+    /// See [`pipewire_fingerprint`].
+    fingerprint: String,
+    saved_at: u64,
+    #[serde(flatten)]
+    defaults: Defaults,
+}
+
+/// Detects the default sink and source. With `use_cache`, a result saved
+/// less than [`CACHE_TTL`] ago by the same PipeWire instance is reused
+/// instead of running `pw-dump` again.
+///
+/// While PipeWire is still starting (e.g. right after login), `pw-dump`
+/// fails or finds no defaults yet; it is retried with exponential backoff
+/// for up to `wait`.
+pub fn detect_defaults(use_cache: bool, wait: Duration) -> Result<Defaults> {
+    let fingerprint = use_cache.then(pipewire_fingerprint).flatten();
+    let path = defaults_cache_path();
+    if let Some(fingerprint) = &fingerprint
+        && let Some(data) = path.as_ref().and_then(|p| std::fs::read_to_string(p).ok())
+        && let Some(defaults) = cached_defaults(&data, fingerprint, unix_now())
+    {
+        tracing::info!("default devices from cache: {defaults:?}");
+        return Ok(defaults);
+    }
+    let defaults = retry_with_backoff(
+        wait,
+        || detect_defaults_with(&SystemRunner),
+        |d| d.sink.is_some() || d.source.is_some(),
+        thread::sleep,
+    )?;
+    if let (Some(fingerprint), Some(path)) = (fingerprint, path) {
+        let cached = CachedDefaults {
+            fingerprint,
+            saved_at: unix_now(),
+            defaults: defaults.clone(),
+        };
+        // Only a speed-up; failing to write it is not worth reporting.
+        if let Some(dir) = path.parent() {
+            let _ = std::fs::create_dir_all(dir);
+        }
+        if let Ok(data) = serde_json::to_string(&cached) {
+            let _ = std::fs::write(&path, data);
+        }
+    }
+    Ok(defaults)
 }
 
-pub fn detect_defaults() -> Result<Defaults> {
-    let output = Command::new("pw-dump")
-        .output()
+/// Calls `attempt` until it returns an `Ok` value that is `ready`, sleeping
+/// between tries with a doubling delay until `budget` is used up. The last
+/// result is returned either way.
+fn retry_with_backoff<T>(
+    budget: Duration,
+    mut attempt: impl FnMut() -> Result<T>,
+    ready: impl Fn(&T) -> bool,
+    mut sleep: impl FnMut(Duration),
+) -> Result<T> {
+    let mut waited = Duration::ZERO;
+    let mut delay = FIRST_RETRY_DELAY;
+    loop {
+        let result = attempt();
+        if result.as_ref().is_ok_and(&ready) || waited >= budget {
+            return result;
+        }
+        match &result {
+            Err(err) => tracing::info!("PipeWire not ready ({err:#}), retrying in {delay:?}"),
+            Ok(_) => tracing::info!("no default devices yet, retrying in {delay:?}"),
+        }
+        let pause = delay.min(budget - waited);
+        sleep(pause);
+        waited += pause;
+        delay = (delay * 2).min(MAX_RETRY_DELAY);
+    }
+}
+
+/// The cached defaults in `data` if they are fresh and from this PipeWire.
+fn cached_defaults(data: &str, fingerprint: &str, now: u64) -> Option<Defaults> {
+    let cached: CachedDefaults = serde_json::from_str(data).ok()?;
+    let fresh = now.saturating_sub(cached.saved_at) < CACHE_TTL.as_secs();
+    (fresh && cached.fingerprint == fingerprint).then_some(cached.defaults)
+}
+
+fn defaults_cache_path() -> Option<PathBuf> {
+    Some(dirs::cache_dir()?.join("rcrd").join("defaults.json"))
+}
+
+/// Identifies the running PipeWire daemon by its socket's inode and mtime,
+/// which change whenever it restarts. `None` when the socket is not found.
+fn pipewire_fingerprint() -> Option<String> {
+    let runtime = std::env::var_os("XDG_RUNTIME_DIR")?;
+    let socket = std::env::var_os("PIPEWIRE_REMOTE").unwrap_or_else(|| "pipewire-0".into());
+    let meta = std::fs::metadata(PathBuf::from(runtime).join(socket)).ok()?;
+    Some(format!(
+        "{}:{}.{}",
+        meta.ino(),
+        meta.mtime(),
+        meta.mtime_nsec()
+    ))
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+pub fn detect_defaults_with(runner: &dyn ProcessRunner) -> Result<Defaults> {
+    let output = runner
+        .output(&mut tool("pw-dump"))
         .context("pw-dump failed (is pipewire-utils installed?)")?;
     if !output.status.success() {
+        tracing::warn!("pw-dump exited with {}", output.status);
         return Err(anyhow!("pw-dump exited with {}", output.status));
     }
-    let root: Value =
-        serde_json::from_slice(&output.stdout).context("pw-dump returned invalid JSON")?;
-    let mut defaults = Defaults::default();
-    let Some(array) = root.as_array() else {
-        return Ok(defaults);
-    };
+    let defaults = parse_defaults(&output.stdout)?;
+    tracing::info!(
+        "default devices: sink={:?} source={:?} monitor={:?}",
+        defaults.sink,
+        defaults.source,
+        defaults.monitor
+    );
+    Ok(defaults)
+}
 
-    for obj in array {
-        let Some(obj_type) = obj.get("type").and_then(Value::as_str) else {
-            continue;
-        };
-        if obj_type != "PipeWire:Interface:Metadata" {
-            continue;
+/// Extracts the default sink/source names from `pw-dump` JSON.
+///
+/// The metadata object named `default` is preferred over other metadata
+/// (`settings`, `route-settings`, ...), and the current default over the
+/// configured one. Depending on the PipeWire version the entries sit in
+/// `metadata` or `info.items`, and values are objects or JSON strings.
+///
+/// The monitor is looked up in the same pass. A real `Audio/Sink` node
+/// gets its monitor from pipewire-pulse as `<sink>.monitor`. Virtual sinks
+/// without one (e.g. loopback pairs) often come with a separate source node
+/// named `<sink>.monitor`, `<sink>-monitor` or `<sink>_monitor`, which is
+/// used instead. `None` when neither is found.
+///
+/// Objects are decoded one at a time into a shape holding only what is
+/// needed here, and once both current defaults of the `default` metadata
+/// are known and the sink's node has been seen, the rest of the dump is
+/// skipped without being decoded. A dump that breaks after the defaults
+/// were settled still returns them, only without a monitor.
+pub fn parse_defaults(json: &[u8]) -> Result<Defaults> {
+    let mut found = Found::default();
+    let mut de = serde_json::Deserializer::from_slice(json);
+    let parsed = de
+        .deserialize_any(DefaultsVisitor(&mut found))
+        .and_then(|()| de.end());
+    if let Err(err) = parsed {
+        if !found.defaults_settled() {
+            return Err(err).context("pw-dump returned invalid JSON");
         }
-        let items = obj.get("metadata").and_then(Value::as_array).or_else(|| {
-            obj.get("info")
-                .and_then(|info| info.get("items"))
-                .and_then(Value::as_array)
-        });
-        let Some(items) = items else {
-            continue;
-        };
+        tracing::warn!("cannot look up the monitor of the default sink: {err}");
+        found.nodes.clear();
+    }
+    Ok(found.into_defaults())
+}
 
-        for item in items {
-            let Some(key) = item.get("key").and_then(Value::as_str) else {
-                continue;
-            };
-            match key {
-                "default.audio.sink" | "default.configured.audio.sink" => {
-                    if defaults.sink.is_none() {
-                        defaults.sink = extract_name(item.get("value"));
-                    }
+/// The parts of a `pw-dump` object `parse_defaults` looks at; everything
+/// else (node info, params, ...) is skipped by serde without allocating.
+#[derive(Deserialize)]
+struct PwObject {
+    #[serde(rename = "type")]
+    kind: Option<String>,
+    props: Option<PwProps>,
+    metadata: Option<Vec<PwItem>>,
+    info: Option<PwInfo>,
+}
+
+#[derive(Deserialize)]
+struct PwInfo {
+    props: Option<PwProps>,
+    items: Option<Vec<PwItem>>,
+}
+
+#[derive(Deserialize)]
+struct PwProps {
+    #[serde(rename = "metadata.name")]
+    metadata_name: Option<String>,
+    #[serde(rename = "node.name")]
+    node_name: Option<String>,
+    #[serde(rename = "media.class")]
+    media_class: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct PwItem {
+    key: Option<String>,
+    value: Option<Value>,
+}
+
+/// Best candidates so far: (rank, name) per role; lower ranks win. `nodes`
+/// holds the (name, class) of every audio sink and source seen.
+#[derive(Default)]
+struct Found {
+    sink: Option<(u8, String)>,
+    source: Option<(u8, String)>,
+    nodes: Vec<(String, String)>,
+}
+
+impl Found {
+    fn add(&mut self, obj: PwObject) {
+        match obj.kind.as_deref() {
+            Some("PipeWire:Interface:Metadata") => self.add_metadata(obj),
+            Some("PipeWire:Interface:Node") => {
+                if let Some(PwProps {
+                    node_name: Some(name),
+                    media_class: Some(class),
+                    ..
+                }) = obj.info.and_then(|info| info.props)
+                    && (class.starts_with("Audio/Sink") || class.starts_with("Audio/Source"))
+                {
+                    self.nodes.push((name, class));
                 }
-                "default.audio.source" | "default.configured.audio.source" => {
-                    if defaults.source.is_none() {
-                        defaults.source = extract_name(item.get("value"));
+            }
+            _ => {}
+        }
+    }
+
+    fn add_metadata(&mut self, obj: PwObject) {
+        let (info_props, info_items) = match obj.info {
+            Some(info) => (info.props, info.items),
+            None => (None, None),
+        };
+        let name = obj
+            .props
+            .and_then(|p| p.metadata_name)
+            .or_else(|| info_props.and_then(|p| p.metadata_name));
+        let other = name.as_deref() != Some("default");
+        for item in obj.metadata.or(info_items).into_iter().flatten() {
+            let (slot, configured) = match item.key.as_deref() {
+                Some("default.audio.sink") => (&mut self.sink, false),
+                Some("default.configured.audio.sink") => (&mut self.sink, true),
+                Some("default.audio.source") => (&mut self.source, false),
+                Some("default.configured.audio.source") => (&mut self.source, true),
+                _ => continue,
+            };
+            let rank = other as u8 * 2 + configured as u8;
+            if slot.as_ref().is_none_or(|(best, _)| rank < *best)
+                && let Some(name) = extract_name(item.value.as_ref())
+            {
+                *slot = Some((rank, name));
+            }
+        }
+    }
+
+    /// No metadata later in the dump can beat the defaults found.
+    fn defaults_settled(&self) -> bool {
+        matches!((&self.sink, &self.source), (Some((0, _)), Some((0, _))))
+    }
+
+    /// Nothing later in the dump can change the result either: the sink's
+    /// own node settles its monitor.
+    fn settled(&self) -> bool {
+        self.defaults_settled()
+            && self.sink.as_ref().is_some_and(|(_, sink)| {
+                self.nodes
+                    .iter()
+                    .any(|(name, class)| name == sink && class.starts_with("Audio/Sink"))
+            })
+    }
+
+    fn monitor(&self, sink: &str) -> Option<String> {
+        if self
+            .nodes
+            .iter()
+            .any(|(name, class)| name == sink && class.starts_with("Audio/Sink"))
+        {
+            return Some(format!("{sink}.monitor"));
+        }
+        let candidates =
+            [".monitor", "-monitor", "_monitor"].map(|suffix| format!("{sink}{suffix}"));
+        self.nodes
+            .iter()
+            .find(|(name, class)| class.starts_with("Audio/Source") && candidates.contains(name))
+            .map(|(name, _)| name.clone())
+    }
+
+    fn into_defaults(self) -> Defaults {
+        let monitor = self.sink.as_ref().and_then(|(_, sink)| self.monitor(sink));
+        Defaults {
+            sink: self.sink.map(|(_, name)| name),
+            source: self.source.map(|(_, name)| name),
+            monitor,
+        }
+    }
+}
+
+/// Fills in the `Found` it borrows, so whatever was found before a decode
+/// error is still there afterwards.
+struct DefaultsVisitor<'a>(&'a mut Found);
+
+impl<'de> Visitor<'de> for DefaultsVisitor<'_> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a pw-dump object array")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        while !self.0.settled() {
+            match seq.next_element::<PwObject>()? {
+                Some(obj) => self.0.add(obj),
+                None => return Ok(()),
+            }
+        }
+        while seq.next_element::<IgnoredAny>()?.is_some() {}
+        Ok(())
+    }
+
+    // Anything but an array has no defaults in it.
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        while map.next_entry::<IgnoredAny, IgnoredAny>()?.is_some() {}
+        Ok(())
+    }
+
+    fn visit_unit<E>(self) -> Result<(), E> {
+        Ok(())
+    }
+}
+
+/// Names (`node.name`) of every node in `pw-dump` JSON.
+pub fn parse_node_names(json: &[u8]) -> Result<Vec<String>> {
+    let root: Value = serde_json::from_slice(json).context("pw-dump returned invalid JSON")?;
+    let names = root
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|obj| obj.get("type").and_then(Value::as_str) == Some("PipeWire:Interface:Node"))
+        .filter_map(|obj| obj.pointer("/info/props/node.name")?.as_str())
+        .map(str::to_owned)
+        .collect();
+    Ok(names)
+}
+
+pub fn node_names_with(runner: &dyn ProcessRunner) -> Result<Vec<String>> {
+    let output = runner
+        .output(&mut tool("pw-dump"))
+        .context("pw-dump failed (is pipewire-utils installed?)")?;
+    if !output.status.success() {
+        return Err(anyhow!("pw-dump exited with {}", output.status));
+    }
+    parse_node_names(&output.stdout)
+}
+
+/// Polls PipeWire until `stop` is set and raises `lost` while the node
+/// `name` is missing from the graph (e.g. a USB mic was unplugged). Failed
+/// polls leave the flag untouched.
+pub fn watch_node(
+    name: String,
+    lost: Arc<AtomicBool>,
+    stop: Arc<AtomicBool>,
+    recent_logs: Arc<Mutex<Vec<String>>>,
+) -> JoinHandle<()> {
+    thread::spawn(move || {
+        let mut last_poll: Option<Instant> = None;
+        while !stop.load(Ordering::Relaxed) {
+            if last_poll.is_none_or(|t| t.elapsed() >= WATCH_INTERVAL) {
+                last_poll = Some(Instant::now());
+                if let Ok(names) = node_names_with(&SystemRunner) {
+                    let present = names.contains(&name);
+                    if lost.swap(!present, Ordering::Relaxed) == present {
+                        tracing::warn!("node {name}: present={present}");
+                        lock(&recent_logs).push(if present {
+                            format!("Input {name} is back")
+                        } else {
+                            format!("Input {name} disappeared; the mic is not being recorded")
+                        });
                     }
                 }
-                _ => {}
             }
+            thread::sleep(Duration::from_millis(100));
         }
-    }
-    Ok(defaults)
+    })
 }
 
 fn extract_name(val: Option<&Value>) -> Option<String> {
-    let Some(val) = val else {
-        return None;
-    };
+    let val = val?;
     if let Some(s) = val.as_str() {
+        // Some versions store the `{"name": ...}` object as a JSON string.
+        if let Ok(inner @ Value::Object(_)) = serde_json::from_str::<Value>(s) {
+            return extract_name(Some(&inner));
+        }
         return Some(s.to_owned());
     }
     if let Some(obj) = val.as_object() {
@@ -77,3 +439,223 @@ fn extract_name(val: Option<&Value>) -> Option<String> {
     }
     None
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::mock::MockRunner;
+    use std::path::Path;
+
+    const PW_DUMP: &str = r#"[
+        {"id": 30, "type": "PipeWire:Interface:Node", "info": {}},
+        {"id": 40, "type": "PipeWire:Interface:Metadata", "metadata": [
+            {"subject": 0, "key": "default.audio.sink", "type": "Spa:String:JSON",
+             "value": {"name": "alsa_output.usb"}},
+            {"subject": 0, "key": "default.audio.source", "type": "Spa:String:JSON",
+             "value": {"name": "alsa_input.usb"}}
+        ]}
+    ]"#;
+
+    #[test]
+    fn detects_defaults_through_runner() {
+        let runner = MockRunner::with_stdout(PW_DUMP);
+        let defaults = detect_defaults_with(&runner).unwrap();
+        assert_eq!(defaults.sink.as_deref(), Some("alsa_output.usb"));
+        assert_eq!(defaults.source.as_deref(), Some("alsa_input.usb"));
+        assert_eq!(runner.calls.lock().unwrap()[0], ["pw-dump"]);
+    }
+
+    #[test]
+    fn retries_with_backoff_until_ready_or_out_of_time() {
+        let mut sleeps = Vec::new();
+        let mut attempts = 0;
+        let result = retry_with_backoff(
+            Duration::from_secs(5),
+            || {
+                attempts += 1;
+                match attempts {
+                    1 | 2 => Err(anyhow!("pw-dump exited with 1")),
+                    3 => Ok(None),
+                    n => Ok(Some(n)),
+                }
+            },
+            Option::is_some,
+            |d| sleeps.push(d),
+        );
+        assert_eq!(result.unwrap(), Some(4));
+        assert_eq!(sleeps, [250, 500, 1000].map(Duration::from_millis));
+
+        // Out of budget: the last pause is cut short and the last error returned.
+        let mut sleeps = Vec::new();
+        let result: Result<()> = retry_with_backoff(
+            Duration::from_millis(600),
+            || Err(anyhow!("pw-dump exited with 1")),
+            |_| true,
+            |d| sleeps.push(d),
+        );
+        assert!(result.is_err());
+        assert_eq!(sleeps, [250, 350].map(Duration::from_millis));
+
+        // No budget: a single attempt, as before.
+        let mut attempts = 0;
+        let result = retry_with_backoff(
+            Duration::ZERO,
+            || {
+                attempts += 1;
+                Ok(None::<u8>)
+            },
+            Option::is_some,
+            |_| panic!("should not sleep"),
+        );
+        assert_eq!((result.unwrap(), attempts), (None, 1));
+    }
+
+    fn fixture(name: &str) -> Vec<u8> {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures")
+            .join(name);
+        std::fs::read(&path).unwrap_or_else(|err| panic!("{}: {err}", path.display()))
+    }
+
+    #[test]
+    fn prefers_the_current_default_over_the_configured_one() {
+        let defaults = parse_defaults(&fixture("pw-dump-0.3.json")).unwrap();
+        assert_eq!(
+            defaults.sink.as_deref(),
+            Some("bluez_output.AA_BB_CC_DD_EE_FF.1")
+        );
+        assert_eq!(
+            defaults.source.as_deref(),
+            Some("alsa_input.pci-0000_00_1f.3.analog-stereo")
+        );
+    }
+
+    #[test]
+    fn parses_values_stored_as_json_strings() {
+        // Also checks that the `default` metadata wins over `route-settings`
+        // even though that comes first in the dump.
+        let defaults = parse_defaults(&fixture("pw-dump-1.0.json")).unwrap();
+        assert_eq!(
+            defaults.sink.as_deref(),
+            Some("alsa_output.usb-Focusrite_Scarlett_2i2-00.analog-stereo")
+        );
+        assert_eq!(
+            defaults.source.as_deref(),
+            Some("alsa_input.usb-Focusrite_Scarlett_2i2-00.analog-stereo")
+        );
+    }
+
+    #[test]
+    fn falls_back_to_info_items_and_configured_defaults() {
+        let defaults = parse_defaults(&fixture("pw-dump-items.json")).unwrap();
+        assert_eq!(defaults.sink.as_deref(), Some("alsa_output.configured"));
+        assert_eq!(defaults.source.as_deref(), Some("alsa_input.configured"));
+    }
+
+    #[test]
+    fn cached_defaults_expire_and_follow_pipewire_restarts() {
+        let data = r#"{"fingerprint":"12:34.5","saved_at":1000,"sink":"s","source":null}"#;
+        let expected = Defaults {
+            sink: Some("s".into()),
+            ..Defaults::default()
+        };
+        assert_eq!(cached_defaults(data, "12:34.5", 1005), Some(expected));
+        assert_eq!(cached_defaults(data, "12:34.5", 1010), None);
+        assert_eq!(cached_defaults(data, "99:34.5", 1005), None);
+        assert_eq!(cached_defaults("garbage", "12:34.5", 1005), None);
+    }
+
+    #[test]
+    fn stops_decoding_once_both_defaults_are_settled() {
+        // The trailing object would fail to decode as a pw-dump object, so
+        // this only keeps the monitor if it is skipped.
+        let json = br#"[
+            {"type": "PipeWire:Interface:Node",
+             "info": {"props": {"node.name": "sink", "media.class": "Audio/Sink"}}},
+            {"type": "PipeWire:Interface:Metadata", "props": {"metadata.name": "default"},
+             "metadata": [
+                {"key": "default.audio.sink", "value": {"name": "sink"}},
+                {"key": "default.audio.source", "value": {"name": "source"}}
+             ]},
+            {"type": 42, "metadata": "not a list"}
+        ]"#;
+        let defaults = parse_defaults(json).unwrap();
+        assert_eq!(defaults.sink.as_deref(), Some("sink"));
+        assert_eq!(defaults.source.as_deref(), Some("source"));
+        assert_eq!(defaults.monitor.as_deref(), Some("sink.monitor"));
+    }
+
+    #[test]
+    fn a_broken_dump_after_the_defaults_only_loses_the_monitor() {
+        let json = br#"[
+            {"type": "PipeWire:Interface:Metadata", "props": {"metadata.name": "default"},
+             "metadata": [
+                {"key": "default.audio.sink", "value": {"name": "sink"}},
+                {"key": "default.audio.source", "value": {"name": "source"}}
+             ]},
+            {"type": "PipeWire:Interface:Node",
+             "info": {"props": {"node.name": "sink-monitor", "media.class": "Audio/Source"}}},
+            {"type": 42}
+        ]"#;
+        let defaults = parse_defaults(json).unwrap();
+        assert_eq!(defaults.sink.as_deref(), Some("sink"));
+        assert_eq!(defaults.source.as_deref(), Some("source"));
+        assert_eq!(defaults.monitor, None);
+
+        assert!(parse_defaults(br#"[{"type": 42}]"#).is_err());
+    }
+
+    #[test]
+    fn finds_the_monitor_of_real_and_virtual_sinks() {
+        let monitor = |sink: &str| {
+            let json = format!(
+                r#"[
+                {{"type": "PipeWire:Interface:Node",
+                 "info": {{"props": {{"node.name": "alsa_output.pci", "media.class": "Audio/Sink"}}}}}},
+                {{"type": "PipeWire:Interface:Node",
+                 "info": {{"props": {{"node.name": "call-sink", "media.class": "Stream/Input/Audio"}}}}}},
+                {{"type": "PipeWire:Interface:Node",
+                 "info": {{"props": {{"node.name": "call-sink-monitor", "media.class": "Audio/Source/Virtual"}}}}}},
+                {{"type": "PipeWire:Interface:Metadata", "props": {{"metadata.name": "default"}},
+                 "metadata": [{{"key": "default.audio.sink", "value": {{"name": "{sink}"}}}}]}}
+            ]"#
+            );
+            parse_defaults(json.as_bytes()).unwrap().monitor
+        };
+        assert_eq!(
+            monitor("alsa_output.pci").as_deref(),
+            Some("alsa_output.pci.monitor")
+        );
+        assert_eq!(monitor("call-sink").as_deref(), Some("call-sink-monitor"));
+        assert_eq!(monitor("missing"), None);
+
+        let detected = Defaults {
+            sink: Some("call-sink".into()),
+            monitor: Some("call-sink-monitor".into()),
+            ..Defaults::default()
+        };
+        assert_eq!(monitor_for("call-sink", &detected), "call-sink-monitor");
+        assert_eq!(monitor_for("other", &detected), "other.monitor");
+    }
+
+    #[test]
+    fn lists_node_names() {
+        let json = br#"[
+            {"id": 31, "type": "PipeWire:Interface:Node",
+             "info": {"props": {"node.name": "alsa_input.usb", "media.class": "Audio/Source"}}},
+            {"id": 32, "type": "PipeWire:Interface:Port",
+             "info": {"props": {"node.name": "not-a-node"}}},
+            {"id": 33, "type": "PipeWire:Interface:Node", "info": {"props": {}}}
+        ]"#;
+        assert_eq!(parse_node_names(json).unwrap(), ["alsa_input.usb"]);
+    }
+
+    #[test]
+    fn failing_pw_dump_is_an_error() {
+        let runner = MockRunner {
+            exit_code: 1,
+            ..MockRunner::default()
+        };
+        assert!(detect_defaults_with(&runner).is_err());
+    }
+}
@@ -0,0 +1,66 @@
+//! Free space where the recording is written: checked before it starts and
+//! watched while it runs, so it stops before the disk fills and ffmpeg is
+//! left unable to finish the file.
+
+use std::ffi::CString;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+
+use anyhow::{Context, Result, anyhow};
+
+/// Below this much free space the recording shows a warning.
+pub const LOW_SPACE_WARN: u64 = 1 << 30;
+
+/// Below this much free space the recording is stopped, leaving room for
+/// the trailer, the sidecars and the joined pieces of a paused recording.
+pub const LOW_SPACE_STOP: u64 = 64 << 20;
+
+/// Bytes available to us on the filesystem `path` is (or will be) written
+/// to: that of its nearest existing ancestor directory.
+pub fn free_bytes(path: &Path) -> Result<u64> {
+    let dir = path
+        .ancestors()
+        .skip(1)
+        .map(|dir| {
+            if dir.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                dir
+            }
+        })
+        .find(|dir| dir.is_dir())
+        .ok_or_else(|| anyhow!("no directory to write {} to", path.display()))?;
+    let c_dir = CString::new(dir.as_os_str().as_bytes())
+        .with_context(|| format!("invalid path {}", dir.display()))?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    // SAFETY: statvfs(3) only fills in `stat` for a NUL-terminated path.
+    if unsafe { libc::statvfs(c_dir.as_ptr(), &mut stat) } != 0 {
+        return Err(std::io::Error::last_os_error())
+            .with_context(|| format!("checking free space in {}", dir.display()));
+    }
+    Ok(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+/// `1.5 GB`, `320 MB`: decimal units, as disk sizes are usually quoted.
+pub fn format_bytes(bytes: u64) -> String {
+    match bytes {
+        b if b >= 1_000_000_000 => format!("{:.1} GB", b as f64 / 1e9),
+        b if b >= 1_000_000 => format!("{} MB", b / 1_000_000),
+        b => format!("{} kB", b.div_ceil(1000)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checks_the_nearest_existing_directory() {
+        let dir = std::env::temp_dir();
+        assert!(free_bytes(&dir.join("not/yet/created/call.ogg")).is_ok());
+        assert!(free_bytes(Path::new("call.ogg")).is_ok());
+        assert_eq!(format_bytes(1_540_000_000), "1.5 GB");
+        assert_eq!(format_bytes(64 << 20), "67 MB");
+        assert_eq!(format_bytes(1), "1 kB");
+    }
+}
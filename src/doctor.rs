@@ -0,0 +1,69 @@
+use std::path::Path;
+use std::process::Command;
+
+use rcrd::output::git_revision;
+use rcrd::process::{find_in_path, tool, tool_path};
+use rcrd::transcript::vulkan_available;
+
+/// `rcrd doctor`: prints the versions and tools rcrd depends on, for bug reports.
+pub fn run_doctor() {
+    println!("rcrd {}", env!("CARGO_PKG_VERSION"));
+    println!(
+        "  git rev: {}",
+        git_revision().as_deref().unwrap_or("unknown")
+    );
+
+    let ffmpeg = tool_path("ffmpeg")
+        .and_then(|_| first_line(tool("ffmpeg").arg("-version")))
+        .unwrap_or_else(|| "not found".into());
+    println!("ffmpeg: {ffmpeg}");
+
+    match find_in_path("whisper-cli") {
+        Some(path) => {
+            println!("whisper-cli: {}", path.display());
+            let libs = linked_libraries(&path);
+            let has = |name: &str| libs.as_deref().map(|l| l.contains(name));
+            println!("  vulkan backend:   {}", availability(has("libvulkan")));
+            println!("  openblas backend: {}", availability(has("openblas")));
+        }
+        None => println!("whisper-cli: not found (live transcription unavailable)"),
+    }
+    println!(
+        "  vulkan GPU:       {}",
+        if vulkan_available() { "yes" } else { "no" }
+    );
+
+    for tool in ["pw-dump", "pactl"] {
+        let found = tool_path(tool)
+            .map(|p| p.display().to_string())
+            .unwrap_or_else(|| "not found".into());
+        println!("{tool}: {found}");
+    }
+}
+
+fn first_line(cmd: &mut Command) -> Option<String> {
+    let output = cmd.output().ok()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    stdout
+        .lines()
+        .map(str::trim)
+        .find(|l| !l.is_empty())
+        .map(str::to_owned)
+}
+
+/// `ldd` output for `binary`, used to tell which whisper backends it was built with.
+fn linked_libraries(binary: &Path) -> Option<String> {
+    let output = Command::new("ldd").arg(binary).output().ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn availability(linked: Option<bool>) -> &'static str {
+    match linked {
+        Some(true) => "yes",
+        Some(false) => "no",
+        None => "unknown (ldd failed)",
+    }
+}
@@ -0,0 +1,73 @@
+//! Failure classes the `rcrd` binary reports through distinct exit codes.
+
+use std::fmt;
+
+/// What went wrong, attached to an [`anyhow::Error`] as context
+/// (`.context(ErrorKind::Ffmpeg)`) so callers can tell a missing device from
+/// an ffmpeg crash without matching on messages.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// Conflicting or out-of-range options.
+    Usage,
+    /// No sink, monitor or microphone to record from.
+    DeviceNotFound,
+    /// ffmpeg could not be started or exited with an error.
+    Ffmpeg,
+    /// The model or whisper-cli failed.
+    Transcriber,
+    /// The output can't be written: its directory isn't writable or has
+    /// less free space than `--require-space`.
+    Output,
+    /// The user discarded the recording.
+    Aborted,
+}
+
+impl ErrorKind {
+    /// Process exit code; 1 is left for errors of no particular kind and 2
+    /// matches clap's own usage errors.
+    pub fn exit_code(self) -> i32 {
+        match self {
+            ErrorKind::Usage => 2,
+            ErrorKind::DeviceNotFound => 3,
+            ErrorKind::Ffmpeg => 4,
+            ErrorKind::Transcriber => 5,
+            ErrorKind::Aborted => 6,
+            ErrorKind::Output => 7,
+        }
+    }
+
+    /// The kind attached anywhere in `err`'s context chain.
+    pub fn of(err: &anyhow::Error) -> Option<Self> {
+        err.downcast_ref::<ErrorKind>().copied()
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ErrorKind::Usage => "invalid options",
+            ErrorKind::DeviceNotFound => "audio device not found",
+            ErrorKind::Ffmpeg => "ffmpeg failed",
+            ErrorKind::Transcriber => "transcription failed",
+            ErrorKind::Aborted => "aborted",
+            ErrorKind::Output => "cannot write the output",
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::anyhow;
+
+    use super::*;
+
+    #[test]
+    fn kind_is_found_below_later_context() {
+        let err = anyhow!("pw-dump: no default sink")
+            .context(ErrorKind::DeviceNotFound)
+            .context("starting the recording");
+        assert_eq!(ErrorKind::of(&err), Some(ErrorKind::DeviceNotFound));
+        assert_eq!(ErrorKind::of(&err).unwrap().exit_code(), 3);
+        assert_eq!(ErrorKind::of(&anyhow!("other")), None);
+    }
+}
@@ -0,0 +1,154 @@
+use std::io::{self, BufRead, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use serde::Serialize;
+
+use rcrd::ffmpeg::Levels;
+
+use crate::ui::RecorderState;
+
+/// How often a `level` event is emitted.
+const LEVEL_INTERVAL: Duration = Duration::from_secs(1);
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// One line of `--json-events` output.
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event<'a> {
+    RecordingStarted {
+        output: String,
+        monitor: Option<&'a str>,
+        monitors: &'a [String],
+        mic: Option<&'a str>,
+    },
+    MarkerAdded {
+        timestamp: f64,
+        wall_clock: &'a str,
+        note: &'a str,
+    },
+    TranscriptSegment {
+        start_ms: i64,
+        end_ms: i64,
+        text: &'a str,
+    },
+    Level(Levels),
+    RecordingStopped {
+        elapsed: f64,
+        markers: usize,
+    },
+}
+
+pub fn emit(event: &Event) {
+    if let Ok(line) = serde_json::to_string(event) {
+        let mut out = io::stdout().lock();
+        let _ = writeln!(out, "{line}");
+        let _ = out.flush();
+    }
+}
+
+extern "C" fn on_sigint(_: libc::c_int) {
+    INTERRUPTED.store(true, Ordering::Relaxed);
+}
+
+/// Headless replacement for `run_app`: emits JSON lines on stdout and accepts
+/// `marker [note]` / `stop` / `discard` commands on stdin.
+pub fn run_events(mut state: RecorderState) -> Result<RecorderState> {
+    unsafe {
+        libc::signal(libc::SIGINT, on_sigint as *const () as libc::sighandler_t);
+    }
+
+    let (tx, rx) = mpsc::channel::<String>();
+    thread::spawn(move || {
+        for line in io::stdin().lock().lines().map_while(Result::ok) {
+            if tx.send(line).is_err() {
+                break;
+            }
+        }
+    });
+
+    state.transcribe_enabled = state.handle.set_transcribing(true);
+
+    emit(&Event::RecordingStarted {
+        output: state.handle.output().display().to_string(),
+        monitor: state.handle.monitor(),
+        monitors: state.handle.monitors(),
+        mic: state.handle.mic(),
+    });
+
+    let segments = state.handle.subscribe();
+    let mut last_level = Instant::now();
+
+    while state.running {
+        while let Ok(line) = rx.try_recv() {
+            let line = line.trim();
+            let (cmd, rest) = line.split_once(' ').unwrap_or((line, ""));
+            match cmd {
+                "marker" => {
+                    let note = if rest.trim().is_empty() {
+                        format!("Marker #{}", state.handle.markers().len() + 1)
+                    } else {
+                        rest.trim().to_string()
+                    };
+                    let marker = state.handle.add_marker(note);
+                    emit(&Event::MarkerAdded {
+                        timestamp: marker.timestamp,
+                        wall_clock: &marker.wall_clock,
+                        note: &marker.note,
+                    });
+                }
+                "pause" | "resume" => {
+                    let res = if cmd == "pause" {
+                        state.handle.pause()
+                    } else {
+                        state.handle.resume()
+                    };
+                    if let Err(err) = res {
+                        state.handle.log(format!("{cmd}: {err:#}"));
+                    }
+                }
+                "stop" | "quit" => state.running = false,
+                "discard" => {
+                    state.discard = true;
+                    state.running = false;
+                }
+                _ => {}
+            }
+        }
+
+        while let Ok(seg) = segments.try_recv() {
+            emit(&Event::TranscriptSegment {
+                start_ms: seg.start_ms,
+                end_ms: seg.end_ms,
+                text: &seg.text,
+            });
+        }
+
+        if last_level.elapsed() >= LEVEL_INTERVAL {
+            state.handle.decay_levels(last_level.elapsed());
+            last_level = Instant::now();
+            emit(&Event::Level(state.handle.levels()));
+        }
+
+        if state.handle.is_finished()? || INTERRUPTED.load(Ordering::Relaxed) {
+            state.running = false;
+        }
+        if let Some(duration) = state.handle.duration()
+            && state.handle.elapsed() >= duration
+        {
+            state.running = false;
+        }
+
+        thread::sleep(Duration::from_millis(100));
+    }
+
+    emit(&Event::RecordingStopped {
+        elapsed: state.handle.elapsed().as_secs_f64(),
+        markers: state.handle.markers().len(),
+    });
+    Ok(state)
+}
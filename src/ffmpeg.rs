@@ -1,105 +1,1571 @@
-use std::fs::{self, OpenOptions};
-use std::io::{BufRead, BufReader, Write};
-use std::path::Path;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::fd::AsRawFd;
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
 use std::process::{Child, Command, Stdio};
 use std::sync::{Arc, Mutex};
 use std::thread;
+use std::time::{Duration, Instant};
 
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, anyhow};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
 
-pub fn prepare_mic_control() -> Result<std::path::PathBuf> {
-    let dir = std::env::temp_dir().join("rcrd-mic");
-    fs::create_dir_all(&dir)?;
-    let cmd_path = dir.join(format!("mic-{}.cmd", std::process::id()));
+use crate::process::{ProcessRunner, argv, tool};
+use crate::sync::{lock, lock_logged};
 
-    // Initialize with unmute command
-    let mut f = OpenOptions::new()
-        .create(true)
-        .write(true)
-        .truncate(true)
-        .open(&cmd_path)?;
-    writeln!(f, "0.0 volume@micvol volume 1.0")?;
+/// Latest peak and RMS levels of the recorded mix per channel, in dBFS
+/// (`-inf` for silence). Updated about ten times a second.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct Levels {
+    pub peak_l: f32,
+    pub peak_r: f32,
+    pub rms_l: f32,
+    pub rms_r: f32,
+    /// Peak of the tapped monitor alone, before the mic is mixed in.
+    pub monitor_peak: f32,
+    /// Highest recent peak per channel, falling back over
+    /// [`PEAK_HOLD_FALL`] (see [`decay`](Self::decay)) so short peaks stay
+    /// visible on a meter.
+    pub peak_hold_l: f32,
+    pub peak_hold_r: f32,
+    /// Times the peak of either channel rose to the clip threshold
+    /// ([`FfmpegOptions::clip_threshold_db`]).
+    pub clips: u32,
+}
+
+/// What ffmpeg reports about the file it is writing, from `-progress`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Progress {
+    /// Audio written so far, by ffmpeg's own clock.
+    pub time: Option<Duration>,
+    /// Size of the file so far.
+    pub size_bytes: Option<u64>,
+    /// Average bitrate of the file so far.
+    pub bitrate_kbps: Option<f64>,
+}
+
+/// Time a held peak takes to fall through the [`PEAK_HOLD_RANGE_DB`] a
+/// meter shows.
+pub const PEAK_HOLD_FALL: Duration = Duration::from_millis(1500);
+/// Range below 0 dBFS a held peak falls through before it is dropped.
+pub const PEAK_HOLD_RANGE_DB: f32 = 60.0;
+
+impl Default for Levels {
+    fn default() -> Self {
+        Self {
+            peak_l: f32::NEG_INFINITY,
+            peak_r: f32::NEG_INFINITY,
+            rms_l: f32::NEG_INFINITY,
+            rms_r: f32::NEG_INFINITY,
+            monitor_peak: f32::NEG_INFINITY,
+            peak_hold_l: f32::NEG_INFINITY,
+            peak_hold_r: f32::NEG_INFINITY,
+            clips: 0,
+        }
+    }
+}
 
+impl Levels {
+    /// Lowers the held peaks for `dt` having passed, never below the live
+    /// peak; a hold that falls out of the meter's range is dropped.
+    pub fn decay(&mut self, dt: Duration) {
+        let fall = PEAK_HOLD_RANGE_DB * dt.as_secs_f32() / PEAK_HOLD_FALL.as_secs_f32();
+        for (hold, peak) in [
+            (&mut self.peak_hold_l, self.peak_l),
+            (&mut self.peak_hold_r, self.peak_r),
+        ] {
+            let lowered = *hold - fall;
+            *hold = if lowered < -PEAK_HOLD_RANGE_DB {
+                peak
+            } else {
+                lowered.max(peak)
+            };
+        }
+    }
+}
+
+/// Loudest the mic can be set to (`--mic-volume`, `+`): twice its level.
+pub const MAX_MIC_VOLUME: f32 = 2.0;
+
+/// Creates the asendcmd file holding the mic volume ffmpeg starts with,
+/// `volume` (1.0 unless `--mic-volume` says otherwise). Uses `path` when
+/// given, otherwise a per-process file under `temp_dir()/rcrd-mic`.
+pub fn prepare_mic_control(path: Option<&Path>, volume: f32) -> Result<PathBuf> {
+    let cmd_path = match path {
+        Some(path) => path.to_path_buf(),
+        None => {
+            let dir = std::env::temp_dir().join("rcrd-mic");
+            fs::create_dir_all(&dir)?;
+            dir.join(format!("mic-{}.cmd", std::process::id()))
+        }
+    };
+    write_mic_volume(&cmd_path, volume, 0.0, volume)?;
     Ok(cmd_path)
 }
 
-pub fn write_mic_volume(cmd_path: &Path, volume: f32) -> Result<()> {
-    let mut f = OpenOptions::new()
-        .create(true)
-        .append(true)
-        .open(cmd_path)?;
-    writeln!(f, "0.0 volume@micvol volume {volume}")?;
+/// Records the mic volume set `at_secs` into the recording. The file is
+/// rewritten rather than appended to, so it never grows: it holds the
+/// starting volume, `start`, and after the first change only the latest
+/// command.
+///
+/// asendcmd reads this file once when the graph is built, so changes made
+/// while recording reach ffmpeg through [`send_mic_volume`] instead.
+pub fn write_mic_volume(cmd_path: &Path, start: f32, at_secs: f64, volume: f32) -> Result<()> {
+    write_mic_commands(cmd_path, start, &[(at_secs, volume)])
+}
+
+/// Steps a mic fade is split into; five over 50 ms is inaudible as steps
+/// but long enough to avoid the click of a hard cut.
+const FADE_STEPS: u32 = 5;
+
+/// Volumes (and their offset in seconds) a fade from `from` to `to` over
+/// `ms` passes through, ending exactly on `to`.
+fn fade_steps(from: f32, to: f32, ms: u32) -> impl Iterator<Item = (f64, f32)> {
+    (1..=FADE_STEPS).map(move |i| {
+        let frac = i as f32 / FADE_STEPS as f32;
+        (
+            ms as f64 * i as f64 / FADE_STEPS as f64 / 1000.0,
+            // Rounded so the commands read `0.4`, not `0.39999998`.
+            ((from + (to - from) * frac) * 100.0).round() / 100.0,
+        )
+    })
+}
+
+/// Like [`write_mic_volume`], but ramps from `from` to `to` over `ms`
+/// starting `at_secs` into the recording.
+pub fn write_mic_fade(
+    cmd_path: &Path,
+    start: f32,
+    at_secs: f64,
+    from: f32,
+    to: f32,
+    ms: u32,
+) -> Result<()> {
+    let steps: Vec<_> = fade_steps(from, to, ms)
+        .map(|(offset, volume)| (at_secs + offset, volume))
+        .collect();
+    write_mic_commands(cmd_path, start, &steps)
+}
+
+fn write_mic_commands(cmd_path: &Path, start: f32, commands: &[(f64, f32)]) -> Result<()> {
+    let mut content = String::new();
+    if commands.first().is_some_and(|&(secs, _)| secs > 0.0) {
+        content.push_str(&format!("0.000 volume@micvol volume {start};\n"));
+    }
+    for (secs, volume) in commands {
+        content.push_str(&format!("{secs:.3} volume@micvol volume {volume};\n"));
+    }
+    // Write a sibling and rename it over the original so `tail -F` and
+    // ffmpeg never see a half-written file.
+    let tmp = cmd_path.with_extension("cmd.tmp");
+    fs::write(&tmp, content)?;
+    fs::rename(&tmp, cmd_path)?;
     Ok(())
 }
 
-#[allow(clippy::too_many_arguments)]
-pub fn spawn_ffmpeg(
-    monitor: &str,
-    mic: Option<&str>,
-    mic_cmd_path: Option<&Path>,
-    outfile: &Path,
-    duration: Option<u32>,
-    recent_logs: Arc<Mutex<Vec<String>>>,
-    debug: bool,
-) -> Result<Child> {
-    let mut cmd = Command::new("ffmpeg");
-    cmd.args(["-hide_banner", "-nostdin", "-y"]);
-    if let Some(d) = duration {
-        cmd.args(["-t", &d.to_string()]);
+/// Changes the mic volume of a running ffmpeg immediately, via its
+/// interactive `c` command on stdin (`<target> <time>|-1 <command> <arg>`).
+pub fn send_mic_volume(stdin: &mut impl Write, volume: f32) -> io::Result<()> {
+    writeln!(stdin, "cvolume@micvol -1 volume {volume}")?;
+    stdin.flush()
+}
+
+/// Ramps the mic volume of a running ffmpeg from `from` to `to` over `ms`.
+/// Blocks for the length of the fade.
+pub fn send_mic_fade(stdin: &mut impl Write, from: f32, to: f32, ms: u32) -> io::Result<()> {
+    let step = Duration::from_millis((ms / FADE_STEPS) as u64);
+    for (_, volume) in fade_steps(from, to, ms) {
+        thread::sleep(step);
+        send_mic_volume(stdin, volume)?;
+    }
+    Ok(())
+}
+
+pub const DEFAULT_SAMPLE_RATE: u32 = 48_000;
+/// Output rates libopus can encode at.
+pub const SUPPORTED_SAMPLE_RATES: &[u32] = &[8_000, 12_000, 16_000, 24_000, 48_000];
+
+/// Frame lengths (ms) libopus accepts for `-frame_duration`.
+pub const OPUS_FRAME_MS: &[f32] = &[2.5, 5.0, 10.0, 20.0, 40.0, 60.0, 80.0, 100.0, 120.0];
+
+/// Codec and container of the recorded file.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum AudioFormat {
+    /// Opus in Ogg: small files, tuned for speech.
+    #[default]
+    Opus,
+    /// Lossless FLAC, for archiving.
+    Flac,
+}
+
+impl AudioFormat {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Opus => "opus",
+            Self::Flac => "flac",
+        }
+    }
+
+    /// Extension of generated file names.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Opus => "ogg",
+            Self::Flac => "flac",
+        }
     }
 
-    cmd.args(["-f", "pulse", "-i", monitor]);
+    /// ffmpeg muxer writing the container.
+    pub fn muxer(self) -> &'static str {
+        match self {
+            Self::Opus => "ogg",
+            Self::Flac => "flac",
+        }
+    }
+}
+
+/// Rate of the s16le PCM handed to the transcriber: whisper's own, so it
+/// never has to resample.
+pub const PCM_TAP_RATE: u32 = 16_000;
+
+/// FLAC `-compression_level` when none is given, ffmpeg's own default.
+pub const DEFAULT_FLAC_LEVEL: u8 = 5;
+/// Highest FLAC `-compression_level`; higher levels encode slower for
+/// slightly smaller files, and every level is lossless.
+pub const MAX_FLAC_LEVEL: u8 = 12;
+
+/// How the file is encoded, shared by recording and `--trim-silence`.
+#[derive(Clone, Copy, Debug)]
+pub struct Encoding {
+    pub format: AudioFormat,
+    pub opus_application: OpusApplication,
+    /// Opus frame length in ms, one of [`OPUS_FRAME_MS`].
+    pub opus_frame_ms: f32,
+    /// FLAC `-compression_level`, up to [`MAX_FLAC_LEVEL`].
+    pub flac_level: u8,
+}
+
+impl Default for Encoding {
+    fn default() -> Self {
+        Self {
+            format: AudioFormat::Opus,
+            opus_application: OpusApplication::Voip,
+            opus_frame_ms: 20.0,
+            flac_level: DEFAULT_FLAC_LEVEL,
+        }
+    }
+}
+
+impl Encoding {
+    /// Rejects settings the chosen encoder would not accept.
+    pub fn validate(&self) -> Result<()> {
+        match self.format {
+            AudioFormat::Opus if !OPUS_FRAME_MS.contains(&self.opus_frame_ms) => Err(anyhow!(
+                "unsupported Opus frame duration {} ms (use one of {:?})",
+                self.opus_frame_ms,
+                OPUS_FRAME_MS
+            )),
+            AudioFormat::Flac if self.flac_level > MAX_FLAC_LEVEL => Err(anyhow!(
+                "FLAC compression level {} is out of range (0 to {MAX_FLAC_LEVEL})",
+                self.flac_level
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Roughly how fast the file grows: Opus at its fixed bitrate, FLAC
+    /// estimated generously at 70% of 16-bit PCM.
+    pub fn bytes_per_sec(&self, sample_rate: u32, channels: u8) -> u64 {
+        match self.format {
+            AudioFormat::Opus => 128_000 / 8,
+            AudioFormat::Flac => u64::from(sample_rate) * u64::from(channels) * 2 * 7 / 10,
+        }
+    }
+
+    /// The encoder arguments, before the output file.
+    pub fn add_args(&self, cmd: &mut Command) {
+        match self.format {
+            AudioFormat::Opus => {
+                cmd.args(["-c:a", "libopus", "-b:a", "128k"]);
+                cmd.args(["-application", self.opus_application.as_str()]);
+                cmd.args(["-frame_duration", &self.opus_frame_ms.to_string()]);
+            }
+            AudioFormat::Flac => {
+                cmd.args(["-c:a", "flac"]);
+                cmd.args(["-compression_level", &self.flac_level.to_string()]);
+            }
+        }
+    }
+}
+
+/// libopus `-application`: what the encoder tunes for.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum OpusApplication {
+    /// Speech intelligibility; the right choice for calls.
+    #[default]
+    Voip,
+    /// Music and mixed content, closer to the input.
+    Audio,
+    /// Minimal algorithmic delay, at some cost in quality.
+    Lowdelay,
+}
+
+impl OpusApplication {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Voip => "voip",
+            Self::Audio => "audio",
+            Self::Lowdelay => "lowdelay",
+        }
+    }
+}
+
+/// How inputs that start (or drift) at different times are kept aligned
+/// before they are mixed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum SyncMode {
+    /// Mix samples as they arrive; a late input lags by its start delay.
+    #[default]
+    None,
+    /// `aresample=async=1`: pad a late start with silence and fill or trim
+    /// later gaps, following the inputs' own timestamps.
+    Resample,
+    /// Like `resample`, but the timestamps are taken from the wall clock on
+    /// arrival, which also covers devices with unreliable clocks.
+    Wallclock,
+}
 
-    let filter_complex = if let Some(mic_name) = mic {
+impl SyncMode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::Resample => "resample",
+            Self::Wallclock => "wallclock",
+        }
+    }
+
+    /// One-line tradeoff, shown with `--debug`.
+    pub fn tradeoff(self) -> &'static str {
+        match self {
+            Self::None => "no correction; inputs that start late stay offset in the mix",
+            Self::Resample => {
+                "silence pads late inputs and gaps; relies on the device timestamps being right"
+            }
+            Self::Wallclock => {
+                "aligns on arrival time; robust to bad device clocks but adds scheduling jitter"
+            }
+        }
+    }
+}
+
+/// What ffmpeg should capture and where it goes.
+#[derive(Clone, Copy)]
+pub struct FfmpegOptions<'a> {
+    /// Sink monitors to tap, summed into one remote side; none records the
+    /// mic only.
+    pub monitors: &'a [&'a str],
+    /// Literal ffmpeg input arguments (see [`parse_raw_input`]) captured in
+    /// place of the monitor.
+    pub raw_input: Option<&'a [String]>,
+    pub mic: Option<&'a str>,
+    /// asendcmd file controlling the mic volume.
+    pub mic_cmd_path: Option<&'a Path>,
+    pub outfile: &'a Path,
+    pub duration: Option<u32>,
+    /// Also emit raw PCM on stdout for the transcriber.
+    pub want_transcript: bool,
+    /// Remote on the left channel, mic on the right instead of mixing.
+    pub split_channels: bool,
+    /// Rate every input is resampled to and the file is encoded at.
+    pub sample_rate: u32,
+    /// Channels in the file: 2, or 1 to downmix voice-only calls.
+    pub channels: u8,
+    /// Write the file as independent segments of this many seconds
+    /// (see [`segment_pattern`]) so a crash loses at most one segment.
+    pub segment_secs: Option<u32>,
+    /// Seconds of captured audio to throw away before anything is written.
+    pub skip_start: Option<u32>,
+    /// Codec and its settings; the file's extension should match the format.
+    pub encoding: Encoding,
+    pub sync_mode: SyncMode,
+    /// Written into the file as `-metadata key=value`.
+    pub metadata: &'a [(String, String)],
+    /// Peak level (dBFS) counted as clipping in [`Levels::clips`].
+    pub clip_threshold_db: f32,
+    /// Only meter the inputs: nothing is encoded and the output goes to
+    /// ffmpeg's null muxer, leaving `outfile` alone.
+    pub standby: bool,
+    /// Report progress as `-progress` key=value updates on fd 3 instead of
+    /// the status line on stderr; [`spawn_ffmpeg`] sets up the pipe.
+    pub progress_pipe: bool,
+    /// PulseAudio/PipeWire sink the mix is also played to, to hear what is
+    /// being recorded (`--monitor-playback`).
+    pub playback: Option<&'a str>,
+}
+
+impl<'a> FfmpegOptions<'a> {
+    /// Monitor-less, mic-less defaults writing to `outfile`; set the inputs next.
+    pub fn new(outfile: &'a Path) -> Self {
+        Self {
+            monitors: &[],
+            raw_input: None,
+            mic: None,
+            mic_cmd_path: None,
+            outfile,
+            duration: None,
+            want_transcript: false,
+            split_channels: false,
+            sample_rate: DEFAULT_SAMPLE_RATE,
+            channels: 2,
+            segment_secs: None,
+            skip_start: None,
+            encoding: Encoding::default(),
+            sync_mode: SyncMode::None,
+            metadata: &[],
+            clip_threshold_db: 0.0,
+            standby: false,
+            progress_pipe: false,
+            playback: None,
+        }
+    }
+}
+
+/// Output pattern for segmented recordings: `call.ogg` becomes `call-000.ogg`, `call-001.ogg`, ...
+pub fn segment_pattern(outfile: &Path) -> PathBuf {
+    let stem = outfile.file_stem().unwrap_or_default().to_string_lossy();
+    let ext = outfile
+        .extension()
+        .map(|e| format!(".{}", e.to_string_lossy()))
+        .unwrap_or_default();
+    outfile.with_file_name(format!("{stem}-%03d{ext}"))
+}
+
+/// Splits a `--raw-input` spec such as `-f alsa -i "hw:1,0"` into ffmpeg
+/// arguments the way a shell would, but without running one: whitespace
+/// separates words, quotes group them and a backslash escapes the next
+/// character. The spec must name exactly one input with `-i`.
+pub fn parse_raw_input(spec: &str) -> Result<Vec<String>> {
+    let mut args = Vec::new();
+    let mut word: Option<String> = None;
+    let mut quote = None;
+    let mut chars = spec.chars();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some('"') | None, '\\') => {
+                let escaped = chars
+                    .next()
+                    .ok_or_else(|| anyhow!("--raw-input ends with a lone backslash"))?;
+                word.get_or_insert_default().push(escaped);
+            }
+            (Some(_), c) => word.get_or_insert_default().push(c),
+            (None, '"' | '\'') => {
+                quote = Some(c);
+                word.get_or_insert_default();
+            }
+            (None, c) if c.is_whitespace() => args.extend(word.take()),
+            (None, c) => word.get_or_insert_default().push(c),
+        }
+    }
+    if quote.is_some() {
+        return Err(anyhow!("--raw-input has an unterminated quote"));
+    }
+    args.extend(word);
+    match args.iter().filter(|a| *a == "-i").count() {
+        0 => Err(anyhow!(
+            "--raw-input needs an input, e.g. `-f alsa -i hw:1`"
+        )),
+        1 if args.last().is_some_and(|a| a == "-i") => {
+            Err(anyhow!("--raw-input: `-i` is missing its value"))
+        }
+        1 => Ok(args),
+        _ => Err(anyhow!("--raw-input takes a single `-i` input")),
+    }
+}
+
+/// Escapes `value` for a filter option inside `-filter_complex`: first for
+/// the filter's option list (`\`, `'`, `:`), then for the graph itself,
+/// which also splits at `[`, `]`, `,` and `;`.
+fn escape_filter_arg(value: &str) -> String {
+    let escape = |value: &str, special: &str| {
+        let mut out = String::with_capacity(value.len());
+        for c in value.chars() {
+            if special.contains(c) {
+                out.push('\\');
+            }
+            out.push(c);
+        }
+        out
+    };
+    escape(&escape(value, "\\':"), "\\'[],;")
+}
+
+/// Builds the ffmpeg invocation for `opts` without running it.
+pub fn build_ffmpeg_command(opts: &FfmpegOptions) -> Result<Command> {
+    if !SUPPORTED_SAMPLE_RATES.contains(&opts.sample_rate) {
+        return Err(anyhow!(
+            "unsupported sample rate {} (use one of {:?})",
+            opts.sample_rate,
+            SUPPORTED_SAMPLE_RATES
+        ));
+    }
+    opts.encoding.validate()?;
+    if opts.split_channels && opts.channels != 2 {
+        return Err(anyhow!("split channels need a stereo file"));
+    }
+    let rate = opts.sample_rate;
+    let mut cmd = tool("ffmpeg");
+    cmd.args(["-hide_banner", "-y"]);
+    if opts.progress_pipe {
+        cmd.args(["-nostats", "-progress", "pipe:3"]);
+    }
+    if opts.mic.is_some() {
+        // Live mic volume changes are sent as interactive commands.
+        cmd.stdin(Stdio::piped());
+    } else {
+        cmd.arg("-nostdin");
+    }
+    let skip = opts.skip_start.unwrap_or(0);
+    // Set on each output rather than on an input: an input limit only ends
+    // that input, and the mix keeps going on the others. On an output,
+    // `-ss` keeps capturing from the start but discards the lead-in, unlike
+    // delaying the launch, and `-t` counts from there.
+    let limit = |cmd: &mut Command| {
+        if skip > 0 {
+            cmd.args(["-ss", &skip.to_string()]);
+        }
+        if let Some(d) = opts.duration {
+            cmd.args(["-t", &d.to_string()]);
+        }
+    };
+
+    let mut filter_complex = String::new();
+    // Every input is resampled explicitly so devices running at other
+    // rates (e.g. 44.1 kHz) are converted once, up front, and aligned there
+    // when a sync mode is chosen.
+    let resample = match opts.sync_mode {
+        SyncMode::None => format!("aresample={rate}"),
+        SyncMode::Resample | SyncMode::Wallclock => {
+            format!("aresample={rate}:async=1:first_pts=0")
+        }
+    };
+    let wallclock = opts.sync_mode == SyncMode::Wallclock;
+    // Inputs 0..remote are the monitor side, the mic (if any) comes after.
+    let mut remote = 0;
+    if let Some(raw) = opts.raw_input {
+        if wallclock {
+            cmd.args(["-use_wallclock_as_timestamps", "1"]);
+        }
+        cmd.args(raw);
+        remote += 1;
+    }
+    for monitor in opts.monitors {
+        if wallclock {
+            cmd.args(["-use_wallclock_as_timestamps", "1"]);
+        }
+        cmd.args(["-f", "pulse", "-i", monitor]);
+        remote += 1;
+    }
+    let has_monitor = remote > 0;
+    if has_monitor {
+        let mon = if remote == 1 {
+            format!("[0:a]{resample}")
+        } else {
+            // Several monitors are summed as they would sound on the
+            // speakers, rather than scaled down one against another.
+            let mut mon = String::new();
+            for i in 0..remote {
+                mon.push_str(&format!("[{i}:a]{resample}[mon{i}];"));
+            }
+            for i in 0..remote {
+                mon.push_str(&format!("[mon{i}]"));
+            }
+            mon.push_str(&format!(
+                "amix=inputs={remote}:duration=longest:normalize=0"
+            ));
+            mon
+        };
+        // Meter the monitors on their own so a wrong sink can be spotted even
+        // when the mic keeps the mix busy.
+        filter_complex.push_str(&format!(
+            "{mon},asplit=2[mon][mon_stats];\
+             [mon_stats]asetnsamples=n=4800,\
+             astats=metadata=1:reset=1:measure_perchannel=none:measure_overall=Peak_level,\
+             ametadata=mode=print,anullsink;",
+        ));
+    }
+
+    let mic_chain = opts.mic.map(|mic_name| {
+        let input = remote;
+        if wallclock {
+            cmd.args(["-use_wallclock_as_timestamps", "1"]);
+        }
         cmd.args(["-f", "pulse", "-i", mic_name]);
-        let mic_cmd = if let Some(cmd_path) = mic_cmd_path {
-            format!("filename={}", cmd_path.display())
+        let mic_cmd = if let Some(cmd_path) = opts.mic_cmd_path {
+            format!(
+                "filename={}",
+                escape_filter_arg(&cmd_path.display().to_string())
+            )
         } else {
             String::from("filename=")
         };
+        format!("[{input}:a]{resample},asendcmd={mic_cmd},volume@micvol=volume=1.0")
+    });
 
-        format!(
-            "[1:a]asendcmd={mic_cmd},volume@micvol=volume=1.0[mic];\
-             [0:a][mic]amix=inputs=2:duration=longest:dropout_transition=3[mix]"
-        )
-    } else {
-        String::from("[0:a]"
-        )
-    };
+    filter_complex.push_str(&match (has_monitor, mic_chain) {
+        // Remote on the left channel, local mic on the right.
+        (true, Some(mic)) if opts.split_channels => format!(
+            "[mon]aformat=channel_layouts=mono[remote];\
+             {mic},aformat=channel_layouts=mono[mic];\
+             [remote][mic]amerge=inputs=2,pan=stereo|c0=c0|c1=c1[mix];"
+        ),
+        (true, Some(mic)) => format!(
+            "{mic}[mic];\
+             [mon][mic]amix=inputs=2:duration=longest:dropout_transition=3[mix];"
+        ),
+        (true, None) => String::from("[mon]anull[mix];"),
+        (false, Some(mic)) => format!("{mic}[mix];"),
+        (false, None) => return Err(anyhow!("nothing to record: no monitor and no mic")),
+    });
+
+    // Split the mix: one branch for the file, one for level metering and
+    // optionally a raw PCM branch for the transcriber and one played back.
+    let playback = opts.playback.filter(|_| !opts.standby);
+    let outputs = 2 + usize::from(opts.want_transcript) + usize::from(playback.is_some());
+    filter_complex.push_str(&format!("[mix]asplit={outputs}[out_file][stats]"));
+    if opts.want_transcript {
+        filter_complex.push_str("[out_pcm]");
+    }
+    if playback.is_some() {
+        filter_complex.push_str("[out_play]");
+    }
+    filter_complex.push_str(
+        ";[stats]asetnsamples=n=4800,\
+         astats=metadata=1:reset=1:measure_perchannel=Peak_level+RMS_level:measure_overall=none,\
+         ametadata=mode=print,anullsink",
+    );
 
     cmd.args(["-filter_complex", &filter_complex]);
     cmd.args(["-map", "[out_file]"]);
+    limit(&mut cmd);
+
+    cmd.args(["-ac", &opts.channels.to_string(), "-ar", &rate.to_string()]);
+    if opts.standby {
+        cmd.args(["-f", "null", "-"]);
+        return Ok(cmd);
+    }
+    opts.encoding.add_args(&mut cmd);
+    // Hand every packet to the muxer right away instead of buffering.
+    cmd.args(["-flush_packets", "1"]);
+    // No shell is involved, and ffmpeg splits at the first `=` only, so the
+    // value needs no quoting.
+    for (key, value) in opts.metadata {
+        cmd.args(["-metadata", &format!("{key}={value}")]);
+    }
+    if let Some(secs) = opts.segment_secs {
+        cmd.args(["-f", "segment", "-segment_time", &secs.to_string()]);
+        cmd.args(["-reset_timestamps", "1"]);
+        cmd.arg(segment_pattern(opts.outfile));
+    } else {
+        cmd.arg(opts.outfile);
+    }
 
-    cmd.args([
-        "-ac", "2", "-ar", "48000", "-c:a", "libopus", "-b:a", "128k",
-    ]);
-    cmd.arg(outfile);
+    if opts.want_transcript {
+        // The transcriber reads whisper's 16 kHz whatever the file rate, in
+        // mono unless each channel is transcribed as its own speaker.
+        let channels = if opts.split_channels { "2" } else { "1" };
+        cmd.args(["-map", "[out_pcm]", "-ac", channels]);
+        cmd.args(["-ar", &PCM_TAP_RATE.to_string()]);
+        limit(&mut cmd);
+        cmd.args(["-f", "s16le", "pipe:1"]);
+        cmd.stdout(Stdio::piped());
+    }
+
+    if let Some(sink) = playback {
+        // The pulse muxer takes the sink as `-device`; the file name becomes
+        // the stream's name in the mixer.
+        cmd.args(["-map", "[out_play]"]);
+        limit(&mut cmd);
+        cmd.args(["-f", "pulse", "-device", sink]);
+        cmd.arg("rcrd monitor");
+    }
+
+    Ok(cmd)
+}
+
+/// Decodes `input` to the mono 16 kHz s16le stream on stdout that the
+/// transcriber reads during live capture.
+pub fn build_decode_command(input: &Path) -> Command {
+    let mut cmd = tool("ffmpeg");
+    cmd.args(["-hide_banner", "-nostdin", "-loglevel", "error", "-i"])
+        .arg(input)
+        .args(["-vn", "-ac", "1", "-ar", &PCM_TAP_RATE.to_string()])
+        .args(["-f", "s16le", "pipe:1"]);
+    cmd
+}
+
+/// Longest stderr line kept; the rest of it is dropped.
+const MAX_LOG_LINE: usize = 4096;
+
+/// Calls `f` with every line of `reader`, ending lines at `\r` as well as
+/// `\n`: ffmpeg rewrites its status line in place with `\r`, so it would
+/// otherwise only come through when ffmpeg exits. Empty lines are skipped
+/// and lines are cut at [`MAX_LOG_LINE`] bytes.
+fn for_each_line(mut reader: impl BufRead, mut f: impl FnMut(&str)) {
+    let mut line = Vec::new();
+    let mut emit = |line: &mut Vec<u8>| {
+        if !line.is_empty() {
+            f(&String::from_utf8_lossy(line));
+            line.clear();
+        }
+    };
+    loop {
+        let buf = match reader.fill_buf() {
+            Ok([]) => break,
+            Ok(buf) => buf,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(_) => break,
+        };
+        for &byte in buf {
+            match byte {
+                b'\r' | b'\n' => emit(&mut line),
+                _ if line.len() < MAX_LOG_LINE => line.push(byte),
+                _ => {}
+            }
+        }
+        let len = buf.len();
+        reader.consume(len);
+    }
+    emit(&mut line);
+}
+
+/// Reads `-progress` output: blocks of `key=value` lines, each ending with
+/// `progress=continue` (or `end`), applied to `progress` as a whole once
+/// the block is complete. Values ffmpeg doesn't know yet (`N/A`) stay `None`.
+fn read_progress(reader: impl BufRead, progress: &Mutex<Progress>) {
+    let mut block = Progress::default();
+    for line in reader.lines().map_while(Result::ok) {
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let value = value.trim();
+        match key.trim() {
+            "out_time_us" => block.time = value.parse().ok().map(Duration::from_micros),
+            "total_size" => block.size_bytes = value.parse().ok(),
+            "bitrate" => block.bitrate_kbps = value.trim_end_matches("kbits/s").parse().ok(),
+            "progress" => *lock(progress) = block,
+            _ => {}
+        }
+    }
+}
+
+/// Starts ffmpeg and reads its output in the background: `-progress`
+/// updates go to `progress`, level lines on stderr update `audio_level`,
+/// and the rest of stderr is appended to `recent_logs`, keeping the last
+/// ten lines.
+pub fn spawn_ffmpeg(
+    runner: &dyn ProcessRunner,
+    opts: &FfmpegOptions,
+    audio_level: Arc<Mutex<Levels>>,
+    progress: Arc<Mutex<Progress>>,
+    recent_logs: Arc<Mutex<Vec<String>>>,
+    debug: bool,
+) -> Result<Child> {
+    let clip_db = opts.clip_threshold_db;
 
     if debug {
+        let mut cmd = build_ffmpeg_command(opts)?;
         println!("FFmpeg command: {:?}", cmd);
-        return Ok(cmd.spawn().context("failed to spawn ffmpeg")?);
+        return runner.spawn(&mut cmd).context("failed to spawn ffmpeg");
     }
 
+    let mut cmd = build_ffmpeg_command(&FfmpegOptions {
+        progress_pipe: true,
+        ..*opts
+    })?;
     cmd.stderr(Stdio::piped());
+    let (progress_out, progress_in) = io::pipe().context("failed to create the progress pipe")?;
+    let fd = progress_in.as_raw_fd();
+    // SAFETY: between fork and exec this only calls dup2(2)/fcntl(2), which
+    // are async-signal-safe. Both ends of the pipe are close-on-exec; fd 3
+    // is the one copy ffmpeg keeps.
+    unsafe {
+        cmd.pre_exec(move || {
+            let rc = if fd == 3 {
+                libc::fcntl(3, libc::F_SETFD, 0)
+            } else {
+                libc::dup2(fd, 3)
+            };
+            if rc == -1 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
 
-    let mut child = cmd.spawn().context("failed to spawn ffmpeg")?;
+    tracing::info!("spawning ffmpeg: {:?}", argv(&cmd));
+    let mut child = runner.spawn(&mut cmd).context("failed to spawn ffmpeg")?;
+    tracing::debug!("ffmpeg pid={}", child.id());
+    // ffmpeg holds the only write end now, so the reader ends when it exits.
+    drop(progress_in);
+    thread::spawn(move || read_progress(BufReader::new(progress_out), &progress));
 
     let stderr = child.stderr.take().expect("failed to capture stderr");
 
     thread::spawn(move || {
-        let reader = BufReader::new(stderr);
-
-        for line in reader.lines() {
-            if let Ok(l) = line {
-                if let Ok(mut logs) = recent_logs.lock() {
-                    if logs.len() >= 10 {
-                        logs.remove(0);
-                    }
-                    logs.push(l.clone());
-                }
+        let level_re = level_regex();
+
+        for_each_line(BufReader::new(stderr), |l| {
+            if parse_level_line(
+                &level_re,
+                l,
+                &mut lock_logged(&audio_level, "audio levels", &recent_logs),
+                clip_db,
+            ) {
+                return;
             }
-        }
+            if l.contains("Parsed_ametadata") {
+                return;
+            }
+            tracing::debug!(target: "rcrd::ffmpeg::stderr", "{l}");
+            let mut logs = lock(&recent_logs);
+            if logs.len() >= 10 {
+                logs.remove(0);
+            }
+            logs.push(l.to_string());
+        });
+        tracing::info!("ffmpeg stderr closed");
     });
 
     Ok(child)
 }
+
+/// How often [`wait_for_level`] looks at the levels.
+const ARM_POLL: Duration = Duration::from_millis(50);
+
+/// `--arm`: runs ffmpeg on the inputs of `opts` in [`standby`](FfmpegOptions::standby)
+/// until the peak of either channel reaches `threshold_db`, then stops it
+/// and returns how long that took. Fails if ffmpeg exits first.
+pub fn wait_for_level(
+    runner: &dyn ProcessRunner,
+    opts: &FfmpegOptions,
+    threshold_db: f32,
+) -> Result<Duration> {
+    let opts = FfmpegOptions {
+        standby: true,
+        duration: None,
+        want_transcript: false,
+        segment_secs: None,
+        skip_start: None,
+        ..*opts
+    };
+    let levels = Arc::new(Mutex::new(Levels::default()));
+    let logs = Arc::new(Mutex::new(Vec::new()));
+    let progress = Arc::new(Mutex::new(Progress::default()));
+    let mut child = spawn_ffmpeg(runner, &opts, levels.clone(), progress, logs.clone(), false)?;
+    let armed = Instant::now();
+    loop {
+        let peak = {
+            let levels = lock(&levels);
+            levels.peak_l.max(levels.peak_r)
+        };
+        if peak >= threshold_db {
+            break;
+        }
+        if let Some(status) = child.try_wait()? {
+            return Err(anyhow!(
+                "ffmpeg exited with {status} while armed: {}",
+                lock(&logs).join("\n")
+            ));
+        }
+        thread::sleep(ARM_POLL);
+    }
+    let _ = child.kill();
+    let _ = child.wait();
+    Ok(armed.elapsed())
+}
+
+fn level_regex() -> Regex {
+    Regex::new(r"lavfi\.astats\.(\d+|Overall)\.(Peak_level|RMS_level)=(\S+)").unwrap()
+}
+
+/// Applies an astats metadata line to `levels`, counting a clip when the
+/// louder channel's peak rises to `clip_db`; returns whether it was one.
+fn parse_level_line(re: &Regex, line: &str, levels: &mut Levels, clip_db: f32) -> bool {
+    let Some(caps) = re.captures(line) else {
+        return false;
+    };
+    let db = caps[3].parse::<f32>().unwrap_or(f32::NEG_INFINITY);
+    let was_clipping = levels.peak_l.max(levels.peak_r) >= clip_db;
+    match (&caps[1], &caps[2]) {
+        ("1", "Peak_level") => {
+            levels.peak_l = db;
+            levels.peak_hold_l = levels.peak_hold_l.max(db);
+        }
+        ("2", "Peak_level") => {
+            levels.peak_r = db;
+            levels.peak_hold_r = levels.peak_hold_r.max(db);
+        }
+        ("1", "RMS_level") => levels.rms_l = db,
+        ("2", "RMS_level") => levels.rms_r = db,
+        ("Overall", "Peak_level") => levels.monitor_peak = db,
+        _ => {}
+    }
+    if !was_clipping && levels.peak_l.max(levels.peak_r) >= clip_db {
+        levels.clips += 1;
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::argv;
+    use crate::process::mock::MockRunner;
+
+    fn args_for(opts: &FfmpegOptions) -> Vec<String> {
+        argv(&build_ffmpeg_command(opts).unwrap())
+    }
+
+    fn value_after(args: &[String], flag: &str) -> Option<String> {
+        let i = args.iter().position(|a| a == flag)?;
+        args.get(i + 1).cloned()
+    }
+
+    fn inputs(args: &[String]) -> Vec<String> {
+        args.windows(2)
+            .filter(|w| w[0] == "-i")
+            .map(|w| w[1].clone())
+            .collect()
+    }
+
+    #[test]
+    fn writes_metadata_before_the_file() {
+        let metadata = [
+            ("comment".to_string(), "a=b; quoted \"x\"".to_string()),
+            ("title".to_string(), "Kick-off call".to_string()),
+        ];
+        let opts = FfmpegOptions {
+            monitors: &["sink.monitor"],
+            metadata: &metadata,
+            ..FfmpegOptions::new(Path::new("call.ogg"))
+        };
+        let args = args_for(&opts);
+        let tail = &args[args.len() - 5..];
+        assert_eq!(
+            tail,
+            [
+                "-metadata",
+                "comment=a=b; quoted \"x\"",
+                "-metadata",
+                "title=Kick-off call",
+                "call.ogg"
+            ]
+        );
+    }
+
+    #[test]
+    fn escapes_the_mic_command_file_in_the_graph() {
+        // The example of ffmpeg-filters(1), "Notes on filtergraph escaping".
+        assert_eq!(
+            escape_filter_arg("this is a 'string': may contain one, or more, special characters"),
+            r"this is a \\\'string\\\'\\: may contain one\, or more\, special characters"
+        );
+        let opts = FfmpegOptions {
+            monitors: &["sink.monitor"],
+            mic: Some("mic"),
+            mic_cmd_path: Some(Path::new("/tmp/it's [a]:b;c.cmd")),
+            ..FfmpegOptions::new(Path::new("call.ogg"))
+        };
+        let filter = value_after(&args_for(&opts), "-filter_complex").unwrap();
+        assert!(
+            filter.contains(r"asendcmd=filename=/tmp/it\\\'s \[a\]\\:b\;c.cmd,volume@micvol"),
+            "{filter}"
+        );
+    }
+
+    #[test]
+    fn mixes_monitor_and_mic() {
+        let out = Path::new("call.ogg");
+        let opts = FfmpegOptions {
+            monitors: &["sink.monitor"],
+            mic: Some("mic"),
+            mic_cmd_path: Some(Path::new("/tmp/mic.cmd")),
+            ..FfmpegOptions::new(out)
+        };
+        let args = args_for(&opts);
+        assert_eq!(args[0], "ffmpeg");
+        assert_eq!(inputs(&args), ["sink.monitor", "mic"]);
+        let filter = value_after(&args, "-filter_complex").unwrap();
+        assert!(filter.contains("[1:a]aresample=48000,asendcmd=filename=/tmp/mic.cmd"));
+        assert!(filter.contains("[mon][mic]amix=inputs=2"));
+        assert_eq!(value_after(&args, "-c:a").as_deref(), Some("libopus"));
+        assert_eq!(args.last().map(String::as_str), Some("call.ogg"));
+        assert!(!args.iter().any(|a| a == "pipe:1"));
+        assert!(!args.iter().any(|a| a == "-t"));
+    }
+
+    #[test]
+    fn several_monitors_feed_one_mix() {
+        let opts = FfmpegOptions {
+            monitors: &["work.monitor", "music.monitor"],
+            mic: Some("mic"),
+            ..FfmpegOptions::new(Path::new("call.ogg"))
+        };
+        let args = args_for(&opts);
+        assert_eq!(inputs(&args), ["work.monitor", "music.monitor", "mic"]);
+        let filter = value_after(&args, "-filter_complex").unwrap();
+        assert!(filter.starts_with(
+            "[0:a]aresample=48000[mon0];[1:a]aresample=48000[mon1];\
+             [mon0][mon1]amix=inputs=2:duration=longest:normalize=0,asplit=2[mon][mon_stats];"
+        ));
+        assert!(filter.contains("[2:a]aresample=48000,asendcmd="));
+        assert!(filter.contains("[mon][mic]amix=inputs=2"));
+    }
+
+    #[test]
+    fn sync_modes_align_every_input() {
+        let mut opts = FfmpegOptions {
+            monitors: &["sink.monitor"],
+            mic: Some("mic"),
+            ..FfmpegOptions::new(Path::new("call.ogg"))
+        };
+        let args = args_for(&opts);
+        assert!(!args.iter().any(|a| a == "-use_wallclock_as_timestamps"));
+        assert!(
+            !value_after(&args, "-filter_complex")
+                .unwrap()
+                .contains("async")
+        );
+
+        opts.sync_mode = SyncMode::Resample;
+        let args = args_for(&opts);
+        let filter = value_after(&args, "-filter_complex").unwrap();
+        assert!(filter.starts_with("[0:a]aresample=48000:async=1:first_pts=0,asplit=2"));
+        assert!(filter.contains("[1:a]aresample=48000:async=1:first_pts=0,asendcmd="));
+        assert!(!args.iter().any(|a| a == "-use_wallclock_as_timestamps"));
+
+        opts.sync_mode = SyncMode::Wallclock;
+        let args = args_for(&opts);
+        let wallclock = args
+            .windows(4)
+            .filter(|w| w[0] == "-use_wallclock_as_timestamps" && w[1] == "1" && w[2] == "-f")
+            .count();
+        assert_eq!(wallclock, 2);
+    }
+
+    #[test]
+    fn monitor_only_without_mic() {
+        let out = Path::new("call.ogg");
+        let opts = FfmpegOptions {
+            monitors: &["sink.monitor"],
+            ..FfmpegOptions::new(out)
+        };
+        let args = args_for(&opts);
+        assert_eq!(inputs(&args), ["sink.monitor"]);
+        let filter = value_after(&args, "-filter_complex").unwrap();
+        assert!(filter.contains("[mon]anull[mix]"));
+        assert!(!filter.contains("amix"));
+    }
+
+    #[test]
+    fn mic_only_skips_monitor() {
+        let out = Path::new("memo.ogg");
+        let opts = FfmpegOptions {
+            mic: Some("mic"),
+            ..FfmpegOptions::new(out)
+        };
+        let args = args_for(&opts);
+        assert_eq!(inputs(&args), ["mic"]);
+        let filter = value_after(&args, "-filter_complex").unwrap();
+        assert!(filter.starts_with("[0:a]aresample=48000,asendcmd="));
+        assert!(!filter.contains("[mon]"));
+    }
+
+    #[test]
+    fn raw_input_replaces_the_monitor() {
+        let raw = parse_raw_input(r#"-f alsa  -thread_queue_size 1024 -i "hw:1,0""#).unwrap();
+        assert_eq!(
+            raw,
+            ["-f", "alsa", "-thread_queue_size", "1024", "-i", "hw:1,0"]
+        );
+        let opts = FfmpegOptions {
+            raw_input: Some(&raw),
+            mic: Some("mic"),
+            ..FfmpegOptions::new(Path::new("call.ogg"))
+        };
+        let args = args_for(&opts);
+        assert_eq!(inputs(&args), ["hw:1,0", "mic"]);
+        assert_eq!(value_after(&args, "-f").as_deref(), Some("alsa"));
+        let filter = value_after(&args, "-filter_complex").unwrap();
+        assert!(filter.contains("[mon][mic]amix=inputs=2"));
+    }
+
+    #[test]
+    fn raw_input_is_split_without_a_shell() {
+        // Shell syntax stays literal.
+        assert_eq!(
+            parse_raw_input(r"-i 'a; rm -rf ~' $HOME a\ b").unwrap(),
+            ["-i", "a; rm -rf ~", "$HOME", "a b"]
+        );
+        assert_eq!(
+            parse_raw_input(r"-f jack -i 'rcrd in' -ac \2 ''").unwrap(),
+            ["-f", "jack", "-i", "rcrd in", "-ac", "2", ""]
+        );
+        assert!(parse_raw_input("-f jack").is_err());
+        assert!(parse_raw_input("-f jack -i").is_err());
+        assert!(parse_raw_input("-i 'open").is_err());
+        assert!(parse_raw_input("-i x -i y").is_err());
+    }
+
+    #[test]
+    fn duration_limits_every_output() {
+        let out = Path::new("call.ogg");
+        let opts = FfmpegOptions {
+            monitors: &["sink.monitor"],
+            mic: Some("mic"),
+            duration: Some(8 * 3600),
+            want_transcript: true,
+            ..FfmpegOptions::new(out)
+        };
+        let args = args_for(&opts);
+        let last_input = args.iter().rposition(|a| a == "-i").unwrap();
+        let limits: Vec<usize> = (0..args.len()).filter(|&i| args[i] == "-t").collect();
+        assert_eq!(limits.len(), 2, "{args:?}");
+        for (t, map) in limits.iter().zip(["[out_file]", "[out_pcm]"]) {
+            assert!(*t > last_input);
+            assert_eq!(args[t + 1], "28800");
+            let output = args[..*t].iter().rposition(|a| a == "-map").unwrap();
+            assert_eq!(args[output + 1], map);
+        }
+    }
+
+    #[test]
+    fn split_channels_and_transcript_tap() {
+        let out = Path::new("call.ogg");
+        let opts = FfmpegOptions {
+            monitors: &["sink.monitor"],
+            mic: Some("mic"),
+            split_channels: true,
+            want_transcript: true,
+            ..FfmpegOptions::new(out)
+        };
+        let args = args_for(&opts);
+        let filter = value_after(&args, "-filter_complex").unwrap();
+        assert!(filter.contains("amerge=inputs=2"));
+        assert!(filter.contains("asplit=3[out_file][stats][out_pcm]"));
+        assert_eq!(args.last().map(String::as_str), Some("pipe:1"));
+        assert_eq!(value_after(&args, "-f").as_deref(), Some("pulse"));
+    }
+
+    #[test]
+    fn plays_the_mix_back_to_a_sink() {
+        let opts = FfmpegOptions {
+            monitors: &["call.monitor"],
+            mic: Some("mic"),
+            want_transcript: true,
+            playback: Some("headphones"),
+            ..FfmpegOptions::new(Path::new("call.ogg"))
+        };
+        let args = args_for(&opts);
+        let filter = value_after(&args, "-filter_complex").unwrap();
+        assert!(filter.contains("asplit=4[out_file][stats][out_pcm][out_play]"));
+        assert!(
+            args.join(" ")
+                .ends_with("-map [out_play] -f pulse -device headphones rcrd monitor"),
+            "{args:?}"
+        );
+        // Standby meters only, so nothing is played either.
+        let args = args_for(&FfmpegOptions {
+            standby: true,
+            want_transcript: false,
+            ..opts
+        });
+        assert!(!args.iter().any(|a| a.contains("out_play")));
+    }
+
+    #[test]
+    fn resamples_inputs_to_the_output_rate() {
+        let out = Path::new("call.ogg");
+        let opts = FfmpegOptions {
+            monitors: &["sink.monitor"],
+            mic: Some("mic"),
+            sample_rate: 24_000,
+            ..FfmpegOptions::new(out)
+        };
+        let args = args_for(&opts);
+        let filter = value_after(&args, "-filter_complex").unwrap();
+        assert!(filter.starts_with("[0:a]aresample=24000,"));
+        assert!(filter.contains("[1:a]aresample=24000,"));
+        assert_eq!(value_after(&args, "-ar").as_deref(), Some("24000"));
+
+        let opts = FfmpegOptions {
+            sample_rate: 44_100,
+            ..opts
+        };
+        assert!(build_ffmpeg_command(&opts).is_err());
+    }
+
+    #[test]
+    fn transcriber_tap_is_16k_mono_unless_speakers_are_split() {
+        let out = Path::new("call.ogg");
+        let opts = FfmpegOptions {
+            monitors: &["sink.monitor"],
+            want_transcript: true,
+            channels: 1,
+            ..FfmpegOptions::new(out)
+        };
+        let values = |args: &[String], flag: &str| -> Vec<String> {
+            args.windows(2)
+                .filter(|w| w[0] == flag)
+                .map(|w| w[1].clone())
+                .collect()
+        };
+        let args = args_for(&opts);
+        assert_eq!(values(&args, "-ac"), ["1", "1"]);
+        assert_eq!(
+            values(&args, "-ar").last().map(String::as_str),
+            Some("16000")
+        );
+        assert!(args.ends_with(&["16000", "-f", "s16le", "pipe:1"].map(String::from)));
+
+        let split = FfmpegOptions {
+            mic: Some("mic"),
+            split_channels: true,
+            channels: 2,
+            ..opts
+        };
+        assert_eq!(values(&args_for(&split), "-ac"), ["2", "2"]);
+
+        let opts = FfmpegOptions {
+            mic: Some("mic"),
+            split_channels: true,
+            ..opts
+        };
+        assert!(build_ffmpeg_command(&opts).is_err());
+    }
+
+    #[test]
+    fn crash_safe_writes_segments() {
+        let out = Path::new("/tmp/call.ogg");
+        let opts = FfmpegOptions {
+            monitors: &["sink.monitor"],
+            segment_secs: Some(60),
+            ..FfmpegOptions::new(out)
+        };
+        let args = args_for(&opts);
+        assert_eq!(value_after(&args, "-segment_time").as_deref(), Some("60"));
+        assert_eq!(args.last().map(String::as_str), Some("/tmp/call-%03d.ogg"));
+        assert_eq!(value_after(&args, "-flush_packets").as_deref(), Some("1"));
+    }
+
+    #[test]
+    fn mic_cmd_file_keeps_only_the_latest_command() {
+        let path = std::env::temp_dir().join(format!("rcrd-test-mic-{}.cmd", std::process::id()));
+        prepare_mic_control(Some(&path), 1.0).unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "0.000 volume@micvol volume 1;\n"
+        );
+        write_mic_volume(&path, 1.0, 1.25, 0.0).unwrap();
+        write_mic_volume(&path, 1.0, 2.5, 1.0).unwrap();
+        write_mic_volume(&path, 1.0, 10.0, 0.0).unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "0.000 volume@micvol volume 1;\n10.000 volume@micvol volume 0;\n"
+        );
+        // A quieter `--mic-volume` is where the recording starts.
+        prepare_mic_control(Some(&path), 0.5).unwrap();
+        write_mic_volume(&path, 0.5, 3.0, 0.0).unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "0.000 volume@micvol volume 0.5;\n3.000 volume@micvol volume 0;\n"
+        );
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn mic_fade_ramps_to_the_target() {
+        let path = std::env::temp_dir().join(format!("rcrd-test-fade-{}.cmd", std::process::id()));
+        write_mic_fade(&path, 1.0, 2.0, 1.0, 0.0, 50).unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "0.000 volume@micvol volume 1;\n\
+             2.010 volume@micvol volume 0.8;\n\
+             2.020 volume@micvol volume 0.6;\n\
+             2.030 volume@micvol volume 0.4;\n\
+             2.040 volume@micvol volume 0.2;\n\
+             2.050 volume@micvol volume 0;\n"
+        );
+        let _ = fs::remove_file(path);
+
+        let mut buf = Vec::new();
+        send_mic_fade(&mut buf, 0.0, 1.0, 5).unwrap();
+        let sent = String::from_utf8(buf).unwrap();
+        assert_eq!(sent.lines().count(), 5);
+        assert!(sent.ends_with("cvolume@micvol -1 volume 1\n"));
+    }
+
+    #[test]
+    fn mic_volume_command_for_stdin() {
+        let mut buf = Vec::new();
+        send_mic_volume(&mut buf, 0.0).unwrap();
+        assert_eq!(buf, b"cvolume@micvol -1 volume 0\n");
+    }
+
+    #[test]
+    fn skip_start_discards_the_lead_in_of_every_output() {
+        let opts = FfmpegOptions {
+            monitors: &["sink.monitor"],
+            duration: Some(30),
+            want_transcript: true,
+            skip_start: Some(5),
+            ..FfmpegOptions::new(Path::new("/tmp/call.ogg"))
+        };
+        let args = args_for(&opts);
+        assert_eq!(value_after(&args, "-t").as_deref(), Some("30"));
+        assert_eq!(args.iter().filter(|a| *a == "-ss").count(), 2);
+        let ss = args.iter().position(|a| a == "-ss").unwrap();
+        let outfile = args.iter().position(|a| a == "/tmp/call.ogg").unwrap();
+        assert!(ss < outfile);
+    }
+
+    #[test]
+    fn passes_the_opus_application_and_frame_duration() {
+        let mut opts = FfmpegOptions {
+            monitors: &["sink.monitor"],
+            ..FfmpegOptions::new(Path::new("/tmp/call.ogg"))
+        };
+        let args = args_for(&opts);
+        assert_eq!(value_after(&args, "-application").as_deref(), Some("voip"));
+        assert_eq!(value_after(&args, "-frame_duration").as_deref(), Some("20"));
+
+        opts.encoding.opus_application = OpusApplication::Audio;
+        opts.encoding.opus_frame_ms = 2.5;
+        let args = args_for(&opts);
+        assert_eq!(value_after(&args, "-application").as_deref(), Some("audio"));
+        assert_eq!(
+            value_after(&args, "-frame_duration").as_deref(),
+            Some("2.5")
+        );
+
+        opts.encoding.opus_frame_ms = 30.0;
+        assert!(build_ffmpeg_command(&opts).is_err());
+    }
+
+    #[test]
+    fn flac_takes_a_compression_level_instead_of_opus_settings() {
+        let mut opts = FfmpegOptions {
+            monitors: &["sink.monitor"],
+            encoding: Encoding {
+                format: AudioFormat::Flac,
+                // Not an Opus frame length, but irrelevant to FLAC.
+                opus_frame_ms: 30.0,
+                ..Encoding::default()
+            },
+            ..FfmpegOptions::new(Path::new("/tmp/call.flac"))
+        };
+        let args = args_for(&opts);
+        assert_eq!(value_after(&args, "-c:a").as_deref(), Some("flac"));
+        assert_eq!(
+            value_after(&args, "-compression_level").as_deref(),
+            Some("5")
+        );
+        assert!(!args.iter().any(|a| a == "-application" || a == "-b:a"));
+
+        opts.encoding.flac_level = 12;
+        let args = args_for(&opts);
+        assert_eq!(
+            value_after(&args, "-compression_level").as_deref(),
+            Some("12")
+        );
+        opts.encoding.flac_level = 13;
+        let err = build_ffmpeg_command(&opts).unwrap_err();
+        assert!(err.to_string().contains("0 to 12"), "{err}");
+    }
+
+    #[test]
+    fn standby_only_meters_the_inputs() {
+        let opts = FfmpegOptions {
+            monitors: &["sink.monitor"],
+            standby: true,
+            metadata: &[("title".into(), "call".into())],
+            ..FfmpegOptions::new(Path::new("/tmp/call.ogg"))
+        };
+        let args = args_for(&opts);
+        assert!(args.ends_with(&["-f".into(), "null".into(), "-".into()]));
+        assert!(
+            !args
+                .iter()
+                .any(|a| a == "-c:a" || a == "-metadata" || a.ends_with("call.ogg"))
+        );
+        assert!(
+            value_after(&args, "-filter_complex")
+                .unwrap()
+                .contains("astats")
+        );
+    }
+
+    #[test]
+    fn nothing_to_record_is_an_error() {
+        assert!(build_ffmpeg_command(&FfmpegOptions::new(Path::new("x.ogg"))).is_err());
+    }
+
+    #[test]
+    fn spawn_goes_through_the_runner() {
+        let runner = MockRunner::default();
+        let out = Path::new("call.ogg");
+        let opts = FfmpegOptions {
+            monitors: &["sink.monitor"],
+            ..FfmpegOptions::new(out)
+        };
+        let levels = Arc::new(Mutex::new(Levels::default()));
+        let logs = Arc::new(Mutex::new(Vec::new()));
+        let progress = Arc::new(Mutex::new(Progress::default()));
+        assert!(spawn_ffmpeg(&runner, &opts, levels, progress, logs, false).is_err());
+        let calls = runner.calls.lock().unwrap();
+        assert_eq!(calls.len(), 1);
+        assert_eq!(
+            calls[0],
+            args_for(&FfmpegOptions {
+                progress_pipe: true,
+                ..opts
+            })
+        );
+        assert_eq!(
+            calls[0][1..5],
+            ["-hide_banner", "-y", "-nostats", "-progress"]
+        );
+    }
+
+    #[test]
+    fn log_lines_split_on_carriage_returns() {
+        let log = "Output #0, ogg\nsize=       0kB time=N/A\r\
+                   size=     256kB time=00:00:05.12 bitrate= 409.6kbits/s\r\n";
+        let mut lines = Vec::new();
+        for_each_line(log.as_bytes(), |l| lines.push(l.to_string()));
+        assert_eq!(
+            lines,
+            [
+                "Output #0, ogg",
+                "size=       0kB time=N/A",
+                "size=     256kB time=00:00:05.12 bitrate= 409.6kbits/s"
+            ]
+        );
+
+        let long = "x".repeat(MAX_LOG_LINE * 2);
+        let mut lens = Vec::new();
+        for_each_line(long.as_bytes(), |l| lens.push(l.len()));
+        assert_eq!(lens, [MAX_LOG_LINE]);
+    }
+
+    #[test]
+    fn progress_is_applied_a_block_at_a_time() {
+        let progress = Mutex::new(Progress::default());
+        read_progress(
+            "out_time_us=N/A\ntotal_size=N/A\nbitrate=N/A\nprogress=continue\n".as_bytes(),
+            &progress,
+        );
+        assert_eq!(*progress.lock().unwrap(), Progress::default());
+        read_progress(
+            "bitrate= 128.4kbits/s\ntotal_size=82176\nout_time_us=5120000\n\
+             out_time=00:00:05.120000\nspeed=1x\nprogress=continue\n\
+             out_time_us=5620000\ntotal_size=90000\n"
+                .as_bytes(),
+            &progress,
+        );
+        // The unfinished second block is not applied.
+        assert_eq!(
+            *progress.lock().unwrap(),
+            Progress {
+                time: Some(Duration::from_millis(5120)),
+                size_bytes: Some(82176),
+                bitrate_kbps: Some(128.4),
+            }
+        );
+    }
+
+    #[test]
+    fn parses_astats_lines() {
+        let re = level_regex();
+        let mut levels = Levels::default();
+        assert!(parse_level_line(
+            &re,
+            "[Parsed_ametadata_7 @ 0x1] lavfi.astats.1.Peak_level=-12.5",
+            &mut levels,
+            0.0
+        ));
+        assert!(parse_level_line(
+            &re,
+            "lavfi.astats.2.RMS_level=-30.0",
+            &mut levels,
+            0.0
+        ));
+        assert!(parse_level_line(
+            &re,
+            "lavfi.astats.Overall.Peak_level=-inf",
+            &mut levels,
+            0.0
+        ));
+        assert!(!parse_level_line(
+            &re,
+            "size=  12kB time=00:00:01",
+            &mut levels,
+            0.0
+        ));
+        assert_eq!(levels.peak_l, -12.5);
+        assert_eq!(levels.rms_r, -30.0);
+        assert_eq!(levels.monitor_peak, f32::NEG_INFINITY);
+        assert_eq!(levels.peak_hold_l, -12.5);
+        parse_level_line(&re, "lavfi.astats.1.Peak_level=-40.0", &mut levels, 0.0);
+        assert_eq!((levels.peak_l, levels.peak_hold_l), (-40.0, -12.5));
+    }
+
+    #[test]
+    fn counts_each_rise_to_the_clip_threshold() {
+        let re = level_regex();
+        let mut levels = Levels::default();
+        for line in [
+            "lavfi.astats.1.Peak_level=-3.0",
+            "lavfi.astats.1.Peak_level=0.000000",
+            // Both channels at once, and staying there, is one clip.
+            "lavfi.astats.2.Peak_level=0.000000",
+            "lavfi.astats.1.Peak_level=0.000000",
+            "lavfi.astats.1.Peak_level=-6.0",
+            "lavfi.astats.2.Peak_level=-6.0",
+            "lavfi.astats.2.Peak_level=0.000000",
+        ] {
+            parse_level_line(&re, line, &mut levels, 0.0);
+        }
+        assert_eq!(levels.clips, 2);
+        let mut levels = Levels::default();
+        parse_level_line(&re, "lavfi.astats.1.Peak_level=-0.5", &mut levels, -1.0);
+        assert_eq!(levels.clips, 1);
+    }
+
+    #[test]
+    fn held_peaks_fall_back_to_the_live_peak() {
+        let mut levels = Levels {
+            peak_l: -50.0,
+            peak_hold_l: -6.0,
+            peak_hold_r: -20.0,
+            ..Levels::default()
+        };
+        // 60 dB per 1.5 s: 4 dB every 100 ms.
+        levels.decay(Duration::from_millis(100));
+        assert!((levels.peak_hold_l + 10.0).abs() < 1e-4, "{levels:?}");
+        assert!((levels.peak_hold_r + 24.0).abs() < 1e-4, "{levels:?}");
+        // Never below the live peak, and dropped once out of the range while
+        // the channel is silent.
+        levels.decay(Duration::from_millis(1000));
+        assert_eq!(levels.peak_hold_l, -50.0);
+        assert_eq!(levels.peak_hold_r, f32::NEG_INFINITY);
+        levels.decay(Duration::from_secs(10));
+        assert_eq!(levels.peak_hold_l, -50.0);
+        assert_eq!(levels.peak_hold_r, f32::NEG_INFINITY);
+    }
+}
@@ -0,0 +1,29 @@
+//! Passive call recorder for PipeWire: taps the default sink monitor and
+//! microphone through ffmpeg, with optional live whisper.cpp transcription.
+//!
+//! Use [`Recorder`] to configure and start a session, then drive it through
+//! the returned [`RecordingHandle`].
+
+pub mod chapters;
+pub mod concat;
+pub mod config;
+pub mod cue;
+pub mod devices;
+pub mod disk;
+pub mod error;
+pub mod ffmpeg;
+pub mod logging;
+pub mod models;
+pub mod output;
+pub mod playback;
+pub mod probe;
+pub mod process;
+mod recorder;
+pub mod session;
+pub mod sync;
+pub mod tags;
+pub mod transcript;
+pub mod trim;
+pub mod webhook;
+
+pub use recorder::{Devices, Marker, Recorder, Recording, RecordingHandle};
@@ -0,0 +1,55 @@
+//! Diagnostic log for `--log-file`: `tracing` events written to a file with
+//! a wall-clock and a monotonic timestamp, independent of the TUI.
+
+use std::fmt;
+use std::fs::OpenOptions;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use anyhow::{Context, Result};
+use tracing::Level;
+use tracing_subscriber::filter::Targets;
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::time::FormatTime;
+use tracing_subscriber::layer::SubscriberExt;
+
+use crate::output::{format_rfc3339, now};
+
+/// `2024-05-06T09:30:00+02:00 +12.345s`: the wall clock, then the time since
+/// the log was opened, which doesn't jump when the clock is adjusted.
+struct Timestamps {
+    started: Instant,
+}
+
+impl FormatTime for Timestamps {
+    fn format_time(&self, w: &mut Writer<'_>) -> fmt::Result {
+        write!(
+            w,
+            "{} +{:.3}s",
+            format_rfc3339(now(false).0),
+            self.started.elapsed().as_secs_f64()
+        )
+    }
+}
+
+/// Appends every `rcrd` event at debug level and above to `path`. Each
+/// event is written in one go, so the log survives a crash or a killed
+/// terminal. Can only be installed once per process.
+pub fn init(path: &Path) -> Result<()> {
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("cannot open log file {}", path.display()))?;
+    let layer = tracing_subscriber::fmt::layer()
+        .with_writer(Mutex::new(file))
+        .with_ansi(false)
+        .with_timer(Timestamps {
+            started: Instant::now(),
+        });
+    let subscriber = tracing_subscriber::registry()
+        .with(layer)
+        .with(Targets::new().with_target("rcrd", Level::DEBUG));
+    tracing::subscriber::set_global_default(subscriber).context("a logger is already installed")
+}
@@ -1,53 +1,238 @@
-mod config;
-mod devices;
-mod ffmpeg;
-mod output;
-mod transcript;
+mod bench;
+mod check;
+mod doctor;
+mod events;
+mod markers;
+mod recover;
+mod theme;
 mod ui;
 
-use std::fs::File;
-use std::io::Write;
-use std::path::PathBuf;
-use std::process::Child;
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::{Arc, Mutex};
-use std::time::{Duration, Instant};
-
-use anyhow::{Result, anyhow};
-use clap::Parser;
-use serde::Serialize;
-
-use crate::config::load_config;
-use crate::devices::detect_defaults;
-use crate::ffmpeg::{prepare_mic_control, spawn_ffmpeg};
-use crate::output::{default_output_name, git_revision};
-use crate::transcript::{TransSegment, start_transcriber};
-use crate::ui::{RecorderState, run_app};
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::Duration;
+
+use anyhow::{Context, Result, anyhow};
+use clap::{Parser, Subcommand};
+
+use rcrd::Recorder;
+use rcrd::config::{Config, config_sources, load_base_config, load_config, save_config};
+use rcrd::cue::save_cue;
+use rcrd::disk::{format_bytes, free_bytes};
+use rcrd::error::ErrorKind;
+use rcrd::ffmpeg::{
+    AudioFormat, DEFAULT_FLAC_LEVEL, DEFAULT_SAMPLE_RATE, Encoding, FfmpegOptions, MAX_FLAC_LEVEL,
+    MAX_MIC_VOLUME, OpusApplication, SUPPORTED_SAMPLE_RATES, SyncMode, parse_raw_input,
+    wait_for_level,
+};
+use rcrd::models::{cached_model_path, resolve_model};
+use rcrd::output::{
+    MarkerFormat, Metadata, check_writable, default_output_name, discard_recording, format_offset,
+    format_rfc3339, git_revision, marker_journal_path, now, run_post_command, save_markers,
+    save_metadata,
+};
+use rcrd::playback::Player;
+use rcrd::probe::probe;
+use rcrd::process::{SystemRunner, set_tool_path};
+use rcrd::tags::{Tags, parse_tag};
+use rcrd::transcript::export::{self, LiveTranscript, TranscriptInfo, start_live_transcript};
+use rcrd::transcript::{
+    AUTO_LANGUAGE, DEFAULT_LENGTH_MS, DEFAULT_STEP_MS, MAX_PROMPT_CHARS, Windowing, merge_segments,
+    prepare_prompt, resolve_backend, transcribe_file,
+};
+use rcrd::trim::{TrimOptions, trim_silence};
+use rcrd::webhook::start_webhook;
+
+use crate::check::run_check;
+use crate::doctor::run_doctor;
+use crate::events::run_events;
+use crate::markers::run_markers_apply;
+use crate::recover::run_recover;
+use crate::theme::Theme;
+use crate::ui::{LayoutMode, LevelHistory, MarkerClock, RecorderState, TranscriptView, run_app};
 
 /// Record a call (Teams, Zoom, etc.) by tapping the current PipeWire sink monitor and microphone.
 #[derive(Parser, Debug)]
 #[command(author, version, about)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Commands>,
+
     /// Output file path (default: rcrd-call-YYYYmmdd-HHMMSS.ogg)
     #[arg(short, long)]
     output: Option<PathBuf>,
 
-    /// Stop after this many seconds (omit to record until Ctrl+C or 'q')
-    #[arg(short, long)]
+    /// Stop after this long: plain seconds or e.g. `90s`, `30m`, `1h30m`
+    /// (omit to record until Ctrl+C or 'q')
+    #[arg(short, long, value_parser = parse_duration)]
     duration: Option<u32>,
 
+    /// Stop at this length even without --duration, as a safety cap
+    /// (default: the config's `max_recording_seconds`, 8 hours; `0` or
+    /// `unlimited` removes it)
+    #[arg(long, value_name = "DURATION", value_parser = parse_max_duration)]
+    max_duration: Option<u32>,
+
+    /// Refuse to start unless this many gigabytes are free where the file is
+    /// written (without it, rcrd only warns when --duration won't fit)
+    #[arg(long, value_name = "GB")]
+    require_space: Option<f64>,
+
     /// PipeWire sink node name to tap (monitor side). Defaults to current default sink.
+    /// Repeat to mix several sinks, e.g. a call and music on another output.
     #[arg(long)]
-    sink: Option<String>,
+    sink: Vec<String>,
+
+    /// How staggered inputs are aligned before mixing: none, resample (pad with
+    /// silence from the input timestamps) or wallclock (timestamp on arrival).
+    #[arg(long, value_enum, default_value_t = SyncMode::None)]
+    sync_mode: SyncMode,
+
+    /// Wait with nothing written until audio arrives, then start recording;
+    /// the level is taken from the monitor, or the mic without one.
+    #[arg(long, default_value_t = false)]
+    arm: bool,
+
+    /// Peak level (dBFS) that starts an `--arm`ed recording.
+    #[arg(long, value_name = "DB", default_value_t = -40.0, allow_negative_numbers = true, requires = "arm")]
+    arm_threshold: f32,
+
+    /// Codec of the file: opus (in Ogg) or lossless flac. Generated names get
+    /// its extension; an --output name should match it.
+    #[arg(long, value_enum, default_value_t = AudioFormat::Opus)]
+    format: AudioFormat,
+
+    /// FLAC compression level, 0 (fastest) to 12 (smallest); default 5.
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u8).range(0..=MAX_FLAC_LEVEL as i64))]
+    flac_level: Option<u8>,
+
+    /// Opus encoder tuning: voip favours speech, audio suits music, lowdelay minimises latency.
+    #[arg(long, value_enum, default_value_t = OpusApplication::Voip)]
+    opus_mode: OpusApplication,
+
+    /// Opus frame length in ms (2.5, 5, 10, 20, 40, 60, 80, 100 or 120); longer frames
+    /// save a little bitrate on speech.
+    #[arg(long, value_name = "MS", default_value_t = 20.0)]
+    opus_frame_ms: f32,
+
+    /// After recording, cut silence off the start and end of the file; markers
+    /// and transcript times are shifted to match.
+    #[arg(long, default_value_t = false)]
+    trim_silence: bool,
+
+    /// With --trim-silence, keep the untouched file as `<name>.untrimmed.ogg`.
+    #[arg(long, default_value_t = false, requires = "trim_silence")]
+    keep_original: bool,
+
+    /// Level below which --trim-silence treats audio as silence.
+    #[arg(
+        long,
+        value_name = "DB",
+        default_value_t = -50.0,
+        allow_negative_numbers = true,
+        requires = "trim_silence"
+    )]
+    trim_threshold: f32,
+
+    /// Shortest silence --trim-silence cuts, in seconds.
+    #[arg(long, value_name = "SECS", default_value_t = 1.0, value_parser = parse_trim_min_secs, requires = "trim_silence")]
+    trim_min_secs: f64,
+
+    /// Literal ffmpeg input to capture instead of any sink monitor, e.g.
+    /// `--raw-input "-f jack -i rcrd"`. Split into arguments without a shell.
+    #[arg(
+        long,
+        value_name = "FFMPEG_INPUT",
+        allow_hyphen_values = true,
+        conflicts_with_all = ["mic_only", "monitor"]
+    )]
+    raw_input: Option<String>,
+
+    /// Input to capture instead of `<sink>.monitor`, for virtual sinks with differently named monitors.
+    /// Repeatable; replaces the monitors of every --sink.
+    #[arg(long, visible_alias = "sink-monitor", conflicts_with = "mic_only")]
+    monitor: Vec<String>,
 
     /// PipeWire source node name to tap (microphone). Defaults to current default source.
     #[arg(long)]
     source: Option<String>,
 
+    /// Output sample rate in Hz; inputs at other rates are resampled.
+    /// One of 8000, 12000, 16000, 24000, 48000.
+    #[arg(long, default_value_t = DEFAULT_SAMPLE_RATE, value_parser = parse_sample_rate)]
+    sample_rate: u32,
+
+    /// Write the recording as segments of SECS seconds (default 60): `<name>-000.ogg`, ...
+    /// so a crash or power loss costs at most the last segment.
+    #[arg(long, value_name = "SECS", num_args = 0..=1, default_missing_value = "60")]
+    crash_safe: Option<u32>,
+
+    /// If ffmpeg dies early (e.g. PipeWire restarted), respawn it up to N times (default 5)
+    /// into `<name>-part2.ogg`, ... with the marker and transcript timeline continuing.
+    #[arg(long, value_name = "N", num_args = 0..=1, default_missing_value = "5")]
+    reconnect: Option<u32>,
+
+    /// Always run pw-dump instead of reusing default devices detected in the last 10 seconds.
+    #[arg(long, default_value_t = false)]
+    no_cache: bool,
+
+    /// Retry finding the audio devices for up to SECS seconds while PipeWire
+    /// is still starting (e.g. in an autostart entry).
+    #[arg(long, value_name = "SECS")]
+    wait_for_audio: Option<u64>,
+
+    /// Start ffmpeg immediately but discard the first SECS seconds of audio.
+    #[arg(long, value_name = "SECS")]
+    skip_start: Option<u32>,
+
+    /// Write mic volume commands (asendcmd) to this file instead of a temporary one;
+    /// it is kept after the run for inspection.
+    #[arg(long, value_name = "PATH")]
+    mic_cmd_file: Option<PathBuf>,
+
+    /// Downmix the file to mono (half the size; default from the `channels` config).
+    #[arg(long, default_value_t = false, conflicts_with = "split_channels")]
+    mono: bool,
+
     /// Do not record microphone; capture only the remote/output side.
     #[arg(long, default_value_t = false)]
     no_mic: bool,
 
+    /// Start the mic at this volume (0.0-2.0, default 1.0), e.g. 0.6 for a hot mic;
+    /// unmuting with `m` returns to it.
+    #[arg(long, value_name = "FLOAT", value_parser = parse_mic_volume, conflicts_with = "no_mic")]
+    mic_volume: Option<f32>,
+
+    /// Also play what is being recorded to a sink, e.g. headphones, to monitor it live.
+    /// Plays to the default sink unless --playback-sink names one.
+    #[arg(long, default_value_t = false)]
+    monitor_playback: bool,
+
+    /// Sink --monitor-playback plays to; it must not be a sink whose monitor is recorded.
+    #[arg(long, value_name = "SINK", requires = "monitor_playback")]
+    playback_sink: Option<String>,
+
+    /// Record only the microphone; do not tap any sink monitor (e.g. voice memos).
+    #[arg(long, default_value_t = false, conflicts_with = "no_mic")]
+    mic_only: bool,
+
+    /// Record remote audio on the left channel and the mic on the right instead of mixing.
+    /// Live transcripts are then labelled per speaker.
+    #[arg(long, default_value_t = false)]
+    split_channels: bool,
+
+    /// Speaker labels for the remote and local channels (with --split-channels).
+    #[arg(long, value_delimiter = ',', num_args = 2, value_names = ["REMOTE", "LOCAL"])]
+    speaker_labels: Option<Vec<String>>,
+
+    /// Warn if the monitor is still silent after this many seconds (default 5).
+    #[arg(long, value_name = "SECS")]
+    silence_check: Option<u64>,
+
+    /// Skip the startup check for a silent monitor.
+    #[arg(long, default_value_t = false)]
+    no_silence_check: bool,
+
     /// Enable debug mode (prints FFmpeg command and output, disables TUI).
     #[arg(long, default_value_t = false)]
     debug: bool,
@@ -56,230 +241,1339 @@ struct Args {
     #[arg(long)]
     model: Option<PathBuf>,
 
+    /// whisper.cpp model by name (e.g. base.en), downloaded to the config dir on first use.
+    #[arg(long, value_name = "NAME", conflicts_with = "model")]
+    model_name: Option<String>,
+
     /// Transcription language (e.g., en, fr).
     #[arg(long)]
     lang: Option<String>,
 
-    /// Save transcript to CSV (timecode,text) when recording stops.
+    /// Detect the language of every window and tag each segment with it, for calls
+    /// that switch languages; needs a multilingual model (not `.en`) and more compute.
+    #[arg(long, default_value_t = false, conflicts_with = "lang")]
+    language_per_segment: bool,
+
+    /// Transcribe from the start with the transcript pane hidden; `t` shows it, `T` pauses.
     #[arg(long, default_value_t = false)]
+    transcribe: bool,
+
+    /// Initial prompt biasing whisper toward names and jargon, e.g. "Ana, Kubernetes, OKRs"
+    /// (default: the config's `transcribe_prompt`); cut to 800 characters.
+    #[arg(long, value_name = "TEXT", conflicts_with = "prompt_file")]
+    prompt: Option<String>,
+
+    /// Read the initial prompt from a file, such as a glossary with one term per line.
+    #[arg(long, value_name = "PATH")]
+    prompt_file: Option<PathBuf>,
+
+    /// Drop transcript segments below this confidence (0..1) from saved output; they are dimmed live.
+    #[arg(long, value_name = "P", value_parser = parse_confidence)]
+    min_confidence: Option<f32>,
+
+    /// Transcribe every this much new audio; smaller steps cut latency but
+    /// run whisper more often, raising CPU/GPU load.
+    #[arg(long, value_name = "MS", default_value_t = DEFAULT_STEP_MS)]
+    transcribe_step_ms: u32,
+
+    /// Audio each transcription window covers, at least the step; the extra
+    /// is context repeated from the previous window. Defaults to 8000 or the step.
+    #[arg(long, value_name = "MS")]
+    transcribe_length_ms: Option<u32>,
+
+    /// Don't transcribe windows whose RMS level is below this (dBFS, e.g. -45).
+    #[arg(long, value_name = "DB", allow_negative_numbers = true, value_parser = parse_vad_threshold)]
+    vad_threshold: Option<f32>,
+
+    /// Merge transcript segments separated by at most this much silence before saving.
+    #[arg(long, value_name = "MS")]
+    merge_gap_ms: Option<u32>,
+
+    /// Save the transcript (see `--transcript-format`) when recording stops.
+    #[arg(long, default_value_t = false, overrides_with = "no_save_transcript")]
     save_transcript: bool,
 
+    /// Don't save the transcript, even if `always_save_transcript` is set in the config.
+    #[arg(long, default_value_t = false, overrides_with = "save_transcript")]
+    no_save_transcript: bool,
+
+    /// Leave the notes typed with `a` out of the saved transcript.
+    #[arg(long, default_value_t = false)]
+    no_manual_notes: bool,
+
+    /// Formats the saved transcript is written in (`json` keeps every segment's metadata).
+    #[arg(long, value_delimiter = ',', default_value = "csv")]
+    transcript_format: Vec<export::Format>,
+
+    /// Save markers to `<file>.json` (the default unless `always_save_markers` is off).
+    #[arg(long, default_value_t = false, overrides_with = "no_save_markers")]
+    save_markers: bool,
+
+    /// Don't write the markers file for this session.
+    #[arg(long, default_value_t = false, overrides_with = "save_markers")]
+    no_save_markers: bool,
+
+    /// Formats the saved markers are written in: `json` (`<file>.json`)
+    /// and/or `cue` (`<file>.cue`, a CUE sheet for editors).
+    #[arg(long, value_delimiter = ',', default_value = "json")]
+    marker_format: Vec<MarkerFormat>,
+
     /// Whisper backend: vulkan or openblas (defaults to config or vulkan).
     #[arg(long)]
     backend: Option<String>,
+
+    /// Fail instead of falling back to openblas when vulkan has no usable GPU.
+    #[arg(long, default_value_t = false)]
+    strict_backend: bool,
+
+    /// Shell command to run once the recording is finalized.
+    /// Placeholders: {file}, {transcript}, {markers}.
+    #[arg(long)]
+    post_command: Option<String>,
+
+    /// Append each finished transcript segment to PATH as it is produced; the
+    /// extension picks the format (`.csv`, `.srt`, `.vtt`, or `.jsonl` for JSON lines).
+    #[arg(long, value_name = "PATH")]
+    transcript_live: Option<PathBuf>,
+
+    /// POST each finished transcript segment as JSON to this URL.
+    #[arg(long, value_name = "URL")]
+    webhook: Option<String>,
+
+    /// Play the recording back once it is finalized (uses ffplay or pw-play).
+    #[arg(long, default_value_t = false)]
+    play: bool,
+
+    /// Remember the sink/source used for this session as the defaults for later runs.
+    #[arg(long, default_value_t = false)]
+    remember_devices: bool,
+
+    /// Show marker times relative to the start or as wall-clock time of day.
+    #[arg(long, value_enum, default_value_t = MarkerClock::Relative)]
+    marker_clock: MarkerClock,
+
+    /// Use UTC timestamps for the filename and metadata instead of local time.
+    #[arg(long, default_value_t = false)]
+    utc: bool,
+
+    /// Maximum TUI redraws per second; the screen is only redrawn when something changed.
+    #[arg(long, default_value_t = 10, value_parser = clap::value_parser!(u32).range(1..=60))]
+    fps: u32,
+
+    /// With --duration, show a large countdown over the last SECS seconds (0 disables).
+    #[arg(
+        long = "count-down-to-stop",
+        visible_alias = "countdown",
+        value_name = "SECS",
+        default_value_t = 10
+    )]
+    count_down_to_stop: u64,
+
+    /// Peak level counted as clipping: lights the CLIP badge and is counted in the summary.
+    #[arg(long, value_name = "DB", default_value_t = 0.0, allow_negative_numbers = true, value_parser = parse_clip_threshold)]
+    clip_threshold_db: f32,
+
+    /// Two-line TUI for short terminals (used automatically below 14 rows).
+    #[arg(long, default_value_t = false)]
+    compact: bool,
+
+    /// Push-to-talk: hold Space to mute the mic (toggles on terminals without key-release events).
+    #[arg(long, default_value_t = false)]
+    ptt: bool,
+
+    /// Tag the recording, e.g. `project=apollo` or `participants="Ana, Bo"`; repeatable.
+    /// Saved in the metadata and written into the file.
+    #[arg(long = "tag", value_name = "KEY=VALUE", value_parser = parse_tag)]
+    tags: Vec<(String, String)>,
+
+    /// Ask for more tags on the terminal before recording starts.
+    #[arg(long, default_value_t = false, conflicts_with = "json_events")]
+    ask_tags: bool,
+
+    /// Replace the TUI with JSON-lines events on stdout (commands on stdin: `marker [note]`, `pause`, `resume`, `stop`, `discard`).
+    #[arg(long, default_value_t = false)]
+    json_events: bool,
+
+    /// Render the TUI without colors (also when `NO_COLOR` is set).
+    #[arg(long, default_value_t = false)]
+    no_color: bool,
+
+    /// Append timestamped diagnostics (devices, ffmpeg, transcriber, keys) to PATH.
+    #[arg(long, value_name = "PATH", global = true)]
+    log_file: Option<PathBuf>,
+
+    /// Apply this preset from the config's `profiles` (default: `default_profile`).
+    #[arg(long, value_name = "NAME", global = true, alias = "config-profile")]
+    profile: Option<String>,
+
+    /// Read this config file last, over the system and user configs.
+    #[arg(long = "config", value_name = "PATH", global = true)]
+    config_file: Option<PathBuf>,
+
+    /// Run this ffmpeg instead of the one on PATH (default: the config's
+    /// `ffmpeg_path`); an ffprobe next to it is used too.
+    #[arg(long, value_name = "PATH", global = true)]
+    ffmpeg_bin: Option<PathBuf>,
+
+    /// Run this pw-dump instead of the one on PATH (default: the config's `pw_dump_path`).
+    #[arg(long, value_name = "PATH", global = true)]
+    pw_dump_bin: Option<PathBuf>,
+
+    /// Print the resolved config and the settings these flags give as JSON, then exit.
+    #[arg(long, default_value_t = false)]
+    print_config: bool,
+}
+
+#[derive(Subcommand, Debug)]
+enum Commands {
+    /// Print rcrd, ffmpeg and whisper versions and which audio tools are available.
+    Doctor,
+    /// Transcribe an existing recording offline and write the transcript next to it.
+    Transcribe(TranscribeArgs),
+    /// Compare transcription speed of the available whisper backends.
+    Bench(BenchArgs),
+    /// Decode a recording in full to confirm it is intact and holds audio.
+    Check(CheckArgs),
+    /// Work with saved markers.
+    #[command(subcommand)]
+    Markers(MarkersCommand),
+    /// Finish a recording rcrd was killed during: repair its file and save its markers and transcript.
+    Recover {
+        /// The recording, or the `<file>.session.json` saved next to it.
+        file: PathBuf,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum MarkersCommand {
+    /// Embed markers as chapters into an audio file, e.g. one edited after recording.
+    Apply {
+        /// Audio file to add the chapters to; it is rewritten in place without re-encoding.
+        audio: PathBuf,
+        /// Markers saved with the recording (`<file>.json`).
+        markers: PathBuf,
+    },
+}
+
+#[derive(clap::Args, Debug)]
+struct CheckArgs {
+    /// Recording to check.
+    file: PathBuf,
+
+    /// Level below which audio counts as silence.
+    #[arg(long, value_name = "DB", default_value_t = -50.0, allow_negative_numbers = true)]
+    threshold: f32,
+
+    /// Shortest silence reported at either end, in seconds.
+    #[arg(long, value_name = "SECS", default_value_t = 1.0)]
+    min_silence_secs: f64,
+}
+
+#[derive(clap::Args, Debug)]
+struct BenchArgs {
+    /// Path to whisper.cpp model (gguf); defaults to the config.
+    #[arg(long)]
+    model: Option<PathBuf>,
+
+    /// whisper.cpp model by name (e.g. base.en), downloaded on first use.
+    #[arg(long, value_name = "NAME", conflicts_with = "model")]
+    model_name: Option<String>,
+
+    /// Audio clip to transcribe; defaults to a generated 30 s tone.
+    #[arg(long)]
+    clip: Option<PathBuf>,
+
+    /// Threads passed to whisper-cli.
+    #[arg(long, default_value_t = 8)]
+    threads: usize,
+
+    /// Internal: benchmark a single backend and print the result as JSON.
+    #[arg(long, hide = true)]
+    run_one: Option<String>,
 }
 
-#[derive(Serialize)]
-pub struct Marker {
-    timestamp: f64,
-    note: String,
+#[derive(clap::Args, Debug)]
+struct TranscribeArgs {
+    /// Audio file to transcribe (anything ffmpeg can decode).
+    file: PathBuf,
+
+    /// Path to whisper.cpp model (gguf); defaults to the config.
+    #[arg(long)]
+    model: Option<PathBuf>,
+
+    /// whisper.cpp model by name (e.g. base.en), downloaded on first use.
+    #[arg(long, value_name = "NAME", conflicts_with = "model")]
+    model_name: Option<String>,
+
+    /// Transcription language (e.g., en, fr).
+    #[arg(long)]
+    lang: Option<String>,
+
+    /// Whisper backend: vulkan or openblas.
+    #[arg(long)]
+    backend: Option<String>,
+
+    /// Fail instead of falling back to openblas when vulkan has no usable GPU.
+    #[arg(long, default_value_t = false)]
+    strict_backend: bool,
+
+    /// Initial prompt biasing whisper toward names and jargon (default: the
+    /// config's `transcribe_prompt`); cut to 800 characters.
+    #[arg(long, value_name = "TEXT", conflicts_with = "prompt_file")]
+    prompt: Option<String>,
+
+    /// Read the initial prompt from a file, such as a glossary with one term per line.
+    #[arg(long, value_name = "PATH")]
+    prompt_file: Option<PathBuf>,
+
+    /// Output formats to write.
+    #[arg(long, value_delimiter = ',', default_value = "csv,srt,vtt")]
+    format: Vec<export::Format>,
+}
+
+fn main() {
+    if let Err(err) = run() {
+        eprintln!("Error: {err:?}");
+        std::process::exit(ErrorKind::of(&err).map_or(1, ErrorKind::exit_code));
+    }
 }
 
-fn main() -> Result<()> {
+fn run() -> Result<()> {
     let args = Args::parse();
-    let cfg = load_config().unwrap_or_default();
-    let defaults = detect_defaults().unwrap_or_default();
-
-    let sink = args
-        .sink
-        .or(defaults.sink)
-        .ok_or_else(|| anyhow!("Could not detect default sink"))?;
-    let source_name = if args.no_mic {
-        None
+    if let Some(path) = &args.log_file {
+        rcrd::logging::init(path)?;
+        tracing::info!("rcrd {} starting", env!("CARGO_PKG_VERSION"));
+    }
+    set_tool_paths(&args)?;
+    match args.command {
+        Some(Commands::Doctor) => {
+            run_doctor();
+            return Ok(());
+        }
+        Some(Commands::Check(c)) => {
+            return run_check(&c.file, c.threshold, c.min_silence_secs);
+        }
+        Some(Commands::Markers(MarkersCommand::Apply { audio, markers })) => {
+            return run_markers_apply(&audio, &markers);
+        }
+        Some(Commands::Recover { file }) => return run_recover(&file),
+        Some(Commands::Transcribe(t)) => {
+            return run_transcribe(t, args.profile.as_deref(), args.config_file.as_deref());
+        }
+        Some(Commands::Bench(b)) => {
+            let cfg = profile_config(args.profile.as_deref(), args.config_file.as_deref())?;
+            let model = resolve_model_arg(b.model, b.model_name.as_deref(), &cfg)?;
+            return match &b.run_one {
+                Some(backend) => bench::run_one(&model, b.clip.as_deref(), backend, b.threads),
+                None => bench::run_bench(&model, b.clip.as_deref(), b.threads),
+            }
+            .context(ErrorKind::Transcriber);
+        }
+        None => {}
+    }
+    let windowing = Windowing::new(
+        args.transcribe_step_ms,
+        args.transcribe_length_ms
+            .unwrap_or(DEFAULT_LENGTH_MS.max(args.transcribe_step_ms)),
+    )
+    .context(ErrorKind::Usage)?;
+    let raw_input = args
+        .raw_input
+        .as_deref()
+        .map(parse_raw_input)
+        .transpose()
+        .context(ErrorKind::Usage)?;
+    if args.flac_level.is_some() && args.format != AudioFormat::Flac {
+        return Err(anyhow!("--flac-level needs --format flac").context(ErrorKind::Usage));
+    }
+    let encoding = Encoding {
+        format: args.format,
+        opus_application: args.opus_mode,
+        opus_frame_ms: args.opus_frame_ms,
+        flac_level: args.flac_level.unwrap_or(DEFAULT_FLAC_LEVEL),
+    };
+    let cfg = profile_config(args.profile.as_deref(), args.config_file.as_deref())?;
+    if args.print_config {
+        return print_config(&args, &cfg);
+    }
+    let max_duration = max_duration(&args, &cfg);
+    let prompt = whisper_prompt(args.prompt.clone(), args.prompt_file.as_deref(), &cfg)?;
+    let theme = Theme::detect(args.no_color, cfg.theme.as_ref()).context(ErrorKind::Usage)?;
+    let mut tags: Tags = args.tags.iter().cloned().collect();
+    if args.ask_tags {
+        prompt_tags(&mut tags).context(ErrorKind::Usage)?;
+    }
+    // Read the clock before any thread is spawned: the local offset is
+    // unavailable once the process is multi-threaded.
+    let (started_at, local_ok) = now(args.utc || cfg.use_utc);
+    let clock_warning = (!local_ok)
+        .then(|| "Warning: local timezone unavailable, using UTC timestamps".to_string());
+    if let Some(warning) = &clock_warning {
+        eprintln!("{warning}");
+    }
+    let recorder = device_recorder(&args, &cfg, raw_input.clone());
+    let devices = recorder.resolve()?;
+    // The first sink names the file and is the one `--remember-devices` keeps.
+    let sink = devices.sinks.first().cloned();
+    let started_at = if args.arm {
+        let monitor_names: Vec<&str> = devices.monitors.iter().map(String::as_str).collect();
+        let has_remote = !monitor_names.is_empty() || raw_input.is_some();
+        // The remote side starts a call; the mic only when there is nothing else.
+        let opts = FfmpegOptions {
+            monitors: &monitor_names,
+            raw_input: raw_input.as_deref(),
+            mic: devices.mic.as_deref().filter(|_| !has_remote),
+            sample_rate: args.sample_rate,
+            sync_mode: args.sync_mode,
+            ..FfmpegOptions::new(Path::new("-"))
+        };
+        eprintln!(
+            "Armed: recording starts when the {} reaches {} dBFS (Ctrl+C to give up)",
+            if has_remote { "monitor" } else { "mic" },
+            args.arm_threshold
+        );
+        let waited =
+            wait_for_level(&SystemRunner, &opts, args.arm_threshold).context(ErrorKind::Ffmpeg)?;
+        eprintln!("Audio detected after {}s", waited.as_secs());
+        // Threads have run by now, so the local offset can't be read again;
+        // keep the one from startup.
+        time::OffsetDateTime::now_utc().to_offset(started_at.offset())
     } else {
-        Some(
-            args.source
-                .or(defaults.source)
-                .ok_or_else(|| anyhow!("Could not detect default source"))?,
-        )
+        started_at
     };
-    let monitor = format!("{sink}.monitor");
-    let outfile = args
-        .output
-        .unwrap_or_else(|| default_output_name(cfg.file_prefix.as_str()));
+    let template = cfg
+        .filename_template
+        .clone()
+        .unwrap_or_else(|| format!("{}{{date}}-{{time}}", cfg.file_prefix));
+    let outfile = args.output.unwrap_or_else(|| {
+        default_output_name(
+            &template,
+            sink.as_deref().unwrap_or_default(),
+            started_at,
+            args.format,
+        )
+    });
+    // Segments, reconnect parts and sidecars all go next to the output file.
+    let mut writable = vec![outfile.as_path()];
+    writable.extend(args.mic_cmd_file.as_deref());
+    check_writable(&writable).context(ErrorKind::Output)?;
 
-    let mic_cmd_path = if source_name.is_some() {
-        Some(prepare_mic_control()?)
-    } else {
-        None
+    let whisper_model = match &args.model_name {
+        Some(name) => Some(resolve_model(name).context(ErrorKind::Transcriber)?),
+        None => args.model.or(cfg.whisper_model.clone()),
     };
-    let audio_level = Arc::new(Mutex::new(ffmpeg::Levels::default()));
-    let recent_logs = Arc::new(Mutex::new(Vec::new()));
-    let transcript = Arc::new(Mutex::new(Vec::<TransSegment>::new()));
-    let transcription_flag = Arc::new(AtomicBool::new(false));
-    let transcription_stop = Arc::new(AtomicBool::new(false));
-    let transcription_reset = Arc::new(AtomicBool::new(false));
-    let base_offset_ms = Arc::new(std::sync::atomic::AtomicI64::new(0));
-    let whisper_model = args.model.or(cfg.whisper_model.clone());
     let backend = args
         .backend
         .or(Some(cfg.backend.clone()))
         .unwrap_or_else(|| "vulkan".into());
-    let language = Arc::new(Mutex::new(
+    let language = if args.language_per_segment {
+        AUTO_LANGUAGE.to_string()
+    } else {
         args.lang
             .or(cfg.language.clone())
-            .unwrap_or_else(|| "en".into()),
-    ));
-    let want_transcript = whisper_model.is_some();
+            .unwrap_or_else(|| "en".into())
+    };
+    if args.language_per_segment
+        && let Some(model) = &whisper_model
+        && english_only(model)
+    {
+        eprintln!(
+            "Warning: {} is an English-only model; --language-per-segment needs a multilingual one",
+            model.display()
+        );
+    }
     let whisper_threads = 8;
 
+    // A mono config default gives way to an explicit --split-channels.
+    let mono = args.mono || (cfg.channels == 1 && !args.split_channels);
+    check_free_space(
+        &outfile,
+        args.require_space,
+        args.duration.map(|secs| {
+            encoding.bytes_per_sec(args.sample_rate, if mono { 1 } else { 2 }) * u64::from(secs)
+        }),
+    )?;
+
     if args.debug {
         println!("Debug mode enabled.");
-        println!("Sink: {}", sink);
-        println!("Monitor: {}", monitor);
-        println!("Mic: {:?}", source_name);
+        println!("Profile: {:?}", cfg.active_profile);
+        println!("Sinks: {:?}", devices.sinks);
+        println!("Monitors: {:?}", devices.monitors);
+        println!("Raw input: {:?}", raw_input);
+        println!("Mic: {:?}", devices.mic);
         println!("Output: {}", outfile.display());
+        println!("Sample rate: {}", args.sample_rate);
+        println!("Mono: {}", mono);
+        println!(
+            "Sync mode: {} ({})",
+            args.sync_mode.as_str(),
+            args.sync_mode.tradeoff()
+        );
         println!("Whisper model: {:?}", whisper_model);
         println!("Whisper backend: {}", backend);
-        if let Ok(lang) = language.lock() {
-            println!("Language: {}", *lang);
-        }
-        if want_transcript {
+        println!("Language: {}", language);
+        if whisper_model.is_some() {
             println!("Whisper threads: {}", whisper_threads);
+            println!(
+                "Transcription window: {} ms every {} ms",
+                windowing.length_ms(),
+                windowing.step_ms()
+            );
         }
     }
 
-    let mut child = spawn_ffmpeg(
-        &monitor,
-        source_name.as_deref(),
-        mic_cmd_path.as_deref(),
-        &outfile,
-        args.duration,
-        audio_level.clone(),
-        recent_logs.clone(),
-        args.debug,
-        want_transcript,
-    )?;
-
-    // Start transcription reader if a model is provided
-    let mut transcript_handle = None;
-    if want_transcript {
-        if let Some(stdout) = child.stdout.take() {
-            if let Some(model_path) = whisper_model.clone() {
-                transcript_handle = Some(start_transcriber(
-                    stdout,
-                    model_path,
-                    language.clone(),
-                    transcript.clone(),
-                    transcription_flag.clone(),
-                    transcription_stop.clone(),
-                    backend.clone(),
-                    base_offset_ms.clone(),
-                    transcription_reset.clone(),
-                    whisper_threads,
-                ));
-            }
-        }
+    let labels = match &args.speaker_labels {
+        Some(labels) => [labels[0].clone(), labels[1].clone()],
+        None => cfg.speaker_labels.clone(),
+    };
+    let remembered = (sink.clone(), devices.mic.clone());
+    let git_rev = git_revision();
+    let mut recorder = recorder
+        .devices(devices)
+        .split_channels(args.split_channels)
+        .output(&outfile)
+        .duration(args.duration)
+        .max_duration(max_duration)
+        .debug(args.debug)
+        .model(whisper_model)
+        .language(language.clone())
+        .backend(backend)
+        .strict_backend(args.strict_backend)
+        .threads(whisper_threads)
+        .speaker_labels(labels)
+        .started_at(started_at)
+        .min_confidence(args.min_confidence)
+        .windowing(windowing)
+        .vad_threshold(args.vad_threshold)
+        .prompt(prompt)
+        .sample_rate(args.sample_rate)
+        .mono(mono)
+        .mic_cmd_file(args.mic_cmd_file.clone())
+        .mic_volume(args.mic_volume)
+        .skip_start(args.skip_start.filter(|&secs| secs > 0))
+        .reconnect(args.reconnect.filter(|&n| n > 0))
+        .format(args.format)
+        .flac_level(encoding.flac_level)
+        .opus_application(args.opus_mode)
+        .opus_frame_ms(args.opus_frame_ms)
+        .sync_mode(args.sync_mode)
+        .git_rev(git_rev.clone())
+        .clip_threshold_db(args.clip_threshold_db)
+        .segment_secs(args.crash_safe.filter(|&secs| secs > 0));
+    for (key, value) in &tags {
+        recorder = recorder.tag(key, value);
+    }
+    let live_transcript = args
+        .transcript_live
+        .as_deref()
+        .map(|path| LiveTranscript::create(path, export::Format::for_path(path)))
+        .transpose()
+        .context(ErrorKind::Usage)?;
+    let mut handle = recorder.start()?;
+    if let Some(warning) = clock_warning {
+        handle.log(warning);
+    }
+    if let Some(live) = live_transcript {
+        start_live_transcript(
+            live,
+            handle.subscribe(),
+            !args.no_manual_notes,
+            handle.logs(),
+        );
+    }
+    if let Some(url) = args.webhook.clone() {
+        start_webhook(url, handle.subscribe(), handle.logs());
     }
 
     if args.debug {
-        let _ = child.wait();
+        let _ = handle.wait();
         return Ok(());
     }
 
+    let transcribe_enabled = args.transcribe && handle.set_transcribing(true);
     let state = RecorderState {
-        start_time: Instant::now(),
-        duration: args.duration.map(|d| Duration::from_secs(d as u64)),
-        mic_muted: false,
-        mic_cmd_file: mic_cmd_path,
+        handle,
         running: true,
-        output_file: outfile.clone(),
-        monitor_source: monitor,
-        mic_source: source_name,
-        git_rev: git_revision(),
-        audio_level,
-        markers: Vec::new(),
-        recent_logs,
-        transcript,
-        transcription_active: false,
-        transcription_flag,
-        transcription_stop: transcription_stop.clone(),
-        transcription_reset,
-        base_offset_ms,
-        language,
-        whisper_model,
+        git_rev,
+        transcribe_enabled,
+        transcript_visible: false,
+        transcribe_toggled: false,
+        transcript_view: TranscriptView::default(),
+        silence_check: (!args.no_silence_check && !args.mic_only)
+            .then(|| args.silence_check.unwrap_or(cfg.silence_check_secs))
+            .filter(|&secs| secs > 0)
+            .map(Duration::from_secs),
+        monitor_heard: false,
+        editing_note: None,
+        editing_transcript_note: None,
+        confirm_discard: false,
+        discard: false,
+        player: None,
+        fps: args.fps,
+        layout: if args.compact {
+            LayoutMode::Compact
+        } else {
+            LayoutMode::Full
+        },
+        marker_clock: args.marker_clock,
+        ptt: args.ptt,
+        theme,
+        clips_seen: 0,
+        last_clip: None,
+        level_history: LevelHistory::default(),
+        countdown: Duration::from_secs(args.count_down_to_stop),
+    };
+
+    let res = if args.json_events {
+        run_events(state)
+    } else {
+        run_app(state)
+    };
+    // Keep stdout machine-readable in JSON mode.
+    let report = |msg: String| {
+        if args.json_events {
+            eprintln!("{msg}");
+        } else {
+            println!("{msg}");
+        }
     };
 
-    let res = run_app(state, &mut child);
+    let discard = matches!(&res, Ok(state) if state.discard);
+    // Stop FFmpeg and the transcriber, keeping what was captured
+    let res = res.map(|state| (state.handle.stop(), state.git_rev));
 
-    // Ensure FFmpeg is dead
-    ensure_child_stopped(&mut child);
-    transcription_stop.store(true, Ordering::Relaxed);
-    if let Some(handle) = transcript_handle {
-        let _ = handle.join();
+    if discard {
+        let mut removed = discard_recording(&outfile);
+        if let Ok((recording, _)) = &res {
+            for part in &recording.parts {
+                removed.extend(discard_recording(part));
+            }
+        }
+        report(format!(
+            "Discarded recording ({} file{} deleted).",
+            removed.len(),
+            if removed.len() == 1 { "" } else { "s" }
+        ));
+        std::process::exit(ErrorKind::Aborted.exit_code());
     }
 
-    // Cleanup command file
-    if let Some(path) = &res.as_ref().ok().and_then(|s| s.mic_cmd_file.as_ref()) {
-        let _ = std::fs::remove_file(path);
+    let mut res = res;
+    let mut trimmed = None;
+    if args.trim_silence
+        && let Ok((recording, _)) = &mut res
+    {
+        if !recording.parts.is_empty() || !outfile.is_file() {
+            eprintln!("--trim-silence: skipped, the recording is split across several files");
+        } else {
+            let opts = TrimOptions {
+                threshold_db: args.trim_threshold,
+                min_silence_secs: args.trim_min_secs,
+                sample_rate: recording.sample_rate,
+                channels: recording.channels,
+                encoding,
+                keep_original: args.keep_original,
+            };
+            match trim_silence(&SystemRunner, &outfile, &opts) {
+                Ok(Some(trim)) => {
+                    for marker in &mut recording.markers {
+                        marker.timestamp = trim.shift_secs(marker.timestamp);
+                    }
+                    for seg in &mut recording.transcript {
+                        seg.start_ms = trim.shift_ms(seg.start_ms);
+                        seg.end_ms = trim.shift_ms(seg.end_ms);
+                    }
+                    report(format!(
+                        "Trimmed silence: kept {:.1}s to {:.1}s",
+                        trim.start, trim.end
+                    ));
+                    trimmed = Some(trim);
+                }
+                Ok(None) => report("No leading or trailing silence to trim".into()),
+                Err(err) => eprintln!("Trimming silence failed, keeping the file as is: {err:#}"),
+            }
+        }
     }
 
+    // The written file can run a little shorter or longer than the session
+    // (startup latency, the trailer), so report what ffprobe finds in it.
+    let recorded = match &res {
+        Ok((recording, _)) if outfile.is_file() => {
+            let files = std::iter::once(&outfile).chain(&recording.parts);
+            match files
+                .map(|file| probe(&SystemRunner, file))
+                .collect::<Result<Vec<_>>>()
+            {
+                Ok(probes) => {
+                    let mut total = probes[0].clone();
+                    total.duration_secs = probes.iter().map(|p| p.duration_secs).sum();
+                    report(format!(
+                        "Recorded {} of audio ({})",
+                        export::format_timecode((total.duration_secs * 1000.0).round() as i64),
+                        total.summary()
+                    ));
+                    Some(total)
+                }
+                Err(err) => {
+                    eprintln!("Could not probe the recording: {err:#}");
+                    None
+                }
+            }
+        }
+        _ => None,
+    };
+
     // Save markers if any
-    if let Ok(final_state) = &res {
-        if !final_state.markers.is_empty() {
-            let marker_file = final_state.output_file.with_extension("json");
-            if let Ok(f) = File::create(&marker_file) {
-                let _ = serde_json::to_writer_pretty(f, &final_state.markers);
-                println!(
-                    "Saved {} markers to {}",
-                    final_state.markers.len(),
-                    marker_file.display()
-                );
+    if let Ok((recording, git_rev)) = &res {
+        let meta = Metadata {
+            output: outfile.display().to_string(),
+            started_at: format_rfc3339(started_at),
+            utc_offset: format_offset(started_at),
+            monitor: recording.monitors.first().cloned(),
+            monitors: recording.monitors.clone(),
+            raw_input: recording.raw_input.clone(),
+            mic: recording.mic.clone(),
+            sample_rate: recording.sample_rate,
+            channels: recording.channels,
+            format: args.format,
+            flac_level: (args.format == AudioFormat::Flac).then_some(encoding.flac_level),
+            opus_application: args.opus_mode,
+            opus_frame_ms: args.opus_frame_ms,
+            sync_mode: args.sync_mode,
+            tags: tags.clone(),
+            trimmed,
+            clips: recording.clips,
+            paused_secs: (!recording.paused.is_zero()).then_some(recording.paused.as_secs_f64()),
+            recorded,
+            rcrd_version: env!("CARGO_PKG_VERSION"),
+            git_rev: git_rev.clone(),
+        };
+        if recording.clips > 0 {
+            report(format!(
+                "Warning: the level reached {} dBFS {} time(s); parts of the recording may be distorted",
+                args.clip_threshold_db, recording.clips
+            ));
+        }
+        if !recording.paused.is_zero() {
+            report(format!(
+                "Left out {:.1}s paused",
+                recording.paused.as_secs_f64()
+            ));
+        }
+        if let Some(err) = &recording.join_error {
+            eprintln!("Failed to join the pieces recorded between pauses: {err}");
+            report("The recording continues in:".into());
+            for part in &recording.parts {
+                report(format!("  {}", part.display()));
+            }
+        } else if !recording.parts.is_empty() {
+            report(format!(
+                "The input dropped {} time(s); the recording continues in:",
+                recording.parts.len()
+            ));
+            for part in &recording.parts {
+                report(format!("  {}", part.display()));
+            }
+        }
+        match save_metadata(&meta, &outfile) {
+            Ok(path) => report(format!("Saved metadata to {}", path.display())),
+            Err(err) => eprintln!("Failed to save metadata: {err:#}"),
+        }
+
+        let save_markers_file = toggle(
+            args.save_markers,
+            args.no_save_markers,
+            cfg.always_save_markers,
+        );
+        let save_transcript = toggle(
+            args.save_transcript,
+            args.no_save_transcript,
+            cfg.always_save_transcript,
+        );
+
+        let mut marker_path = None;
+        if recording.markers.is_empty() || !save_markers_file {
+            let _ = std::fs::remove_file(marker_journal_path(&recording.output));
+        } else {
+            for &format in &args.marker_format {
+                let saved = match format {
+                    MarkerFormat::Json => save_markers(&recording.markers, &recording.output),
+                    MarkerFormat::Cue => save_cue(&recording.markers, &recording.output),
+                };
+                match saved {
+                    Ok(path) => {
+                        report(format!(
+                            "Saved {} markers to {}",
+                            recording.markers.len(),
+                            path.display()
+                        ));
+                        marker_path.get_or_insert(path);
+                    }
+                    Err(err) => eprintln!("Failed to save markers: {err:#}"),
+                }
+            }
+            // Saving the JSON drops the journal; without it, the journal
+            // goes once the markers were saved some other way.
+            if !args.marker_format.contains(&MarkerFormat::Json) && marker_path.is_some() {
+                let _ = std::fs::remove_file(marker_journal_path(&recording.output));
+            }
+        }
+        let mut transcript_path = None;
+        if save_transcript {
+            let mut transcript = match args.merge_gap_ms {
+                Some(gap) => merge_segments(&recording.transcript, gap as i64),
+                None => recording.transcript.clone(),
+            };
+            if args.no_manual_notes {
+                transcript.retain(|seg| !seg.manual);
+            }
+            let info = TranscriptInfo {
+                started_at: Some(format_rfc3339(started_at)),
+                language: Some(language.clone()),
+            };
+            for &format in &args.transcript_format {
+                if let Some(path) = export::save(format, &transcript, &outfile, &info)? {
+                    report(format!("Saved transcript to {}", path.display()));
+                    transcript_path.get_or_insert(path);
+                }
             }
         }
-        if args.save_transcript {
-            save_transcript_csv(final_state, &outfile)?;
+        if let Some(post) = args.post_command.as_deref().or(cfg.post_command.as_deref())
+            && let Err(err) = run_post_command(
+                post,
+                &outfile,
+                transcript_path.as_deref(),
+                marker_path.as_deref(),
+                // Keep the hook's output out of the event stream.
+                if args.json_events {
+                    Stdio::from(std::io::stderr())
+                } else {
+                    Stdio::inherit()
+                },
+            )
+        {
+            eprintln!("Post command failed: {err:#}");
         }
     }
 
-    if let Err(err) = res {
-        eprintln!("Error: {:?}", err);
-    } else {
-        println!("Recording finished successfully.");
+    if args.remember_devices && res.is_ok() {
+        let (sink, source) = remembered;
+        // Saved into the base config, so profile overrides stay in their profile.
+        let mut base = load_base_config().unwrap_or_default();
+        // Keep the other side's remembered device when it wasn't used this time.
+        base.last_sink = sink.or(base.last_sink);
+        base.last_source = source.or(base.last_source);
+        match save_config(&base) {
+            Ok(()) => report("Remembered devices for next time".into()),
+            Err(err) => eprintln!("Failed to save config: {err:#}"),
+        }
+    }
+
+    if args.play && res.is_ok() {
+        match Player::detect() {
+            Some(player) => {
+                report(format!(
+                    "Playing {} with {}",
+                    outfile.display(),
+                    player.binary()
+                ));
+                if let Err(err) = player.spawn(&outfile, None).and_then(|mut c| Ok(c.wait()?)) {
+                    eprintln!("Playback failed: {err:#}");
+                }
+            }
+            None => eprintln!("--play: neither ffplay nor pw-play found in PATH"),
+        }
     }
 
+    let (recording, _) = res?;
+    if let Some(err) = recording.ffmpeg_error {
+        return Err(anyhow!(err).context(ErrorKind::Ffmpeg));
+    }
+    report("Recording finished successfully.".into());
     Ok(())
 }
 
-fn ensure_child_stopped(child: &mut Child) {
-    match child.try_wait() {
-        Ok(Some(_)) => {}
-        Ok(None) => {
-            let _ = child.kill();
-            let _ = child.wait();
+fn run_transcribe(
+    args: TranscribeArgs,
+    profile: Option<&str>,
+    config_file: Option<&Path>,
+) -> Result<()> {
+    let cfg = profile_config(profile, config_file)?;
+    let model = resolve_model_arg(args.model, args.model_name.as_deref(), &cfg)?;
+    let requested = args.backend.unwrap_or_else(|| cfg.backend.clone());
+    let backend =
+        resolve_backend(&requested, args.strict_backend).context(ErrorKind::Transcriber)?;
+    if backend != requested {
+        eprintln!("Warning: no usable Vulkan GPU, transcribing with openblas");
+    }
+    let language = args
+        .lang
+        .or(cfg.language.clone())
+        .unwrap_or_else(|| "en".into());
+
+    eprintln!("Transcribing {}...", args.file.display());
+    let prompt = whisper_prompt(args.prompt, args.prompt_file.as_deref(), &cfg)?;
+    let transcript = transcribe_file(&args.file, model, &language, backend, 8, prompt)?;
+    if transcript.is_empty() {
+        eprintln!("No speech found.");
+        return Ok(());
+    }
+    let info = TranscriptInfo {
+        started_at: None,
+        language: Some(language),
+    };
+    for &format in &args.format {
+        if let Some(path) = export::save(format, &transcript, &args.file, &info)? {
+            println!("Saved transcript to {}", path.display());
         }
-        Err(_) => {
-            let _ = child.kill();
-            let _ = child.wait();
+    }
+    Ok(())
+}
+
+/// `--flag`/`--no-flag`: whichever was given, else the config's `default`.
+fn toggle(on: bool, off: bool, default: bool) -> bool {
+    if on || off { on } else { default }
+}
+
+/// Fails when less than `required_gb` is free for `outfile`, and warns when
+/// less than `expected` bytes are.
+fn check_free_space(outfile: &Path, required_gb: Option<f64>, expected: Option<u64>) -> Result<()> {
+    let free = match free_bytes(outfile) {
+        Ok(free) => free,
+        Err(err) => {
+            tracing::warn!("could not check free space: {err:#}");
+            return Ok(());
         }
+    };
+    if let Some(err) = space_error(free, outfile, required_gb) {
+        return Err(err);
+    }
+    if let Some(warning) = space_warning(free, outfile, expected) {
+        eprintln!("Warning: {warning}");
     }
+    Ok(())
 }
 
-fn save_transcript_csv(state: &RecorderState, outfile: &PathBuf) -> Result<()> {
-    let transcript = match state.transcript.lock() {
-        Ok(t) => t.clone(),
-        Err(_) => Vec::new(),
+/// The refusal of `--require-space` when `free` bytes are less than `required_gb`.
+fn space_error(free: u64, outfile: &Path, required_gb: Option<f64>) -> Option<anyhow::Error> {
+    let gb = required_gb.filter(|gb| (free as f64) < gb * 1e9)?;
+    Some(
+        anyhow!(
+            "only {} free for {}, less than --require-space {gb} GB",
+            format_bytes(free),
+            outfile.display()
+        )
+        .context(ErrorKind::Output),
+    )
+}
+
+/// What to warn about when `free` bytes may not hold the `expected` size.
+fn space_warning(free: u64, outfile: &Path, expected: Option<u64>) -> Option<String> {
+    let expected = expected.filter(|&expected| free < expected)?;
+    Some(format!(
+        "only {} free for {}, but the recording may take {}",
+        format_bytes(free),
+        outfile.display(),
+        format_bytes(expected)
+    ))
+}
+
+/// The initial prompt from `--prompt`, `--prompt-file` or the config,
+/// warning when it has to be cut.
+fn whisper_prompt(
+    prompt: Option<String>,
+    prompt_file: Option<&Path>,
+    cfg: &Config,
+) -> Result<Option<String>> {
+    let prompt = match prompt_file {
+        Some(path) => Some(
+            std::fs::read_to_string(path)
+                .with_context(|| format!("reading {}", path.display()))
+                .context(ErrorKind::Usage)?,
+        ),
+        None => prompt.or(cfg.transcribe_prompt.clone()),
     };
-    if transcript.is_empty() {
-        return Ok(());
+    let Some(prompt) = prompt else {
+        return Ok(None);
+    };
+    let prepared = prepare_prompt(&prompt);
+    let words = |text: &str| text.split_whitespace().count();
+    if let Some(kept) = &prepared
+        && words(kept) < words(&prompt)
+    {
+        eprintln!(
+            "Warning: the prompt was cut to {} of its {} words ({MAX_PROMPT_CHARS} characters)",
+            words(kept),
+            words(&prompt)
+        );
     }
-    let csv_path = outfile.with_extension("csv");
-    let mut w = File::create(&csv_path)?;
-    writeln!(w, "start,end,text")?;
-    for seg in transcript {
-        let start = format_timecode(seg.start_ms);
-        let end = format_timecode(seg.end_ms);
-        let text = seg.text.replace('"', "\"\"");
-        writeln!(w, "{start},{end},\"{text}\"")?;
+    Ok(prepared)
+}
+
+/// Points ffmpeg and pw-dump at `--ffmpeg-bin`/`--pw-dump-bin` or the
+/// config's paths. A config that fails to load is reported later by the
+/// commands that need it.
+fn set_tool_paths(args: &Args) -> Result<()> {
+    let cfg = load_config(args.profile.as_deref(), args.config_file.as_deref()).unwrap_or_default();
+    let tools = [
+        (
+            "ffmpeg",
+            args.ffmpeg_bin.clone(),
+            "--ffmpeg-bin",
+            cfg.ffmpeg_path,
+            "ffmpeg_path",
+        ),
+        (
+            "pw-dump",
+            args.pw_dump_bin.clone(),
+            "--pw-dump-bin",
+            cfg.pw_dump_path,
+            "pw_dump_path",
+        ),
+    ];
+    for (tool, arg, flag, configured, key) in tools {
+        let (path, source) = match (arg, configured) {
+            (Some(path), _) => (path, flag),
+            (None, Some(path)) => (path, key),
+            (None, None) => continue,
+        };
+        if !path.is_file() {
+            return Err(anyhow!("{tool} not found at {} ({source})", path.display())
+                .context(ErrorKind::Usage));
+        }
+        tracing::info!("using {tool} at {}", path.display());
+        set_tool_path(tool, path);
     }
-    println!("Saved transcript to {}", csv_path.display());
     Ok(())
 }
 
-fn format_timecode(ms: i64) -> String {
-    let h = ms / 3_600_000;
-    let m = (ms / 60_000) % 60;
-    let s = (ms / 1000) % 60;
-    let ms = ms % 1000;
-    format!("{:02}:{:02}:{:02}.{:03}", h, m, s, ms)
+/// The safety cap in seconds: `--max-duration`, else the config's; `None`
+/// when either sets it to 0.
+fn max_duration(args: &Args, cfg: &Config) -> Option<u32> {
+    let secs = args
+        .max_duration
+        .map_or(cfg.max_recording_seconds, u64::from);
+    (secs > 0).then(|| u32::try_from(secs).unwrap_or(u32::MAX))
+}
+
+/// `--print-config`: the merged config and what the recording would use
+/// once the flags are applied. Devices left to detection are `null`, and
+/// `--model-name` shows where the model is cached without downloading it.
+fn print_config(args: &Args, cfg: &Config) -> Result<()> {
+    let model = match &args.model_name {
+        Some(name) => Some(cached_model_path(name)),
+        None => args.model.clone().or(cfg.whisper_model.clone()),
+    };
+    let formats: Vec<_> = args
+        .transcript_format
+        .iter()
+        .filter_map(clap::ValueEnum::to_possible_value)
+        .map(|v| v.get_name().to_string())
+        .collect();
+    let template = cfg
+        .filename_template
+        .clone()
+        .unwrap_or_else(|| format!("{}{{date}}-{{time}}", cfg.file_prefix));
+    let tags: Tags = args.tags.iter().cloned().collect();
+    let raw_input = args
+        .raw_input
+        .as_deref()
+        .and_then(|raw| parse_raw_input(raw).ok());
+    let devices = device_recorder(args, cfg, raw_input).requested_devices();
+    let resolved = serde_json::json!({
+        "sources": config_sources(args.config_file.as_deref()),
+        "profile": cfg.active_profile,
+        "config": cfg,
+        "effective": {
+            "output": args.output.as_ref().map_or_else(
+                || format!("{template}.{}", args.format.extension()),
+                |p| p.display().to_string(),
+            ),
+            "sinks": devices.sinks,
+            "source": devices.mic,
+            "playback": args.monitor_playback.then(|| devices.playback.unwrap_or_else(|| "default".into())),
+            "mic_volume": (!args.no_mic).then(|| args.mic_volume.unwrap_or(1.0)),
+            "model": model,
+            "backend": args.backend.clone().unwrap_or_else(|| cfg.backend.clone()),
+            "language": if args.language_per_segment {
+                AUTO_LANGUAGE.to_string()
+            } else {
+                args.lang.clone().or(cfg.language.clone()).unwrap_or_else(|| "en".into())
+            },
+            "prompt": whisper_prompt(args.prompt.clone(), args.prompt_file.as_deref(), cfg)?,
+            "format": args.format,
+            "flac_level": (args.format == AudioFormat::Flac)
+                .then(|| args.flac_level.unwrap_or(DEFAULT_FLAC_LEVEL)),
+            "sample_rate": args.sample_rate,
+            "mono": args.mono || (cfg.channels == 1 && !args.split_channels),
+            "split_channels": args.split_channels,
+            "sync_mode": args.sync_mode.as_str(),
+            "use_utc": args.utc || cfg.use_utc,
+            "speaker_labels": args.speaker_labels.clone().unwrap_or_else(|| cfg.speaker_labels.to_vec()),
+            "silence_check_secs": (!args.no_silence_check && !args.mic_only)
+                .then(|| args.silence_check.unwrap_or(cfg.silence_check_secs)),
+            "wait_for_audio_secs": args.wait_for_audio.unwrap_or(cfg.wait_for_audio_secs),
+            "duration_secs": args.duration,
+            "max_duration_secs": max_duration(args, cfg),
+            "save_transcript": toggle(args.save_transcript, args.no_save_transcript, cfg.always_save_transcript),
+            "transcript_format": formats,
+            "save_markers": toggle(args.save_markers, args.no_save_markers, cfg.always_save_markers),
+            "marker_format": args.marker_format,
+            "post_command": args.post_command.clone().or(cfg.post_command.clone()),
+            "clip_threshold_db": args.clip_threshold_db,
+            "tags": tags,
+        },
+    });
+    println!("{}", serde_json::to_string_pretty(&resolved)?);
+    Ok(())
+}
+
+/// The layered config with `profile` applied. An unreadable config still
+/// falls back to the defaults, but naming a profile it doesn't define, or a
+/// `--config` file that can't be read, is an error.
+fn profile_config(profile: Option<&str>, config_file: Option<&Path>) -> Result<Config> {
+    match load_config(profile, config_file) {
+        Err(err) if profile.is_some() || config_file.is_some() => {
+            Err(err.context(ErrorKind::Usage))
+        }
+        res => Ok(res.unwrap_or_default()),
+    }
+}
+
+/// Model for the offline subcommands: `--model-name`, else `--model`, else the config.
+fn resolve_model_arg(model: Option<PathBuf>, name: Option<&str>, cfg: &Config) -> Result<PathBuf> {
+    match name {
+        Some(name) => resolve_model(name).context(ErrorKind::Transcriber),
+        None => model
+            .or(cfg.whisper_model.clone())
+            .ok_or_else(|| anyhow!("no whisper model: pass --model or --model-name"))
+            .context(ErrorKind::Usage),
+    }
+}
+
+fn parse_sample_rate(s: &str) -> Result<u32, String> {
+    match s.parse::<u32>() {
+        Ok(rate) if SUPPORTED_SAMPLE_RATES.contains(&rate) => Ok(rate),
+        _ => Err(format!(
+            "unsupported sample rate '{s}' (use one of {SUPPORTED_SAMPLE_RATES:?})"
+        )),
+    }
+}
+
+fn parse_confidence(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(p) if (0.0..=1.0).contains(&p) => Ok(p),
+        _ => Err(format!("'{s}' is not a probability between 0 and 1")),
+    }
+}
+
+/// A recorder with the devices the flags and config ask for; the rest of
+/// the recording is set up on it once [`Recorder::resolve`] has found them.
+fn device_recorder(args: &Args, cfg: &Config, raw_input: Option<Vec<String>>) -> Recorder {
+    let mut recorder = Recorder::new()
+        .no_mic(args.no_mic)
+        .mic_only(args.mic_only)
+        .monitor_playback(args.monitor_playback)
+        .playback_sink(args.playback_sink.clone())
+        .no_device_cache(args.no_cache)
+        .wait_for_audio(Duration::from_secs(
+            args.wait_for_audio.unwrap_or(cfg.wait_for_audio_secs),
+        ));
+    // Devices remembered with `--remember-devices` come before the defaults.
+    let sinks = if args.sink.is_empty() {
+        cfg.last_sink.iter().cloned().collect()
+    } else {
+        args.sink.clone()
+    };
+    for sink in sinks {
+        recorder = recorder.sink(sink);
+    }
+    for monitor in &args.monitor {
+        recorder = recorder.monitor(monitor);
+    }
+    if let Some(raw) = raw_input {
+        recorder = recorder.raw_input(raw);
+    }
+    if let Some(source) = args.source.clone().or(cfg.last_source.clone()) {
+        recorder = recorder.source(source);
+    }
+    recorder
+}
+
+/// Whether `model` is one of whisper's English-only models (`ggml-base.en.bin`),
+/// which can't detect other languages.
+fn english_only(model: &Path) -> bool {
+    model
+        .file_name()
+        .is_some_and(|name| name.to_string_lossy().contains(".en."))
+}
+
+/// ffmpeg's `silencedetect` needs a positive duration.
+fn parse_trim_min_secs(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(secs) if secs.is_finite() && secs > 0.0 => Ok(secs),
+        _ => Err(format!("'{s}' is not a positive number of seconds")),
+    }
+}
+
+fn parse_mic_volume(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(v) if (0.0..=MAX_MIC_VOLUME).contains(&v) => Ok(v),
+        _ => Err(format!(
+            "'{s}' is not a volume between 0.0 and {MAX_MIC_VOLUME:.1}"
+        )),
+    }
+}
+
+/// `--ask-tags`: reads `KEY=VALUE` lines until an empty one; a bad line is
+/// reported and asked again.
+fn prompt_tags(tags: &mut Tags) -> Result<()> {
+    let stdin = std::io::stdin();
+    if !stdin.is_terminal() {
+        return Err(anyhow!("--ask-tags needs a terminal on stdin"));
+    }
+    eprintln!("Tags for this recording, one KEY=VALUE per line (empty line to start):");
+    loop {
+        eprint!("tag> ");
+        std::io::stderr().flush()?;
+        let mut line = String::new();
+        if stdin.read_line(&mut line)? == 0 || line.trim().is_empty() {
+            return Ok(());
+        }
+        match parse_tag(&line) {
+            Ok((key, value)) => {
+                tags.insert(key, value);
+            }
+            Err(err) => eprintln!("{err}"),
+        }
+    }
+}
+
+fn parse_clip_threshold(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(db) if (-20.0..=0.0).contains(&db) => Ok(db),
+        _ => Err(format!("'{s}' is not a level between -20 and 0 dBFS")),
+    }
+}
+
+fn parse_vad_threshold(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(db) if (-100.0..=0.0).contains(&db) => Ok(db),
+        _ => Err(format!("'{s}' is not a level between -100 and 0 dBFS")),
+    }
+}
+
+/// Parses `--duration`: plain seconds (`90`) or `h`/`m`/`s` components (`1h30m`).
+fn parse_duration(s: &str) -> Result<u32, String> {
+    let s = s.trim();
+    let secs: u64 = if let Ok(secs) = s.parse() {
+        secs
+    } else {
+        let mut total: u64 = 0;
+        let mut digits = String::new();
+        for c in s.chars() {
+            match c {
+                '0'..='9' => digits.push(c),
+                'h' | 'm' | 's' if !digits.is_empty() => {
+                    let n: u64 = digits
+                        .parse()
+                        .map_err(|_| format!("invalid duration '{s}'"))?;
+                    let unit = match c {
+                        'h' => 3600,
+                        'm' => 60,
+                        _ => 1,
+                    };
+                    total = n
+                        .checked_mul(unit)
+                        .and_then(|n| total.checked_add(n))
+                        .ok_or_else(|| format!("duration '{s}' is too large"))?;
+                    digits.clear();
+                }
+                _ => {
+                    return Err(format!(
+                        "invalid duration '{s}' (use e.g. 90, 90s, 30m, 1h30m)"
+                    ));
+                }
+            }
+        }
+        if !digits.is_empty() || s.is_empty() {
+            return Err(format!(
+                "invalid duration '{s}' (use e.g. 90, 90s, 30m, 1h30m)"
+            ));
+        }
+        total
+    };
+    if secs == 0 {
+        return Err(
+            "duration must be greater than zero; omit --duration to record until stopped".into(),
+        );
+    }
+    u32::try_from(secs).map_err(|_| format!("duration '{s}' is too large"))
+}
+
+/// `--max-duration`: a duration as for `--duration`, or `0`/`unlimited`
+/// (returned as 0) for no cap.
+fn parse_max_duration(s: &str) -> Result<u32, String> {
+    match s.trim() {
+        "0" | "unlimited" | "none" => Ok(0),
+        _ => parse_duration(s),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(parse_duration("90"), Ok(90));
+        assert_eq!(parse_duration("90s"), Ok(90));
+        assert_eq!(parse_duration("30m"), Ok(1800));
+        assert_eq!(parse_duration("1h30m"), Ok(5400));
+        assert_eq!(parse_duration("1h0m5s"), Ok(3605));
+    }
+
+    #[test]
+    fn rejects_bad_durations() {
+        assert!(parse_duration("0").is_err());
+        assert!(parse_duration("0m").is_err());
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("5x").is_err());
+        assert!(parse_duration("1h30").is_err());
+        assert!(parse_duration("m").is_err());
+        assert!(parse_duration("99999999999").is_err());
+        assert!(parse_duration("9999999h").is_err());
+    }
+
+    #[test]
+    fn max_duration_can_be_lifted() {
+        assert_eq!(parse_max_duration("unlimited"), Ok(0));
+        assert_eq!(parse_max_duration("0"), Ok(0));
+        assert_eq!(parse_max_duration("2h"), Ok(7200));
+        assert!(parse_max_duration("0m").is_err());
+    }
+
+    #[test]
+    fn unwritable_output_has_its_own_exit_code() {
+        let outfile = Path::new("/rec/call.ogg");
+        let err = space_error(2_000_000_000, outfile, Some(5.0)).unwrap();
+        assert_eq!(ErrorKind::of(&err).map(ErrorKind::exit_code), Some(7));
+        assert!(format!("{err:#}").contains("only 2.0 GB free"), "{err:#}");
+        assert!(space_error(5_000_000_000, outfile, Some(5.0)).is_none());
+        assert!(space_error(0, outfile, None).is_none());
+        assert!(space_warning(100_000_000, outfile, Some(200_000_000)).is_some());
+        assert!(space_warning(300_000_000, outfile, Some(200_000_000)).is_none());
+    }
+
+    #[test]
+    fn trim_options_need_trim_silence() {
+        assert_eq!(parse_trim_min_secs("0.5"), Ok(0.5));
+        assert!(parse_trim_min_secs("0").is_err());
+        assert!(parse_trim_min_secs("-1").is_err());
+        assert!(Args::try_parse_from(["rcrd", "--trim-min-secs", "2"]).is_err());
+        assert!(Args::try_parse_from(["rcrd", "--trim-threshold", "-40"]).is_err());
+        let args =
+            Args::try_parse_from(["rcrd", "--trim-silence", "--trim-threshold", "-40"]).unwrap();
+        assert_eq!(args.trim_threshold, -40.0);
+        assert_eq!(args.trim_min_secs, 1.0);
+    }
 }
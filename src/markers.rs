@@ -0,0 +1,20 @@
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use rcrd::chapters::apply_markers;
+use rcrd::error::ErrorKind;
+use rcrd::output::load_markers;
+use rcrd::process::SystemRunner;
+
+/// `rcrd markers apply`: embeds the markers in `markers` into `audio` as
+/// chapters.
+pub fn run_markers_apply(audio: &Path, markers: &Path) -> Result<()> {
+    let markers = load_markers(markers).context(ErrorKind::Usage)?;
+    if markers.is_empty() {
+        println!("No markers to apply.");
+        return Ok(());
+    }
+    apply_markers(&SystemRunner, audio, &markers).context(ErrorKind::Ffmpeg)?;
+    println!("Added {} chapters to {}", markers.len(), audio.display());
+    Ok(())
+}
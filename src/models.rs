@@ -0,0 +1,222 @@
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{Context, Result, anyhow};
+
+use crate::config::config_path;
+
+const BASE_URL: &str = "https://huggingface.co/ggerganov/whisper.cpp/resolve/main";
+
+/// Models published with whisper.cpp, by the name used in `ggml-<name>.bin`.
+pub const KNOWN_MODELS: &[&str] = &[
+    "tiny",
+    "tiny.en",
+    "base",
+    "base.en",
+    "small",
+    "small.en",
+    "medium",
+    "medium.en",
+    "large-v1",
+    "large-v2",
+    "large-v3",
+    "large-v3-turbo",
+];
+
+/// Magic at the start of every ggml whisper model (`0x67676d6c`, little endian).
+const GGML_MAGIC: [u8; 4] = *b"lmgg";
+/// Magic of the newer gguf container.
+const GGUF_MAGIC: [u8; 4] = *b"GGUF";
+
+/// Where downloaded models are cached: `models/` next to the config file.
+pub fn models_dir() -> PathBuf {
+    config_path()
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("models")
+}
+
+/// Where the model `name` is kept once downloaded.
+pub fn cached_model_path(name: &str) -> PathBuf {
+    models_dir().join(format!("ggml-{name}.bin"))
+}
+
+/// Where the size a model was downloaded at is recorded, so a cached model
+/// cut short later (a full disk, an interrupted copy) is noticed.
+fn size_path(model: &Path) -> PathBuf {
+    model.with_extension("bin.size")
+}
+
+/// Path of the cached model `name`, downloading it first if needed, or
+/// again if the cached file no longer has its downloaded size.
+pub fn resolve_model(name: &str) -> Result<PathBuf> {
+    if !KNOWN_MODELS.contains(&name) {
+        return Err(anyhow!(
+            "unknown model '{name}' (known: {})",
+            KNOWN_MODELS.join(", ")
+        ));
+    }
+    let path = cached_model_path(name);
+    if path.exists() {
+        match verify_cached(&path) {
+            Ok(()) => return Ok(path),
+            Err(err) => eprintln!("{err:#}; downloading it again"),
+        }
+    }
+    download_model(name, &path)?;
+    Ok(path)
+}
+
+/// `Content-Length` of the last response in `headers`, as `curl -sIL`
+/// prints them: one block per redirect, the file itself last.
+fn content_length(headers: &str) -> Option<u64> {
+    let headers = headers.replace("\r\n", "\n");
+    let last = headers
+        .split("\n\n")
+        .filter(|block| !block.trim().is_empty())
+        .last()?;
+    last.lines().find_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        key.trim()
+            .eq_ignore_ascii_case("content-length")
+            .then(|| value.trim().parse().ok())?
+    })
+}
+
+/// Size of the file at `url`, asked for before downloading it.
+fn remote_size(url: &str) -> Result<u64> {
+    let out = Command::new("curl")
+        .args(["-fsIL", url])
+        .output()
+        .context("failed to run curl")?;
+    if !out.status.success() {
+        return Err(anyhow!("looking up {url} failed ({})", out.status));
+    }
+    content_length(&String::from_utf8_lossy(&out.stdout))
+        .ok_or_else(|| anyhow!("{url} reported no size"))
+}
+
+fn check_size(path: &Path, expected: u64) -> Result<()> {
+    let size = fs::metadata(path)
+        .with_context(|| format!("reading {}", path.display()))?
+        .len();
+    if size != expected {
+        return Err(anyhow!(
+            "{} is {size} bytes instead of {expected} (truncated?)",
+            path.display()
+        ));
+    }
+    Ok(())
+}
+
+/// [`verify_model`] for a cached model, which also has to have the size it
+/// was downloaded at when that was recorded.
+fn verify_cached(path: &Path) -> Result<()> {
+    verify_model(path)?;
+    if let Some(expected) = fs::read_to_string(size_path(path))
+        .ok()
+        .and_then(|s| s.trim().parse().ok())
+    {
+        check_size(path, expected)?;
+    }
+    Ok(())
+}
+
+/// Fetches `ggml-<name>.bin` into `dest`. curl shows a progress bar on
+/// stderr and fails on HTTP errors; the file is only moved into place once
+/// it has the size the server reported and looks like a ggml model, and
+/// that size is recorded next to it.
+fn download_model(name: &str, dest: &Path) -> Result<()> {
+    let dir = dest.parent().unwrap_or(Path::new("."));
+    fs::create_dir_all(dir).with_context(|| format!("creating {}", dir.display()))?;
+    let part = dest.with_extension("bin.part");
+    let url = format!("{BASE_URL}/ggml-{name}.bin");
+    let expected = remote_size(&url)?;
+    eprintln!("Downloading {url}");
+    let status = Command::new("curl")
+        .args(["-fL", "-#", "-o"])
+        .arg(&part)
+        .arg(&url)
+        .status()
+        .context("failed to run curl")?;
+    if !status.success() {
+        let _ = fs::remove_file(&part);
+        return Err(anyhow!("downloading {url} failed ({status})"));
+    }
+    if let Err(err) = check_size(&part, expected).and_then(|()| verify_model(&part)) {
+        let _ = fs::remove_file(&part);
+        return Err(err);
+    }
+    fs::rename(&part, dest).with_context(|| format!("moving model to {}", dest.display()))?;
+    let sizes = size_path(dest);
+    fs::write(&sizes, expected.to_string()).with_context(|| format!("writing {}", sizes.display()))
+}
+
+/// Checks that `path` can be read and starts like a ggml or gguf model.
+/// This catches a wrong path or a truncated or corrupt file up front; a
+/// model built for another architecture still only fails in whisper.
+pub fn verify_model(path: &Path) -> Result<()> {
+    let mut magic = [0u8; 4];
+    File::open(path)
+        .and_then(|mut f| f.read_exact(&mut magic))
+        .with_context(|| format!("reading {}", path.display()))?;
+    if magic != GGML_MAGIC && magic != GGUF_MAGIC {
+        return Err(anyhow!(
+            "{} is not a ggml model (corrupt download?)",
+            path.display()
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_missing_and_bogus_models() {
+        let dir = std::env::temp_dir().join(format!("rcrd-models-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let missing = dir.join("missing.bin");
+        let err = verify_model(&missing).unwrap_err();
+        assert!(format!("{err:#}").contains("missing.bin"), "{err:#}");
+
+        let bogus = dir.join("bogus.bin");
+        fs::write(&bogus, b"<html>404</html>").unwrap();
+        let err = verify_model(&bogus).unwrap_err();
+        assert!(err.to_string().contains("not a ggml model"), "{err}");
+        fs::write(&bogus, b"lm").unwrap();
+        assert!(verify_model(&bogus).is_err());
+
+        for magic in [GGML_MAGIC, GGUF_MAGIC] {
+            let model = dir.join("model.bin");
+            fs::write(&model, [&magic[..], b"weights"].concat()).unwrap();
+            assert!(verify_model(&model).is_ok());
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn cached_models_keep_their_downloaded_size() {
+        let headers = "HTTP/2 302\r\nlocation: https://cdn/x\r\ncontent-length: 1146\r\n\r\n\
+                       HTTP/2 200\r\nContent-Length: 147951465\r\n\r\n";
+        assert_eq!(content_length(headers), Some(147_951_465));
+        assert_eq!(content_length("HTTP/2 200\r\n\r\n"), None);
+
+        let dir = std::env::temp_dir().join(format!("rcrd-cached-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let model = dir.join("ggml-tiny.bin");
+        fs::write(&model, [&GGML_MAGIC[..], b"weights"].concat()).unwrap();
+        // Without a recorded size, only the magic is checked.
+        assert!(verify_cached(&model).is_ok());
+        fs::write(size_path(&model), "11").unwrap();
+        assert!(verify_cached(&model).is_ok());
+        fs::write(&model, [&GGML_MAGIC[..], b"wei"].concat()).unwrap();
+        let err = verify_cached(&model).unwrap_err();
+        assert!(err.to_string().contains("7 bytes instead of 11"), "{err}");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
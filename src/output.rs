@@ -1,18 +1,257 @@
-use std::path::PathBuf;
-use std::process::Command;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
 
-pub fn default_output_name(prefix: &str) -> PathBuf {
-    let tm = time::OffsetDateTime::now_local().unwrap_or_else(|_| time::OffsetDateTime::now_utc());
-    let datetime = format!(
-        "{:04}{:02}{:02}-{:02}{:02}{:02}",
+use anyhow::{Context, Result, anyhow};
+use serde::Serialize;
+use time::OffsetDateTime;
+
+use crate::Marker;
+use crate::ffmpeg::{AudioFormat, OpusApplication, SyncMode};
+use crate::probe::Probe;
+use crate::tags::Tags;
+use crate::trim::Trim;
+
+/// Recording details written next to the output as `<file>.meta.json`.
+#[derive(Serialize)]
+pub struct Metadata {
+    pub output: String,
+    /// Recording start, RFC 3339 with the offset the filename was built from.
+    pub started_at: String,
+    pub utc_offset: String,
+    /// The first of `monitors`, kept for readers written against a single one.
+    pub monitor: Option<String>,
+    pub monitors: Vec<String>,
+    /// `--raw-input` arguments, when captured instead of a monitor.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub raw_input: Option<Vec<String>>,
+    pub mic: Option<String>,
+    pub sample_rate: u32,
+    pub channels: u8,
+    pub format: AudioFormat,
+    /// FLAC `-compression_level`, for FLAC files.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub flac_level: Option<u8>,
+    pub opus_application: OpusApplication,
+    pub opus_frame_ms: f32,
+    pub sync_mode: SyncMode,
+    /// `--tag` values, also written into the file.
+    #[serde(skip_serializing_if = "Tags::is_empty")]
+    pub tags: Tags,
+    /// Span of the original recording kept by `--trim-silence`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trimmed: Option<Trim>,
+    /// Times the level reached `--clip-threshold-db`.
+    pub clips: u32,
+    /// Time spent paused, left out of the file.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub paused_secs: Option<f64>,
+    /// Duration and format ffprobe found in the written file(s).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recorded: Option<Probe>,
+    pub rcrd_version: &'static str,
+    pub git_rev: Option<String>,
+}
+
+/// Current time, in UTC when `use_utc` is set. The second value is `false`
+/// when local time was requested but the offset could not be determined and
+/// UTC was used instead.
+pub fn now(use_utc: bool) -> (OffsetDateTime, bool) {
+    if use_utc {
+        return (OffsetDateTime::now_utc(), true);
+    }
+    match OffsetDateTime::now_local() {
+        Ok(tm) => (tm, true),
+        Err(_) => (OffsetDateTime::now_utc(), false),
+    }
+}
+
+pub fn format_offset(tm: OffsetDateTime) -> String {
+    let (h, m, _) = tm.offset().as_hms();
+    let sign = if h < 0 || m < 0 { '-' } else { '+' };
+    format!("{sign}{:02}:{:02}", h.unsigned_abs(), m.unsigned_abs())
+}
+
+pub fn format_rfc3339(tm: OffsetDateTime) -> String {
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}{}",
         tm.year(),
         tm.month() as u8,
         tm.day(),
         tm.hour(),
         tm.minute(),
-        tm.second()
-    );
-    PathBuf::from(format!("{prefix}{datetime}.ogg"))
+        tm.second(),
+        format_offset(tm)
+    )
+}
+
+pub fn save_metadata(meta: &Metadata, outfile: &Path) -> Result<PathBuf> {
+    let path = outfile.with_extension("meta.json");
+    let f = std::fs::File::create(&path)
+        .with_context(|| format!("creating metadata {}", path.display()))?;
+    serde_json::to_writer_pretty(f, meta)?;
+    Ok(path)
+}
+
+/// Formats the markers are saved in (`--marker-format`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum MarkerFormat {
+    /// `<file>.json`, read back by `rcrd markers apply`.
+    Json,
+    /// `<file>.cue`, for editors; see [`crate::cue`].
+    Cue,
+}
+
+/// Append-only log of markers written while recording, so they survive a crash.
+pub fn marker_journal_path(outfile: &Path) -> PathBuf {
+    outfile.with_extension("markers.jsonl")
+}
+
+/// Reads the markers in a journal left by a recording that didn't stop
+/// cleanly; a line cut short by the crash is skipped.
+pub fn load_marker_journal(path: &Path) -> Result<Vec<Marker>> {
+    let data = std::fs::read_to_string(path)
+        .with_context(|| format!("reading marker journal {}", path.display()))?;
+    Ok(data
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
+
+/// Writes the final pretty-printed `<file>.json` and drops the journal it supersedes.
+pub fn save_markers(markers: &[Marker], outfile: &Path) -> Result<PathBuf> {
+    let path = outfile.with_extension("json");
+    let f = std::fs::File::create(&path)
+        .with_context(|| format!("creating markers {}", path.display()))?;
+    serde_json::to_writer_pretty(f, markers)?;
+    let _ = std::fs::remove_file(marker_journal_path(outfile));
+    Ok(path)
+}
+
+/// Reads markers saved by [`save_markers`].
+pub fn load_markers(path: &Path) -> Result<Vec<Marker>> {
+    let data = std::fs::read_to_string(path)
+        .with_context(|| format!("reading markers {}", path.display()))?;
+    serde_json::from_str(&data).with_context(|| format!("parsing markers {}", path.display()))
+}
+
+/// Sidecars rcrd may have written next to `<file>.<ext>`.
+const SIDECAR_EXTENSIONS: &[&str] = &[
+    "json",
+    "cue",
+    "meta.json",
+    "markers.jsonl",
+    "session.json",
+    "csv",
+    "srt",
+    "vtt",
+    "transcript.json",
+];
+
+/// Makes sure every file in `paths` can be written before recording starts:
+/// creates missing parent directories, writes and removes a probe file in
+/// each, and checks that existing files can be opened for writing.
+/// Otherwise ffmpeg would only fail a few seconds in.
+pub fn check_writable(paths: &[&Path]) -> Result<()> {
+    for path in paths {
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("cannot create output directory {}", dir.display()))?;
+        let probe = dir.join(format!(".rcrd-write-test-{}", std::process::id()));
+        std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&probe)
+            .with_context(|| format!("output directory {} is not writable", dir.display()))?;
+        let _ = std::fs::remove_file(&probe);
+        if path.exists() {
+            std::fs::OpenOptions::new()
+                .write(true)
+                .open(path)
+                .with_context(|| format!("cannot overwrite {}", path.display()))?;
+        }
+    }
+    Ok(())
+}
+
+/// Deletes the recording at `outfile` (or its `--crash-safe` segments) and
+/// every sidecar written for it. Returns the files removed.
+pub fn discard_recording(outfile: &Path) -> Vec<PathBuf> {
+    let mut candidates: Vec<PathBuf> = SIDECAR_EXTENSIONS
+        .iter()
+        .map(|ext| outfile.with_extension(ext))
+        .collect();
+    candidates.push(outfile.to_path_buf());
+
+    let stem = outfile.file_stem().unwrap_or_default().to_string_lossy();
+    let ext = outfile.extension().unwrap_or_default().to_string_lossy();
+    let segment = regex::Regex::new(&format!(
+        r"^{}-\d{{3,}}\.{}$",
+        regex::escape(&stem),
+        regex::escape(&ext)
+    ))
+    .expect("escaped segment pattern");
+    let dir = match outfile.parent() {
+        Some(p) if !p.as_os_str().is_empty() => p,
+        _ => Path::new("."),
+    };
+    if let Ok(entries) = std::fs::read_dir(dir) {
+        candidates.extend(
+            entries
+                .filter_map(|e| e.ok())
+                .filter(|e| segment.is_match(&e.file_name().to_string_lossy()))
+                .map(|e| outfile.with_file_name(e.file_name())),
+        );
+    }
+    candidates
+        .into_iter()
+        .filter(|path| std::fs::remove_file(path).is_ok())
+        .collect()
+}
+
+/// Expands the filename template (`{date}`, `{time}`, `{sink}`, `{host}`) for
+/// the time `tm` and appends the extension of `format`.
+pub fn default_output_name(
+    template: &str,
+    sink: &str,
+    tm: OffsetDateTime,
+    format: AudioFormat,
+) -> PathBuf {
+    let date = format!("{:04}{:02}{:02}", tm.year(), tm.month() as u8, tm.day());
+    let time = format!("{:02}{:02}{:02}", tm.hour(), tm.minute(), tm.second());
+    let name = template
+        .replace("{date}", &date)
+        .replace("{time}", &time)
+        .replace("{sink}", &sanitize(sink))
+        .replace("{host}", &sanitize(&hostname().unwrap_or_default()));
+    PathBuf::from(format!("{name}.{}", format.extension()))
+}
+
+/// Makes a device or host name safe to embed in a filename.
+fn sanitize(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+fn hostname() -> Option<String> {
+    let mut buf = [0u8; 256];
+    let rc = unsafe { libc::gethostname(buf.as_mut_ptr().cast(), buf.len()) };
+    if rc != 0 {
+        return None;
+    }
+    let len = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+    let host = String::from_utf8_lossy(&buf[..len]).into_owned();
+    if host.is_empty() { None } else { Some(host) }
 }
 
 pub fn git_revision() -> Option<String> {
@@ -26,3 +265,98 @@ pub fn git_revision() -> Option<String> {
     let rev = String::from_utf8_lossy(&output.stdout).trim().to_string();
     if rev.is_empty() { None } else { Some(rev) }
 }
+
+/// Runs the post-recording hook through `sh -c`. Placeholders are replaced by
+/// shell-quoted paths; sidecars that were not written expand to `''`. The
+/// hook's stdout goes to `stdout`, e.g. stderr when stdout carries events.
+pub fn run_post_command(
+    template: &str,
+    file: &Path,
+    transcript: Option<&Path>,
+    markers: Option<&Path>,
+    stdout: Stdio,
+) -> Result<()> {
+    let quote = |p: Option<&Path>| {
+        let s = p.map(|p| p.display().to_string()).unwrap_or_default();
+        format!("'{}'", s.replace('\'', "'\\''"))
+    };
+    let cmd = template
+        .replace("{file}", &quote(Some(file)))
+        .replace("{transcript}", &quote(transcript))
+        .replace("{markers}", &quote(markers));
+    let status = Command::new("sh")
+        .args(["-c", &cmd])
+        .stdout(stdout)
+        .status()
+        .with_context(|| format!("running post command `{cmd}`"))?;
+    if !status.success() {
+        return Err(anyhow!("post command `{cmd}` exited with {status}"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writability_check_creates_dirs_and_rejects_bad_parents() {
+        let dir = std::env::temp_dir().join(format!("rcrd-writable-{}", std::process::id()));
+        let nested = dir.join("calls/2024/call.ogg");
+        check_writable(&[&nested]).unwrap();
+        assert!(dir.join("calls/2024").is_dir());
+        assert_eq!(
+            std::fs::read_dir(dir.join("calls/2024")).unwrap().count(),
+            0
+        );
+
+        let file = dir.join("not-a-dir");
+        std::fs::write(&file, "").unwrap();
+        let err = check_writable(&[&nested, &file.join("call.ogg")]).unwrap_err();
+        assert!(err.to_string().contains("not-a-dir"), "{err}");
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn discard_removes_the_recording_and_its_sidecars_only() {
+        let dir = std::env::temp_dir().join(format!("rcrd-discard-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let names = [
+            "call.ogg",
+            "call.markers.jsonl",
+            "call.csv",
+            "call-000.ogg",
+            "call-001.ogg",
+            "other.ogg",
+            "call-notes.txt",
+        ];
+        for name in names {
+            std::fs::write(dir.join(name), "").unwrap();
+        }
+        let mut removed = discard_recording(&dir.join("call.ogg"));
+        removed.sort();
+        assert_eq!(removed.len(), 5);
+        assert!(dir.join("other.ogg").exists());
+        assert!(dir.join("call-notes.txt").exists());
+        assert!(!dir.join("call-001.ogg").exists());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn post_command_output_goes_to_the_given_stdout() {
+        let dir = std::env::temp_dir().join(format!("rcrd-post-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let log = dir.join("hook.log");
+        let out = std::fs::File::create(&log).unwrap();
+        run_post_command(
+            "echo {file} {transcript}",
+            Path::new("/calls/it's.ogg"),
+            None,
+            None,
+            Stdio::from(out),
+        )
+        .unwrap();
+        assert_eq!(std::fs::read_to_string(&log).unwrap(), "/calls/it's.ogg \n");
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
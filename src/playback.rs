@@ -0,0 +1,53 @@
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+
+use anyhow::{Context, Result};
+
+use crate::process::find_in_path;
+
+/// An external player found on `PATH`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Player {
+    /// `ffplay`, which can seek to a start offset.
+    Ffplay,
+    /// `pw-play`, always from the beginning.
+    PwPlay,
+}
+
+impl Player {
+    /// The first available player, preferring `ffplay`.
+    pub fn detect() -> Option<Player> {
+        [Player::Ffplay, Player::PwPlay]
+            .into_iter()
+            .find(|p| find_in_path(p.binary()).is_some())
+    }
+
+    pub fn binary(self) -> &'static str {
+        match self {
+            Player::Ffplay => "ffplay",
+            Player::PwPlay => "pw-play",
+        }
+    }
+
+    pub fn can_seek(self) -> bool {
+        self == Player::Ffplay
+    }
+
+    /// Spawns the player on `file` without a window or terminal output,
+    /// starting `start` seconds in when the player can seek.
+    pub fn spawn(self, file: &Path, start: Option<f64>) -> Result<Child> {
+        let mut cmd = Command::new(self.binary());
+        if self == Player::Ffplay {
+            cmd.args(["-nodisp", "-autoexit", "-loglevel", "quiet"]);
+            if let Some(start) = start {
+                cmd.args(["-ss", &format!("{start:.3}")]);
+            }
+        }
+        cmd.arg(file)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .with_context(|| format!("failed to start {}", self.binary()))
+    }
+}
@@ -0,0 +1,262 @@
+//! `ffprobe` wrapper: what actually ended up in a finished file, which can
+//! differ slightly from the wall-clock time the recording ran.
+
+use std::path::Path;
+use std::process::Command;
+
+use anyhow::{Context, Result, anyhow};
+use serde::{Deserialize, Serialize};
+
+use crate::process::{ProcessRunner, tool};
+use crate::trim::{EdgeSilence, parse_edge_silence, silencedetect_filter};
+
+/// Duration and format of an audio file as reported by `ffprobe`.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct Probe {
+    pub duration_secs: f64,
+    /// Codec of the first audio stream, e.g. `opus`.
+    pub codec: Option<String>,
+    /// Average bitrate in bits per second.
+    pub bit_rate: Option<u64>,
+    pub channels: Option<u8>,
+}
+
+impl Probe {
+    /// `opus, stereo, 96 kb/s`, leaving out what ffprobe didn't report.
+    pub fn summary(&self) -> String {
+        let mut parts = Vec::new();
+        parts.extend(self.codec.clone());
+        parts.extend(self.channels.map(|c| match c {
+            1 => "mono".to_string(),
+            2 => "stereo".to_string(),
+            n => format!("{n} channels"),
+        }));
+        parts.extend(self.bit_rate.map(|b| format!("{} kb/s", b / 1000)));
+        parts.join(", ")
+    }
+}
+
+/// The parts of `ffprobe -of json` output [`probe`] reads. ffprobe prints
+/// numbers in `format` as strings.
+#[derive(Deserialize)]
+struct ProbeJson {
+    #[serde(default)]
+    streams: Vec<StreamJson>,
+    format: FormatJson,
+}
+
+#[derive(Deserialize)]
+struct StreamJson {
+    codec_name: Option<String>,
+    channels: Option<u8>,
+}
+
+#[derive(Deserialize)]
+struct FormatJson {
+    duration: Option<String>,
+    bit_rate: Option<String>,
+}
+
+pub fn build_probe_command(path: &Path) -> Command {
+    let mut cmd = tool("ffprobe");
+    cmd.args(["-v", "error", "-select_streams", "a:0"])
+        .args([
+            "-show_entries",
+            "format=duration,bit_rate:stream=codec_name,channels",
+        ])
+        .args(["-of", "json"])
+        .arg(path);
+    cmd
+}
+
+/// Runs `ffprobe` on `path`.
+pub fn probe(runner: &dyn ProcessRunner, path: &Path) -> Result<Probe> {
+    let out = runner
+        .output(&mut build_probe_command(path))
+        .context("failed to run ffprobe (is ffmpeg installed?)")?;
+    if !out.status.success() {
+        return Err(anyhow!(
+            "ffprobe exited with {}: {}",
+            out.status,
+            String::from_utf8_lossy(&out.stderr).trim()
+        ));
+    }
+    parse_probe(&out.stdout)
+}
+
+fn parse_probe(json: &[u8]) -> Result<Probe> {
+    let parsed: ProbeJson =
+        serde_json::from_slice(json).context("ffprobe returned invalid JSON")?;
+    let duration_secs = parsed
+        .format
+        .duration
+        .as_deref()
+        .and_then(|d| d.parse().ok())
+        .ok_or_else(|| anyhow!("ffprobe reported no duration"))?;
+    let stream = parsed.streams.into_iter().next();
+    Ok(Probe {
+        duration_secs,
+        codec: stream.as_ref().and_then(|s| s.codec_name.clone()),
+        bit_rate: parsed.format.bit_rate.and_then(|b| b.parse().ok()),
+        channels: stream.and_then(|s| s.channels),
+    })
+}
+
+/// What `rcrd check` found in a file.
+#[derive(Debug)]
+pub struct Check {
+    /// `None` when ffprobe could not read the file.
+    pub probe: Option<Probe>,
+    /// Probe and decoder errors; empty when the whole file decoded cleanly.
+    pub errors: Vec<String>,
+    /// Silence at the ends, from the same decoding pass.
+    pub silence: Option<EdgeSilence>,
+}
+
+impl Check {
+    /// Readable, decodable, and not silent from start to end.
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty() && !self.silence.is_some_and(|s| s.all_silent())
+    }
+}
+
+/// Decodes all of `path`, stopping at the first error (`-xerror`), while
+/// `silencedetect` measures the silence at its ends.
+pub fn build_check_command(path: &Path, threshold_db: f32, min_silence_secs: f64) -> Command {
+    let mut cmd = tool("ffmpeg");
+    cmd.args(["-hide_banner", "-nostdin", "-xerror", "-i"])
+        .arg(path)
+        .args([
+            "-af",
+            &silencedetect_filter(threshold_db, min_silence_secs),
+            "-f",
+            "null",
+            "-",
+        ]);
+    cmd
+}
+
+/// Probes `path` and decodes it in full; see [`Check`].
+pub fn check_file(
+    runner: &dyn ProcessRunner,
+    path: &Path,
+    threshold_db: f32,
+    min_silence_secs: f64,
+) -> Result<Check> {
+    let mut errors = Vec::new();
+    let probe = probe(runner, path)
+        .map_err(|err| errors.push(format!("{err:#}")))
+        .ok();
+    let out = runner
+        .output(&mut build_check_command(
+            path,
+            threshold_db,
+            min_silence_secs,
+        ))
+        .context("failed to run ffmpeg")?;
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    let mut decode = decode_errors(&stderr);
+    if !out.status.success() && decode.is_empty() {
+        decode.push(format!("ffmpeg exited with {}", out.status));
+    }
+    errors.extend(decode);
+    Ok(Check {
+        probe,
+        errors,
+        silence: parse_edge_silence(&stderr),
+    })
+}
+
+/// Lines of ffmpeg's log reporting a broken stream.
+fn decode_errors(stderr: &str) -> Vec<String> {
+    stderr
+        .lines()
+        .filter(|line| {
+            let line = line.to_lowercase();
+            ["error", "invalid", "corrupt"]
+                .iter()
+                .any(|word| line.contains(word))
+        })
+        .map(|line| line.trim().to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::mock::MockRunner;
+
+    const FFPROBE: &str = r#"{
+        "programs": [],
+        "streams": [{"codec_name": "opus", "channels": 2}],
+        "format": {"duration": "42.507000", "bit_rate": "96512"}
+    }"#;
+
+    #[test]
+    fn reads_duration_and_format() {
+        let runner = MockRunner::with_stdout(FFPROBE);
+        let found = probe(&runner, Path::new("call.ogg")).unwrap();
+        assert_eq!(
+            found,
+            Probe {
+                duration_secs: 42.507,
+                codec: Some("opus".into()),
+                bit_rate: Some(96_512),
+                channels: Some(2),
+            }
+        );
+        assert_eq!(found.summary(), "opus, stereo, 96 kb/s");
+        let call = &runner.calls.lock().unwrap()[0];
+        assert_eq!(call[0], "ffprobe");
+        assert_eq!(call.last().map(String::as_str), Some("call.ogg"));
+    }
+
+    #[test]
+    fn missing_fields_and_failures() {
+        let bare = parse_probe(br#"{"format": {"duration": "3.5"}}"#).unwrap();
+        assert_eq!(bare.duration_secs, 3.5);
+        assert_eq!(bare.summary(), "");
+        assert!(parse_probe(br#"{"format": {}}"#).is_err());
+        assert!(parse_probe(b"not json").is_err());
+        let failing = MockRunner {
+            exit_code: 1,
+            ..MockRunner::default()
+        };
+        assert!(probe(&failing, Path::new("missing.ogg")).is_err());
+    }
+
+    #[test]
+    fn check_reports_probe_and_decode_failures() {
+        let runner = MockRunner::with_stdout(FFPROBE);
+        let check = check_file(&runner, Path::new("call.ogg"), -50.0, 1.0).unwrap();
+        assert!(check.is_ok(), "{check:?}");
+        assert_eq!(check.probe.unwrap().duration_secs, 42.507);
+        let calls = runner.calls.lock().unwrap();
+        assert_eq!(
+            calls[1][..4],
+            ["ffmpeg", "-hide_banner", "-nostdin", "-xerror"]
+        );
+
+        let failing = MockRunner {
+            exit_code: 1,
+            ..MockRunner::default()
+        };
+        let check = check_file(&failing, Path::new("broken.ogg"), -50.0, 1.0).unwrap();
+        assert!(!check.is_ok());
+        assert!(check.probe.is_none());
+        assert_eq!(check.errors.len(), 2);
+        assert_eq!(check.errors[1], "ffmpeg exited with exit status: 1");
+
+        let log = "[ogg @ 0x1] Page at 1234 is missing granule\n\
+                   [opus @ 0x2] Error parsing Opus packet header.\n\
+                   size=N/A time=00:00:02.00 bitrate=N/A\n\
+                   Invalid data found when processing input\n";
+        assert_eq!(
+            decode_errors(log),
+            [
+                "[opus @ 0x2] Error parsing Opus packet header.",
+                "Invalid data found when processing input"
+            ]
+        );
+    }
+}
@@ -0,0 +1,137 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::path::PathBuf;
+use std::process::{Child, Command, Output};
+use std::sync::Mutex;
+
+use crate::sync::lock;
+
+/// Seam between rcrd and the external tools it drives (`ffmpeg`, `pw-dump`),
+/// so command construction can be tested without launching anything.
+pub trait ProcessRunner: Send + Sync {
+    /// Runs `cmd` to completion and collects its output.
+    fn output(&self, cmd: &mut Command) -> io::Result<Output>;
+    /// Starts `cmd` in the background.
+    fn spawn(&self, cmd: &mut Command) -> io::Result<Child>;
+}
+
+/// Runs commands for real.
+pub struct SystemRunner;
+
+impl ProcessRunner for SystemRunner {
+    fn output(&self, cmd: &mut Command) -> io::Result<Output> {
+        cmd.output()
+    }
+
+    fn spawn(&self, cmd: &mut Command) -> io::Result<Child> {
+        cmd.spawn()
+    }
+}
+
+/// Binaries set with `--ffmpeg-bin` and the like, by the tool they replace.
+static TOOL_PATHS: Mutex<BTreeMap<&str, PathBuf>> = Mutex::new(BTreeMap::new());
+
+/// Runs `tool` from `path` from now on instead of looking it up on `PATH`.
+/// Setting `ffmpeg` also sets `ffprobe` when there is one next to it, as
+/// builds ship them together.
+pub fn set_tool_path(tool: &'static str, path: PathBuf) {
+    let mut paths = lock(&TOOL_PATHS);
+    if tool == "ffmpeg" {
+        let ffprobe = path.with_file_name("ffprobe");
+        if ffprobe.is_file() {
+            paths.entry("ffprobe").or_insert(ffprobe);
+        }
+    }
+    paths.insert(tool, path);
+}
+
+/// A command running `tool` (`ffmpeg`, `ffprobe`, `pw-dump`): the binary
+/// set with [`set_tool_path`], or else the one on `PATH`.
+pub fn tool(tool: &str) -> Command {
+    match lock(&TOOL_PATHS).get(tool) {
+        Some(path) => Command::new(path),
+        None => Command::new(tool),
+    }
+}
+
+/// Where [`tool`] runs `tool` from, if that file exists.
+pub fn tool_path(tool: &str) -> Option<PathBuf> {
+    match lock(&TOOL_PATHS).get(tool) {
+        Some(path) => path.is_file().then(|| path.clone()),
+        None => find_in_path(tool),
+    }
+}
+
+/// Program and arguments of `cmd` as plain strings.
+pub fn argv(cmd: &Command) -> Vec<String> {
+    std::iter::once(cmd.get_program())
+        .chain(cmd.get_args())
+        .map(|s| s.to_string_lossy().into_owned())
+        .collect()
+}
+
+/// Full path of `binary` if it is an executable file somewhere on `PATH`.
+pub fn find_in_path(binary: &str) -> Option<PathBuf> {
+    let path = std::env::var_os("PATH")?;
+    std::env::split_paths(&path)
+        .map(|dir| dir.join(binary))
+        .find(|candidate| candidate.is_file())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn configured_tools_replace_the_path_lookup() {
+        assert_eq!(tool("rcrd-test-tool").get_program(), "rcrd-test-tool");
+        assert_eq!(tool_path("rcrd-test-tool"), None);
+        let exe = std::env::current_exe().unwrap();
+        set_tool_path("rcrd-test-tool", exe.clone());
+        assert_eq!(tool("rcrd-test-tool").get_program(), exe.as_os_str());
+        assert_eq!(tool_path("rcrd-test-tool"), Some(exe));
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod mock {
+    use std::os::unix::process::ExitStatusExt;
+    use std::process::ExitStatus;
+    use std::sync::Mutex;
+
+    use super::*;
+
+    /// Records every command and answers `output` with canned stdout.
+    /// `spawn` always fails since a `Child` cannot be faked.
+    #[derive(Default)]
+    pub struct MockRunner {
+        pub stdout: Vec<u8>,
+        pub exit_code: i32,
+        pub calls: Mutex<Vec<Vec<String>>>,
+    }
+
+    impl MockRunner {
+        pub fn with_stdout(stdout: impl Into<Vec<u8>>) -> Self {
+            Self {
+                stdout: stdout.into(),
+                ..Self::default()
+            }
+        }
+    }
+
+    impl ProcessRunner for MockRunner {
+        fn output(&self, cmd: &mut Command) -> io::Result<Output> {
+            self.calls.lock().unwrap().push(argv(cmd));
+            Ok(Output {
+                status: ExitStatus::from_raw(self.exit_code << 8),
+                stdout: self.stdout.clone(),
+                stderr: Vec::new(),
+            })
+        }
+
+        fn spawn(&self, cmd: &mut Command) -> io::Result<Child> {
+            self.calls.lock().unwrap().push(argv(cmd));
+            Err(io::Error::other("mock runner does not spawn"))
+        }
+    }
+}
@@ -0,0 +1,1665 @@
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin};
+use std::sync::atomic::{AtomicBool, AtomicI64, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result, anyhow};
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+use crate::concat::join_pieces;
+use crate::devices::{Defaults, detect_defaults, monitor_for, watch_node};
+use crate::disk::{LOW_SPACE_STOP, LOW_SPACE_WARN, format_bytes, free_bytes};
+use crate::error::ErrorKind;
+use crate::ffmpeg::{
+    AudioFormat, DEFAULT_FLAC_LEVEL, DEFAULT_SAMPLE_RATE, Encoding, FfmpegOptions, Levels,
+    MAX_MIC_VOLUME, OpusApplication, Progress, SUPPORTED_SAMPLE_RATES, SyncMode,
+    prepare_mic_control, send_mic_fade, send_mic_volume, spawn_ffmpeg, write_mic_fade,
+    write_mic_volume,
+};
+use crate::models::verify_model;
+use crate::output::{default_output_name, format_rfc3339, marker_journal_path, now};
+use crate::process::SystemRunner;
+use crate::session::{Session, SessionPart, save_session, session_path};
+use crate::sync::{lock, lock_logged};
+use crate::tags::{FileInfo, Tags, ffmpeg_metadata};
+use crate::transcript::{
+    TransSegment, TranscriberStatus, Windowing, resolve_backend, start_transcriber,
+};
+
+const DEFAULT_TEMPLATE: &str = "rcrd-call-{date}-{time}";
+/// Length of the volume ramp on mute/unmute; a hard cut clicks.
+const MIC_FADE_MS: u32 = 50;
+/// Wait before the first reconnect; doubled after each attempt up to the max.
+const RECONNECT_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_RECONNECT_BACKOFF: Duration = Duration::from_secs(30);
+/// A respawned ffmpeg that ran this long resets the backoff.
+const STABLE_RUN: Duration = Duration::from_secs(60);
+/// How long ffmpeg gets to finish the file after SIGINT when pausing.
+const PAUSE_TIMEOUT: Duration = Duration::from_secs(3);
+/// How often free disk space is checked while recording.
+const DISK_POLL: Duration = Duration::from_secs(5);
+/// How often the session state is saved for `rcrd recover`.
+const SESSION_SAVE: Duration = Duration::from_secs(10);
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Marker {
+    /// Seconds since the recording started.
+    pub timestamp: f64,
+    /// Absolute time the marker was added (RFC 3339).
+    pub wall_clock: String,
+    pub note: String,
+}
+
+/// Devices a recording uses, as [`Recorder::resolve`] found them.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct Devices {
+    /// Sinks whose monitors are tapped; the first names the file.
+    pub sinks: Vec<String>,
+    /// Capture names of their monitors, or those given with [`Recorder::monitor`].
+    pub monitors: Vec<String>,
+    pub mic: Option<String>,
+    /// Where the mix is played to with [`Recorder::monitor_playback`].
+    pub playback: Option<String>,
+}
+
+/// Builder for a recording session.
+///
+/// ```no_run
+/// let mut rec = rcrd::Recorder::new().output("call.ogg").start()?;
+/// rec.add_marker("intro");
+/// let done = rec.stop();
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Default)]
+pub struct Recorder {
+    sinks: Vec<String>,
+    source: Option<String>,
+    no_mic: bool,
+    mic_only: bool,
+    split_channels: bool,
+    output: Option<PathBuf>,
+    duration: Option<u32>,
+    max_duration: Option<u32>,
+    debug: bool,
+    model: Option<PathBuf>,
+    language: Option<String>,
+    backend: Option<String>,
+    threads: Option<usize>,
+    speaker_labels: Option<[String; 2]>,
+    started_at: Option<OffsetDateTime>,
+    min_confidence: Option<f32>,
+    windowing: Windowing,
+    vad_threshold: Option<f32>,
+    prompt: Option<String>,
+    sample_rate: Option<u32>,
+    mono: bool,
+    segment_secs: Option<u32>,
+    strict_backend: bool,
+    mic_cmd_file: Option<PathBuf>,
+    mic_volume: Option<f32>,
+    monitor_playback: bool,
+    playback_sink: Option<String>,
+    devices: Option<Devices>,
+    skip_start: Option<u32>,
+    reconnect: Option<u32>,
+    no_device_cache: bool,
+    wait_for_audio: Duration,
+    monitors: Vec<String>,
+    raw_input: Option<Vec<String>>,
+    opus_application: OpusApplication,
+    opus_frame_ms: Option<f32>,
+    format: AudioFormat,
+    flac_level: Option<u8>,
+    sync_mode: SyncMode,
+    tags: Tags,
+    git_rev: Option<String>,
+    clip_threshold_db: Option<f32>,
+}
+
+impl Recorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// PipeWire sink whose monitor is tapped; defaults to the current default
+    /// sink. Call again to tap several sinks, mixed together.
+    pub fn sink(mut self, sink: impl Into<String>) -> Self {
+        self.sinks.push(sink.into());
+        self
+    }
+
+    /// PipeWire source used as the microphone; defaults to the current default source.
+    pub fn source(mut self, source: impl Into<String>) -> Self {
+        self.source = Some(source.into());
+        self
+    }
+
+    /// Record only the remote side.
+    pub fn no_mic(mut self, no_mic: bool) -> Self {
+        self.no_mic = no_mic;
+        self
+    }
+
+    /// Record only the microphone, without tapping any sink monitor.
+    pub fn mic_only(mut self, mic_only: bool) -> Self {
+        self.mic_only = mic_only;
+        self
+    }
+
+    /// Keep remote (left) and mic (right) on separate channels instead of mixing.
+    pub fn split_channels(mut self, split: bool) -> Self {
+        self.split_channels = split;
+        self
+    }
+
+    /// Output file; defaults to `rcrd-call-YYYYmmdd-HHMMSS.ogg`.
+    pub fn output(mut self, path: impl Into<PathBuf>) -> Self {
+        self.output = Some(path.into());
+        self
+    }
+
+    /// Stop automatically after this many seconds.
+    pub fn duration(mut self, seconds: Option<u32>) -> Self {
+        self.duration = seconds;
+        self
+    }
+
+    /// Stop after this many seconds even without [`Recorder::duration`], as
+    /// a safety cap for recordings nobody stops.
+    pub fn max_duration(mut self, seconds: Option<u32>) -> Self {
+        self.max_duration = seconds;
+        self
+    }
+
+    /// Let ffmpeg write straight to the terminal instead of capturing its log.
+    pub fn debug(mut self, debug: bool) -> Self {
+        self.debug = debug;
+        self
+    }
+
+    /// whisper.cpp model enabling live transcription.
+    pub fn model(mut self, model: Option<PathBuf>) -> Self {
+        self.model = model;
+        self
+    }
+
+    pub fn language(mut self, lang: impl Into<String>) -> Self {
+        self.language = Some(lang.into());
+        self
+    }
+
+    /// Whisper backend: `vulkan` or `openblas`.
+    pub fn backend(mut self, backend: impl Into<String>) -> Self {
+        self.backend = Some(backend.into());
+        self
+    }
+
+    /// Fail instead of falling back to `openblas` when `vulkan` is requested
+    /// but no usable GPU is found.
+    pub fn strict_backend(mut self, strict: bool) -> Self {
+        self.strict_backend = strict;
+        self
+    }
+
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = Some(threads);
+        self
+    }
+
+    /// Labels for the remote and local speakers when recording split channels.
+    pub fn speaker_labels(mut self, labels: [String; 2]) -> Self {
+        self.speaker_labels = Some(labels);
+        self
+    }
+
+    /// Rate the inputs are resampled to and the file is encoded at; one of
+    /// [`SUPPORTED_SAMPLE_RATES`]. Defaults to 48 kHz.
+    pub fn sample_rate(mut self, rate: u32) -> Self {
+        self.sample_rate = Some(rate);
+        self
+    }
+
+    /// Split the file into independent segments of this many seconds
+    /// (`call-000.ogg`, `call-001.ogg`, ...) so a crash loses at most one.
+    pub fn segment_secs(mut self, secs: Option<u32>) -> Self {
+        self.segment_secs = secs;
+        self
+    }
+
+    /// Start capturing right away but drop the first `secs` seconds. Marker
+    /// and transcript times count from the end of the skipped lead-in.
+    pub fn skip_start(mut self, secs: Option<u32>) -> Self {
+        self.skip_start = secs;
+        self
+    }
+
+    /// Opus encoder tuning; [`OpusApplication::Voip`] by default.
+    pub fn opus_application(mut self, app: OpusApplication) -> Self {
+        self.opus_application = app;
+        self
+    }
+
+    /// Opus frame length in ms (20 by default); see [`crate::ffmpeg::OPUS_FRAME_MS`].
+    pub fn opus_frame_ms(mut self, ms: f32) -> Self {
+        self.opus_frame_ms = Some(ms);
+        self
+    }
+
+    /// Codec and container; Opus in Ogg by default.
+    pub fn format(mut self, format: AudioFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// FLAC compression level, 0 to [`crate::ffmpeg::MAX_FLAC_LEVEL`] (5 by
+    /// default); only used with [`AudioFormat::Flac`].
+    pub fn flac_level(mut self, level: u8) -> Self {
+        self.flac_level = Some(level);
+        self
+    }
+
+    /// How late-starting inputs are aligned before mixing; none by default.
+    pub fn sync_mode(mut self, mode: SyncMode) -> Self {
+        self.sync_mode = mode;
+        self
+    }
+
+    /// Tag written into the file (see [`ffmpeg_metadata`]); call again for more.
+    pub fn tag(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.tags.insert(key.into(), value.into());
+        self
+    }
+
+    /// Peak level (dBFS) counted as clipping; 0 by default.
+    pub fn clip_threshold_db(mut self, db: f32) -> Self {
+        self.clip_threshold_db = Some(db);
+        self
+    }
+
+    /// rcrd's revision, named in the file's `comment`.
+    pub fn git_rev(mut self, rev: Option<String>) -> Self {
+        self.git_rev = rev;
+        self
+    }
+
+    /// Capture this input instead of the sinks' monitors; for virtual sinks
+    /// whose monitor is not named `<sink>.monitor`. Repeatable like
+    /// [`sink`](Self::sink).
+    pub fn monitor(mut self, name: impl Into<String>) -> Self {
+        self.monitors.push(name.into());
+        self
+    }
+
+    /// Capture these literal ffmpeg input arguments (from
+    /// [`crate::ffmpeg::parse_raw_input`]) instead of any sink monitor, e.g. a
+    /// JACK port or a network stream. The mic is still mixed in.
+    pub fn raw_input(mut self, args: Vec<String>) -> Self {
+        self.raw_input = Some(args);
+        self
+    }
+
+    /// Always run `pw-dump` to find the default devices instead of reusing
+    /// what a launch in the last few seconds detected.
+    pub fn no_device_cache(mut self, no_cache: bool) -> Self {
+        self.no_device_cache = no_cache;
+        self
+    }
+
+    /// Keep retrying device detection for up to `wait` while PipeWire is
+    /// still starting, e.g. when launched from a login hook.
+    pub fn wait_for_audio(mut self, wait: Duration) -> Self {
+        self.wait_for_audio = wait;
+        self
+    }
+
+    /// Respawn ffmpeg up to `max_attempts` times when it exits on its own
+    /// before the duration is up (e.g. PipeWire restarted), continuing in
+    /// `<name>-part2.<ext>`, `<name>-part3.<ext>`, ... with a growing backoff.
+    pub fn reconnect(mut self, max_attempts: Option<u32>) -> Self {
+        self.reconnect = max_attempts;
+        self
+    }
+
+    /// Use this asendcmd file for mic volume commands instead of a temporary
+    /// one. It is left in place afterwards so it can be inspected.
+    pub fn mic_cmd_file(mut self, path: Option<PathBuf>) -> Self {
+        self.mic_cmd_file = path;
+        self
+    }
+
+    /// Volume the mic starts at and returns to when unmuted (default 1.0).
+    pub fn mic_volume(mut self, volume: Option<f32>) -> Self {
+        self.mic_volume = volume;
+        self
+    }
+
+    /// Also play the mix while recording, to [`Self::playback_sink`] or the
+    /// default sink. A sink whose monitor is tapped is refused: what is
+    /// played would be recorded again and played again, a feedback loop.
+    pub fn monitor_playback(mut self, on: bool) -> Self {
+        self.monitor_playback = on;
+        self
+    }
+
+    /// Sink [`Self::monitor_playback`] plays to instead of the default one.
+    pub fn playback_sink(mut self, sink: Option<String>) -> Self {
+        self.playback_sink = sink;
+        self
+    }
+
+    /// Records on `devices` from an earlier [`Self::resolve`] instead of
+    /// detecting them again.
+    pub fn devices(mut self, devices: Devices) -> Self {
+        self.devices = Some(devices);
+        self
+    }
+
+    /// Downmix the file to a single channel.
+    pub fn mono(mut self, mono: bool) -> Self {
+        self.mono = mono;
+        self
+    }
+
+    /// Drop transcript segments whose mean token probability is below this
+    /// (0..1) from subscribers and the final transcript; they are still shown live.
+    pub fn min_confidence(mut self, min: Option<f32>) -> Self {
+        self.min_confidence = min;
+        self
+    }
+
+    /// Initial prompt for every whisper run, e.g. names and jargon to
+    /// expect; see [`crate::transcript::prepare_prompt`].
+    pub fn prompt(mut self, prompt: Option<String>) -> Self {
+        self.prompt = prompt;
+        self
+    }
+
+    /// Step and length of the live transcription windows.
+    pub fn windowing(mut self, windowing: Windowing) -> Self {
+        self.windowing = windowing;
+        self
+    }
+
+    /// Skip transcribing windows quieter than this RMS level (dBFS).
+    pub fn vad_threshold(mut self, db: Option<f32>) -> Self {
+        self.vad_threshold = db;
+        self
+    }
+
+    /// Wall-clock start of the session, used for the default file name and
+    /// marker times. Defaults to the local time (UTC if the offset is unknown)
+    /// when `start` is called; pass it in when the caller is multi-threaded.
+    pub fn started_at(mut self, tm: OffsetDateTime) -> Self {
+        self.started_at = Some(tm);
+        self
+    }
+
+    /// The devices set on the builder, with monitors named after the sinks;
+    /// those left to detection stay empty.
+    pub fn requested_devices(&self) -> Devices {
+        self.pick_devices(&Defaults::default())
+    }
+
+    /// Detects the default devices (see [`Self::no_device_cache`] and
+    /// [`Self::wait_for_audio`]) for what the builder leaves open. `pw-dump`
+    /// is the slowest part of starting up, so it is skipped when every
+    /// device is given.
+    pub fn resolve(&self) -> Result<Devices> {
+        let needs_detect = (!self.mic_only && self.sinks.is_empty() && !self.explicit_input())
+            || (!self.no_mic && self.source.is_none())
+            || (self.monitor_playback && self.playback_sink.is_none());
+        let defaults = if needs_detect {
+            detect_defaults(!self.no_device_cache, self.wait_for_audio).unwrap_or_default()
+        } else {
+            Default::default()
+        };
+        self.resolve_with(&defaults)
+    }
+
+    fn explicit_input(&self) -> bool {
+        !self.monitors.is_empty() || self.raw_input.is_some()
+    }
+
+    fn pick_devices(&self, defaults: &Defaults) -> Devices {
+        let sinks = if self.mic_only {
+            Vec::new()
+        } else if !self.sinks.is_empty() {
+            self.sinks.clone()
+        } else {
+            defaults.sink.clone().into_iter().collect()
+        };
+        let monitors = if self.mic_only || self.raw_input.is_some() {
+            Vec::new()
+        } else if !self.monitors.is_empty() {
+            self.monitors.clone()
+        } else {
+            sinks
+                .iter()
+                .map(|sink| monitor_for(sink, defaults))
+                .collect()
+        };
+        Devices {
+            sinks,
+            monitors,
+            mic: if self.no_mic {
+                None
+            } else {
+                self.source.clone().or(defaults.source.clone())
+            },
+            playback: if self.monitor_playback {
+                self.playback_sink.clone().or(defaults.sink.clone())
+            } else {
+                None
+            },
+        }
+    }
+
+    /// [`Self::resolve`] with `defaults` as detected.
+    fn resolve_with(&self, defaults: &Defaults) -> Result<Devices> {
+        let devices = self.pick_devices(defaults);
+        // An explicit monitor or raw input makes the sink optional; it then
+        // only names the file.
+        if !self.mic_only && devices.sinks.is_empty() && !self.explicit_input() {
+            return Err(anyhow!("Could not detect default sink").context(ErrorKind::DeviceNotFound));
+        }
+        if !self.no_mic && devices.mic.is_none() {
+            return Err(
+                anyhow!("Could not detect default source").context(ErrorKind::DeviceNotFound)
+            );
+        }
+        if self.monitor_playback {
+            let sink = devices
+                .playback
+                .as_deref()
+                .ok_or_else(|| {
+                    anyhow!(
+                        "Could not detect the default sink to play back to; pass --playback-sink"
+                    )
+                })
+                .context(ErrorKind::DeviceNotFound)?;
+            let monitor = monitor_for(sink, defaults);
+            if devices.monitors.contains(&monitor) {
+                return Err(anyhow!(
+                    "playing back to {sink} would record it again through {monitor}; \
+                     pick another sink with --playback-sink, such as headphones"
+                )
+                .context(ErrorKind::Usage));
+            }
+        }
+        Ok(devices)
+    }
+
+    /// Resolves devices, spawns ffmpeg (and the transcriber when a model is set).
+    pub fn start(mut self) -> Result<RecordingHandle> {
+        if self.no_mic && self.mic_only {
+            return Err(
+                anyhow!("--no-mic and --mic-only are mutually exclusive").context(ErrorKind::Usage)
+            );
+        }
+        if self.mic_only && self.raw_input.is_some() {
+            return Err(anyhow!("--raw-input and --mic-only are mutually exclusive")
+                .context(ErrorKind::Usage));
+        }
+        let Devices {
+            sinks,
+            monitors,
+            mic,
+            playback,
+        } = match self.devices.take() {
+            Some(devices) => devices,
+            None => self.resolve()?,
+        };
+        if self.split_channels && self.mono {
+            return Err(
+                anyhow!("--split-channels and --mono are mutually exclusive")
+                    .context(ErrorKind::Usage),
+            );
+        }
+        if self.split_channels
+            && (mic.is_none() || (monitors.is_empty() && self.raw_input.is_none()))
+        {
+            return Err(
+                anyhow!("--split-channels needs both the monitor and a microphone")
+                    .context(ErrorKind::Usage),
+            );
+        }
+        let started_at = self.started_at.unwrap_or_else(|| now(false).0);
+        let output = self.output.unwrap_or_else(|| {
+            let sink = sinks.first().map_or("", String::as_str);
+            default_output_name(DEFAULT_TEMPLATE, sink, started_at, self.format)
+        });
+        let encoding = Encoding {
+            format: self.format,
+            opus_application: self.opus_application,
+            opus_frame_ms: self.opus_frame_ms.unwrap_or(20.0),
+            flac_level: self.flac_level.unwrap_or(DEFAULT_FLAC_LEVEL),
+        };
+        encoding.validate().context(ErrorKind::Usage)?;
+
+        let sample_rate = self.sample_rate.unwrap_or(DEFAULT_SAMPLE_RATE);
+        let channels = if self.mono { 1 } else { 2 };
+        if !SUPPORTED_SAMPLE_RATES.contains(&sample_rate) {
+            return Err(anyhow!(
+                "unsupported sample rate {sample_rate} (use one of {SUPPORTED_SAMPLE_RATES:?})"
+            )
+            .context(ErrorKind::Usage));
+        }
+        let mic_volume = self.mic_volume.unwrap_or(1.0);
+        let mic_cmd_file = if mic.is_some() {
+            Some(prepare_mic_control(
+                self.mic_cmd_file.as_deref(),
+                mic_volume,
+            )?)
+        } else {
+            None
+        };
+        let audio_level = Arc::new(Mutex::new(Levels::default()));
+        let progress = Arc::new(Mutex::new(Progress::default()));
+        let recent_logs = Arc::new(Mutex::new(Vec::new()));
+        let transcript = Arc::new(Mutex::new(Vec::new()));
+        let subscribers = Arc::new(Mutex::new(Vec::new()));
+        let language = Arc::new(Mutex::new(self.language.unwrap_or_else(|| "en".into())));
+
+        // A model that can't be loaded costs the transcript, not the
+        // recording: ffmpeg then has no PCM tap, and `t` reports the error.
+        let mut transcriber_error = None;
+        let model = self.model.filter(|model| match verify_model(model) {
+            Ok(()) => true,
+            Err(err) => {
+                let msg = format!("Transcription disabled: {err:#}");
+                tracing::error!("{msg}");
+                lock(&recent_logs).push(msg);
+                transcriber_error = Some(format!("{err:#}"));
+                false
+            }
+        });
+
+        let monitor_names: Vec<&str> = monitors.iter().map(String::as_str).collect();
+        // The file begins once the lead-in is over; `elapsed` stays at zero
+        // until then, so markers line up with it.
+        let skipped = Duration::from_secs(self.skip_start.unwrap_or(0) as u64);
+        let title = output.file_stem().unwrap_or_default().to_string_lossy();
+        let info = FileInfo {
+            title: &title,
+            started_at: started_at + skipped,
+            git_rev: self.git_rev.as_deref(),
+        };
+        let metadata: Vec<(String, String)> =
+            ffmpeg_metadata(&self.tags, &info).into_iter().collect();
+        let opts = FfmpegOptions {
+            monitors: &monitor_names,
+            raw_input: self.raw_input.as_deref(),
+            mic: mic.as_deref(),
+            mic_cmd_path: mic_cmd_file.as_deref(),
+            outfile: &output,
+            duration: match (self.duration, self.max_duration) {
+                (Some(d), Some(max)) => Some(d.min(max)),
+                (d, max) => d.or(max),
+            },
+            want_transcript: model.is_some(),
+            split_channels: self.split_channels,
+            sample_rate,
+            channels,
+            segment_secs: self.segment_secs,
+            skip_start: self.skip_start,
+            encoding,
+            sync_mode: self.sync_mode,
+            metadata: &metadata,
+            clip_threshold_db: self.clip_threshold_db.unwrap_or(0.0),
+            standby: false,
+            progress_pipe: false,
+            playback: playback.as_deref(),
+        };
+        let mut child = spawn_ffmpeg(
+            &SystemRunner,
+            &opts,
+            audio_level.clone(),
+            progress.clone(),
+            recent_logs.clone(),
+            self.debug,
+        )
+        .context(ErrorKind::Ffmpeg)?;
+
+        let speaker_labels = self.split_channels.then(|| {
+            self.speaker_labels
+                .clone()
+                .unwrap_or_else(|| ["them".into(), "me".into()])
+        });
+        let mut transcriber = None;
+        if let Some(model) = model
+            && let Some(stdout) = child.stdout.take()
+        {
+            let active = Arc::new(AtomicBool::new(false));
+            let stop = Arc::new(AtomicBool::new(false));
+            let reset = Arc::new(AtomicBool::new(false));
+            let base_offset_ms = Arc::new(AtomicI64::new(0));
+            let status = Arc::new(Mutex::new(TranscriberStatus::Loading));
+            let requested = self.backend.unwrap_or_else(|| "vulkan".into());
+            let backend = match resolve_backend(&requested, self.strict_backend) {
+                Ok(backend) => backend,
+                Err(err) => {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    return Err(err.context(ErrorKind::Transcriber));
+                }
+            };
+            if backend != requested {
+                lock(&recent_logs)
+                    .push("Warning: no usable Vulkan GPU, transcribing with openblas".into());
+            }
+            let whisper_threads = self.threads.unwrap_or(8);
+            let thread = start_transcriber(
+                stdout,
+                model.clone(),
+                language.clone(),
+                transcript.clone(),
+                active.clone(),
+                stop.clone(),
+                backend.clone(),
+                base_offset_ms.clone(),
+                reset.clone(),
+                whisper_threads,
+                subscribers.clone(),
+                speaker_labels.clone(),
+                self.min_confidence,
+                status.clone(),
+                self.windowing,
+                self.vad_threshold,
+                self.prompt.clone(),
+                recent_logs.clone(),
+            );
+            transcriber = Some(Transcriber {
+                model,
+                backend,
+                whisper_threads,
+                min_confidence: self.min_confidence,
+                windowing: self.windowing,
+                vad_threshold: self.vad_threshold,
+                prompt: self.prompt,
+                status,
+                active,
+                stop,
+                reset,
+                base_offset_ms,
+                workers: vec![thread],
+            });
+        }
+
+        let watch_stop = Arc::new(AtomicBool::new(false));
+        let mic_lost = Arc::new(AtomicBool::new(false));
+        let mic_watch = mic.clone().map(|name| {
+            watch_node(
+                name,
+                mic_lost.clone(),
+                watch_stop.clone(),
+                recent_logs.clone(),
+            )
+        });
+
+        tracing::info!(
+            "recording started: output={} monitors={monitors:?} raw_input={:?} mic={mic:?}",
+            output.display(),
+            self.raw_input
+        );
+        let ffmpeg_stdin = child.stdin.take();
+        Ok(RecordingHandle {
+            child,
+            ffmpeg_stdin,
+            start_time: Instant::now() + skipped,
+            started_at: started_at + skipped,
+            sample_rate,
+            channels,
+            duration: self.duration.map(|d| Duration::from_secs(d as u64)),
+            max_duration: self.max_duration.map(|d| Duration::from_secs(d as u64)),
+            output,
+            monitors,
+            raw_input: self.raw_input,
+            mic,
+            keep_mic_cmd_file: self.mic_cmd_file.is_some(),
+            mic_cmd_file,
+            mic_muted: false,
+            mic_volume,
+            mic_start_volume: mic_volume,
+            markers: Vec::new(),
+            audio_level,
+            progress,
+            recent_logs,
+            transcript,
+            subscribers,
+            language,
+            transcriber,
+            transcriber_error,
+            speaker_labels,
+            watch_stop,
+            mic_lost,
+            mic_watch,
+            disk_checked: None,
+            session_saved: None,
+            free_space: None,
+            reconnect: self.reconnect.map(|max_attempts| Reconnect {
+                max_attempts,
+                attempts: 0,
+                backoff: RECONNECT_BACKOFF,
+                retry_at: None,
+                lost_at: Duration::ZERO,
+                spawned: Instant::now(),
+            }),
+            respawn: Respawn {
+                split_channels: self.split_channels,
+                segment_secs: self.segment_secs,
+                encoding,
+                sync_mode: self.sync_mode,
+                metadata,
+                clip_threshold_db: self.clip_threshold_db.unwrap_or(0.0),
+                playback,
+                debug: self.debug,
+            },
+            paused_at: None,
+            paused_total: Duration::ZERO,
+            parts: Vec::new(),
+            ffmpeg_error: None,
+        })
+    }
+}
+
+/// Reconnect budget and backoff.
+struct Reconnect {
+    max_attempts: u32,
+    attempts: u32,
+    backoff: Duration,
+    /// Set while waiting out the backoff after ffmpeg died.
+    retry_at: Option<Instant>,
+    /// Elapsed time when the input dropped.
+    lost_at: Duration,
+    spawned: Instant,
+}
+
+/// What is needed to respawn ffmpeg the same way, after a drop or a pause.
+struct Respawn {
+    split_channels: bool,
+    segment_secs: Option<u32>,
+    encoding: Encoding,
+    sync_mode: SyncMode,
+    metadata: Vec<(String, String)>,
+    clip_threshold_db: f32,
+    playback: Option<String>,
+    debug: bool,
+}
+
+/// A file ffmpeg continued in after the first.
+struct Part {
+    path: PathBuf,
+    /// Recording time it starts at.
+    offset: Duration,
+    /// Started by [`RecordingHandle::resume`]: joined onto the file before
+    /// it on stop. Parts started by reconnecting stay separate files.
+    resumed: bool,
+}
+
+/// File for the `n`th piece of a reconnected recording: `call.ogg` becomes `call-part2.ogg`.
+fn part_path(outfile: &Path, n: usize) -> PathBuf {
+    let stem = outfile.file_stem().unwrap_or_default().to_string_lossy();
+    let ext = outfile
+        .extension()
+        .map(|e| format!(".{}", e.to_string_lossy()))
+        .unwrap_or_default();
+    outfile.with_file_name(format!("{stem}-part{n}{ext}"))
+}
+
+struct Transcriber {
+    model: PathBuf,
+    backend: String,
+    whisper_threads: usize,
+    min_confidence: Option<f32>,
+    windowing: Windowing,
+    vad_threshold: Option<f32>,
+    prompt: Option<String>,
+    status: Arc<Mutex<TranscriberStatus>>,
+    active: Arc<AtomicBool>,
+    stop: Arc<AtomicBool>,
+    reset: Arc<AtomicBool>,
+    base_offset_ms: Arc<AtomicI64>,
+    /// One reader per ffmpeg process; earlier ones end with their process.
+    workers: Vec<JoinHandle<()>>,
+}
+
+/// A running recording. Dropping it kills ffmpeg; call [`stop`](Self::stop)
+/// to finish cleanly and get the results.
+///
+/// The handle is `Send`; everything shared with the worker threads sits
+/// behind its own lock, and accessors return owned snapshots or clones.
+pub struct RecordingHandle {
+    child: Child,
+    /// ffmpeg's interactive command input, present when a mic is recorded.
+    ffmpeg_stdin: Option<ChildStdin>,
+    start_time: Instant,
+    started_at: OffsetDateTime,
+    sample_rate: u32,
+    channels: u8,
+    duration: Option<Duration>,
+    max_duration: Option<Duration>,
+    output: PathBuf,
+    monitors: Vec<String>,
+    raw_input: Option<Vec<String>>,
+    mic: Option<String>,
+    mic_cmd_file: Option<PathBuf>,
+    keep_mic_cmd_file: bool,
+    mic_muted: bool,
+    /// What the mic is at while unmuted.
+    mic_volume: f32,
+    /// What the current ffmpeg started the mic at.
+    mic_start_volume: f32,
+    markers: Vec<Marker>,
+    audio_level: Arc<Mutex<Levels>>,
+    /// What the current ffmpeg reports about the file it writes.
+    progress: Arc<Mutex<Progress>>,
+    recent_logs: Arc<Mutex<Vec<String>>>,
+    transcript: Arc<Mutex<Vec<TransSegment>>>,
+    subscribers: Arc<Mutex<Vec<Sender<TransSegment>>>>,
+    language: Arc<Mutex<String>>,
+    transcriber: Option<Transcriber>,
+    /// Why the configured model was not used.
+    transcriber_error: Option<String>,
+    speaker_labels: Option<[String; 2]>,
+    watch_stop: Arc<AtomicBool>,
+    mic_lost: Arc<AtomicBool>,
+    mic_watch: Option<JoinHandle<()>>,
+    /// When free space was last checked, and what was found.
+    disk_checked: Option<Instant>,
+    /// Last time the session file was written; see [`crate::session`].
+    session_saved: Option<Instant>,
+    free_space: Option<u64>,
+    reconnect: Option<Reconnect>,
+    respawn: Respawn,
+    /// Set while paused; `elapsed` stands still.
+    paused_at: Option<Instant>,
+    /// Time spent paused before `paused_at`.
+    paused_total: Duration,
+    /// Files ffmpeg continued in after reconnecting or resuming.
+    parts: Vec<Part>,
+    /// Exit status when ffmpeg ended on its own with an error.
+    ffmpeg_error: Option<String>,
+}
+
+/// What a finished recording produced.
+pub struct Recording {
+    pub output: PathBuf,
+    /// Tapped monitors; empty when recording with `mic_only`.
+    pub monitors: Vec<String>,
+    /// ffmpeg input arguments recorded instead of a monitor.
+    pub raw_input: Option<Vec<String>>,
+    pub mic: Option<String>,
+    pub sample_rate: u32,
+    pub channels: u8,
+    pub elapsed: Duration,
+    pub markers: Vec<Marker>,
+    pub transcript: Vec<TransSegment>,
+    /// Files the recording continued in after reconnecting, in order.
+    pub parts: Vec<PathBuf>,
+    /// Time spent paused, left out of the file.
+    pub paused: Duration,
+    /// Times the level rose to the clip threshold.
+    pub clips: u32,
+    /// Set when the pieces written between pauses could not be joined;
+    /// they are then listed in `parts`.
+    pub join_error: Option<String>,
+    /// Set when ffmpeg exited with an error instead of being stopped, e.g.
+    /// because a device disappeared; what it wrote is still kept.
+    pub ffmpeg_error: Option<String>,
+}
+
+impl RecordingHandle {
+    pub fn start_time(&self) -> Instant {
+        self.start_time
+    }
+
+    /// Rate the file is encoded at.
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Remote and local speaker labels when recording split channels.
+    pub fn speaker_labels(&self) -> Option<&[String; 2]> {
+        self.speaker_labels.as_ref()
+    }
+
+    /// Channels in the file (1 with `mono`).
+    pub fn channels(&self) -> u8 {
+        self.channels
+    }
+
+    /// Wall-clock time the session started.
+    pub fn started_at(&self) -> OffsetDateTime {
+        self.started_at
+    }
+
+    /// Recorded time: since the start, less any time spent paused.
+    pub fn elapsed(&self) -> Duration {
+        let now = self.paused_at.unwrap_or_else(Instant::now);
+        now.saturating_duration_since(self.start_time)
+            .saturating_sub(self.paused_total)
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused_at.is_some()
+    }
+
+    /// Stops writing until [`resume`](Self::resume), which continues in a
+    /// new file; the pieces are joined on [`stop`](Self::stop), so the
+    /// paused time is not in the file and `elapsed`, markers and transcript
+    /// times skip it. Not available with segmented output.
+    pub fn pause(&mut self) -> Result<()> {
+        if self.paused_at.is_some() {
+            return Ok(());
+        }
+        if self.respawn.segment_secs.is_some() {
+            return Err(anyhow!("pausing is not supported with --crash-safe"));
+        }
+        if self
+            .reconnect
+            .as_ref()
+            .is_some_and(|r| r.retry_at.is_some())
+        {
+            return Err(anyhow!("cannot pause while reconnecting"));
+        }
+        let at = self.elapsed();
+        self.paused_at = Some(Instant::now());
+        self.ffmpeg_stdin = None;
+        finish_ffmpeg(&mut self.child);
+        let mut levels = lock(&self.audio_level);
+        *levels = Levels {
+            clips: levels.clips,
+            ..Levels::default()
+        };
+        drop(levels);
+        tracing::info!("paused at {:.3}s", at.as_secs_f64());
+        self.log(format!("Paused at {:.1}s", at.as_secs_f64()));
+        Ok(())
+    }
+
+    /// Continues a paused recording in the next part file.
+    pub fn resume(&mut self) -> Result<()> {
+        let Some(paused_at) = self.paused_at else {
+            return Ok(());
+        };
+        // The clock stays stopped until ffmpeg runs again.
+        let path = self.respawn(true)?;
+        let paused = paused_at.elapsed();
+        self.paused_total += paused;
+        self.paused_at = None;
+        if let Some(r) = &mut self.reconnect {
+            r.spawned = Instant::now();
+        }
+        tracing::info!(
+            "resumed into {} after {:.1}s paused",
+            path.display(),
+            paused.as_secs_f64()
+        );
+        self.log(format!("Resumed after {:.1}s paused", paused.as_secs_f64()));
+        Ok(())
+    }
+
+    pub fn duration(&self) -> Option<Duration> {
+        self.duration
+    }
+
+    /// The safety cap, when it ends the recording before [`Self::duration`].
+    pub fn max_duration(&self) -> Option<Duration> {
+        self.max_duration
+            .filter(|max| self.duration.is_none_or(|d| *max < d))
+    }
+
+    /// When ffmpeg is told to stop: the duration or the cap, whichever is sooner.
+    fn limit(&self) -> Option<Duration> {
+        self.max_duration().or(self.duration)
+    }
+
+    /// Says why the recording stopped when the cap, not the duration, ended it.
+    fn log_max_duration(&self) {
+        if let Some(max) = self.max_duration() {
+            let msg = format!(
+                "Reached the maximum recording length ({}s); stopping",
+                max.as_secs()
+            );
+            tracing::warn!("{msg}");
+            self.log(msg);
+        }
+    }
+
+    pub fn output(&self) -> &Path {
+        &self.output
+    }
+
+    /// The first tapped monitor.
+    pub fn monitor(&self) -> Option<&str> {
+        self.monitors.first().map(String::as_str)
+    }
+
+    /// Every tapped monitor, mixed together.
+    pub fn monitors(&self) -> &[String] {
+        &self.monitors
+    }
+
+    /// The literal ffmpeg input arguments captured instead of a monitor.
+    pub fn raw_input(&self) -> Option<&[String]> {
+        self.raw_input.as_deref()
+    }
+
+    pub fn mic(&self) -> Option<&str> {
+        self.mic.as_deref()
+    }
+
+    /// Adds a marker at the current position and appends it to the marker
+    /// journal next to the output, so it is kept even if rcrd dies.
+    pub fn add_marker(&mut self, note: impl Into<String>) -> &Marker {
+        let elapsed = self.elapsed();
+        let marker = Marker {
+            timestamp: elapsed.as_secs_f64(),
+            wall_clock: format_rfc3339(self.started_at + elapsed),
+            note: note.into(),
+        };
+        tracing::info!("marker at {:.3}s: {}", marker.timestamp, marker.note);
+        if let Err(err) = self.append_journal(&marker) {
+            self.log(format!("Failed to write marker journal: {err}"));
+        }
+        self.markers.push(marker);
+        self.markers.last().unwrap()
+    }
+
+    pub fn markers(&self) -> &[Marker] {
+        &self.markers
+    }
+
+    /// Removes and returns the most recent marker.
+    pub fn pop_marker(&mut self) -> Option<Marker> {
+        let marker = self.markers.pop()?;
+        self.rewrite_journal();
+        Some(marker)
+    }
+
+    /// Replaces the note of the most recent marker; returns false if there is none.
+    pub fn set_last_marker_note(&mut self, note: impl Into<String>) -> bool {
+        let Some(marker) = self.markers.last_mut() else {
+            return false;
+        };
+        marker.note = note.into();
+        self.rewrite_journal();
+        true
+    }
+
+    fn append_journal(&self, marker: &Marker) -> std::io::Result<()> {
+        let mut f = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(marker_journal_path(&self.output))?;
+        writeln!(f, "{}", serde_json::to_string(marker)?)
+    }
+
+    /// Rewrites the journal from memory after a marker was removed or edited.
+    fn rewrite_journal(&self) {
+        let path = marker_journal_path(&self.output);
+        let res = (|| -> std::io::Result<()> {
+            let mut f = File::create(&path)?;
+            for marker in &self.markers {
+                writeln!(f, "{}", serde_json::to_string(marker)?)?;
+            }
+            Ok(())
+        })();
+        if let Err(err) = res {
+            self.log(format!("Failed to write marker journal: {err}"));
+        }
+    }
+
+    /// True while the recorded source has vanished from PipeWire (e.g. unplugged);
+    /// ffmpeg keeps running but the mic side of the mix is silent.
+    pub fn mic_lost(&self) -> bool {
+        self.mic_lost.load(Ordering::Relaxed)
+    }
+
+    /// Free space on the output's filesystem, while it is below
+    /// [`LOW_SPACE_WARN`].
+    pub fn low_space(&self) -> Option<u64> {
+        self.free_space.filter(|&free| free < LOW_SPACE_WARN)
+    }
+
+    pub fn mic_muted(&self) -> bool {
+        self.mic_muted
+    }
+
+    /// Volume the mic is recorded at while unmuted (`--mic-volume`, `+`/`-`).
+    pub fn mic_volume(&self) -> f32 {
+        self.mic_volume
+    }
+
+    /// Sets the mic volume, clamped to 0..=[`MAX_MIC_VOLUME`] and returned.
+    /// While muted it only becomes the level unmuting returns to.
+    pub fn set_mic_volume(&mut self, volume: f32) -> Result<f32> {
+        let volume = volume.clamp(0.0, MAX_MIC_VOLUME);
+        let Some(cmd_path) = &self.mic_cmd_file else {
+            return Ok(self.mic_volume);
+        };
+        if !self.mic_muted {
+            write_mic_volume(
+                cmd_path,
+                self.mic_start_volume,
+                self.elapsed().as_secs_f64(),
+                volume,
+            )?;
+            if let Some(stdin) = &mut self.ffmpeg_stdin {
+                send_mic_volume(stdin, volume)?;
+            }
+        }
+        tracing::info!("mic volume={volume}");
+        self.mic_volume = volume;
+        Ok(volume)
+    }
+
+    /// Mutes or unmutes the microphone; a no-op when no mic is recorded.
+    pub fn set_mic_muted(&mut self, muted: bool) -> Result<()> {
+        let Some(cmd_path) = &self.mic_cmd_file else {
+            return Ok(());
+        };
+        if muted == self.mic_muted {
+            return Ok(());
+        }
+        let volume = self.mic_volume;
+        let (from, to) = if muted { (volume, 0.0) } else { (0.0, volume) };
+        write_mic_fade(
+            cmd_path,
+            self.mic_start_volume,
+            self.elapsed().as_secs_f64(),
+            from,
+            to,
+            MIC_FADE_MS,
+        )?;
+        if let Some(stdin) = &mut self.ffmpeg_stdin {
+            send_mic_fade(stdin, from, to, MIC_FADE_MS)?;
+        }
+        tracing::info!("mic muted={muted}");
+        self.mic_muted = muted;
+        Ok(())
+    }
+
+    /// Snapshot of the current levels.
+    ///
+    /// The meter is fed by ffmpeg's log reader thread; this only holds its
+    /// lock for the copy, so it is cheap to call from a render loop and a
+    /// caller can never block or poison the writer.
+    pub fn levels(&self) -> Levels {
+        *lock_logged(&self.audio_level, "audio levels", &self.recent_logs)
+    }
+
+    /// Size and length of the file being written, as ffmpeg reports them;
+    /// after a pause or reconnect, of the current part only.
+    pub fn progress(&self) -> Progress {
+        *lock(&self.progress)
+    }
+
+    /// Lets the held peaks fall for `dt`; call once per meter refresh.
+    pub fn decay_levels(&self, dt: Duration) {
+        lock_logged(&self.audio_level, "audio levels", &self.recent_logs).decay(dt);
+    }
+
+    /// Recent ffmpeg log lines and status messages.
+    pub fn logs(&self) -> Arc<Mutex<Vec<String>>> {
+        self.recent_logs.clone()
+    }
+
+    pub fn log(&self, msg: impl Into<String>) {
+        lock(&self.recent_logs).push(msg.into());
+    }
+
+    /// Transcript segments produced so far.
+    pub fn transcript(&self) -> Arc<Mutex<Vec<TransSegment>>> {
+        self.transcript.clone()
+    }
+
+    /// Inserts `text` into the transcript at the current position, flagged
+    /// [`manual`](TransSegment::manual), and sends it to subscribers.
+    pub fn add_note(&self, text: impl Into<String>) {
+        let ms = self.elapsed().as_millis().try_into().unwrap_or(i64::MAX);
+        let note = TransSegment {
+            manual: true,
+            ..TransSegment::new(ms, ms, text)
+        };
+        tracing::info!("transcript note at {ms}ms: {}", note.text);
+        lock(&self.subscribers).retain(|tx| tx.send(note.clone()).is_ok());
+        let mut transcript = lock(&self.transcript);
+        // Windows still being transcribed may end up before the note.
+        let at = transcript.partition_point(|seg| seg.start_ms <= ms);
+        transcript.insert(at, note);
+    }
+
+    /// Returns a channel receiving every transcript segment produced from now on.
+    pub fn subscribe(&self) -> Receiver<TransSegment> {
+        let (tx, rx) = mpsc::channel();
+        lock(&self.subscribers).push(tx);
+        rx
+    }
+
+    /// whisper model in use; `None` without transcription.
+    pub fn model(&self) -> Option<&Path> {
+        self.transcriber.as_ref().map(|t| t.model.as_path())
+    }
+
+    /// Backend whisper runs on (`vulkan` or `openblas`, after any fallback).
+    pub fn backend(&self) -> Option<&str> {
+        self.transcriber.as_ref().map(|t| t.backend.as_str())
+    }
+
+    /// Why transcription is off although a model was configured, e.g. a
+    /// missing or corrupt model file.
+    pub fn transcriber_error(&self) -> Option<&str> {
+        self.transcriber_error.as_deref()
+    }
+
+    /// Outcome of the latest whisper run; `None` without a model.
+    pub fn transcriber_status(&self) -> Option<TranscriberStatus> {
+        self.transcriber.as_ref().map(|t| lock(&t.status).clone())
+    }
+
+    pub fn is_transcribing(&self) -> bool {
+        self.transcriber
+            .as_ref()
+            .is_some_and(|t| t.active.load(Ordering::Relaxed))
+    }
+
+    /// Starts or pauses live transcription. Segment times stay relative to the
+    /// recording start. Returns `false` when no model is configured.
+    pub fn set_transcribing(&self, on: bool) -> bool {
+        let Some(t) = &self.transcriber else {
+            return false;
+        };
+        t.active.store(on, Ordering::Relaxed);
+        tracing::info!("transcription active={on}");
+        if on {
+            let elapsed_ms = self.elapsed().as_millis().try_into().unwrap_or(0);
+            t.base_offset_ms.store(elapsed_ms, Ordering::Relaxed);
+            t.reset.store(true, Ordering::Relaxed);
+        }
+        true
+    }
+
+    pub fn language(&self) -> String {
+        lock(&self.language).clone()
+    }
+
+    pub fn set_language(&self, lang: impl Into<String>) {
+        *lock(&self.language) = lang.into();
+    }
+
+    /// Whether ffmpeg has exited (duration reached, device gone, ...), or
+    /// should be stopped because the disk is almost full.
+    ///
+    /// With [`Recorder::reconnect`], an early exit is not reported: ffmpeg is
+    /// respawned into the next part file once the backoff has passed, and
+    /// this only returns `true` when the attempts are used up.
+    pub fn is_finished(&mut self) -> Result<bool> {
+        if self
+            .session_saved
+            .is_none_or(|at| at.elapsed() >= SESSION_SAVE)
+        {
+            self.session_saved = Some(Instant::now());
+            self.save_session();
+        }
+        if self.paused_at.is_some() {
+            return Ok(false);
+        }
+        if self.limit().is_some_and(|d| self.elapsed() >= d) {
+            self.log_max_duration();
+            return Ok(true);
+        }
+        if self.disk_checked.is_none_or(|at| at.elapsed() >= DISK_POLL) {
+            self.disk_checked = Some(Instant::now());
+            self.free_space = free_bytes(&self.output).ok();
+            if let Some(free) = self.free_space
+                && free < LOW_SPACE_STOP
+            {
+                let msg = format!(
+                    "Only {} left on disk; stopping before it fills",
+                    format_bytes(free)
+                );
+                tracing::warn!("{msg}");
+                self.log(msg);
+                return Ok(true);
+            }
+        }
+        let retry_at = self.reconnect.as_ref().and_then(|r| r.retry_at);
+        let Some(retry_at) = retry_at else {
+            let Some(status) = self.child.try_wait()? else {
+                return Ok(false);
+            };
+            let at_end = self
+                .limit()
+                .is_some_and(|d| self.elapsed() + Duration::from_secs(1) >= d);
+            if at_end {
+                self.log_max_duration();
+            }
+            if at_end || self.reconnect.is_none() {
+                return Ok(true);
+            }
+            self.reconnect.as_mut().unwrap().lost_at = self.elapsed();
+            return Ok(!self.schedule_reconnect(&format!("ffmpeg exited ({status})")));
+        };
+        if Instant::now() < retry_at {
+            return Ok(false);
+        }
+        match self.respawn(false) {
+            Ok(path) => {
+                let elapsed = self.elapsed();
+                let r = self.reconnect.as_mut().unwrap();
+                r.retry_at = None;
+                r.spawned = Instant::now();
+                let gap = elapsed.saturating_sub(r.lost_at).as_secs_f64();
+                let at = r.lost_at.as_secs_f64();
+                self.log(format!(
+                    "Reconnected: {gap:.1}s gap at {at:.1}s; continuing in {}",
+                    path.display()
+                ));
+                tracing::info!("reconnected into {} after a {gap:.1}s gap", path.display());
+                Ok(false)
+            }
+            Err(err) => Ok(!self.schedule_reconnect(&format!("respawning ffmpeg failed: {err:#}"))),
+        }
+    }
+
+    /// Saves what `rcrd recover` needs should rcrd die before [`Self::stop`].
+    fn save_session(&self) {
+        let session = Session {
+            output: self.output.clone(),
+            started_at: format_rfc3339(self.started_at),
+            format: self.respawn.encoding.format,
+            segmented: self.respawn.segment_secs.is_some(),
+            parts: self
+                .parts
+                .iter()
+                .map(|part| SessionPart {
+                    path: part.path.clone(),
+                    offset_secs: part.offset.as_secs_f64(),
+                    resumed: part.resumed,
+                })
+                .collect(),
+            elapsed_secs: self.elapsed().as_secs_f64(),
+            paused_secs: self.paused_total.as_secs_f64(),
+            markers: self.markers.clone(),
+            transcript: lock(&self.transcript)
+                .iter()
+                .filter(|seg| !seg.low_confidence)
+                .cloned()
+                .collect(),
+        };
+        if let Err(err) = save_session(&session) {
+            tracing::warn!("saving the session failed: {err:#}");
+        }
+    }
+
+    /// Arms the next reconnect attempt after `reason`; `false` when none are left.
+    fn schedule_reconnect(&mut self, reason: &str) -> bool {
+        let elapsed = self.elapsed().as_secs_f64();
+        let r = self.reconnect.as_mut().unwrap();
+        if r.attempts >= r.max_attempts {
+            r.retry_at = None;
+            self.log(format!(
+                "{reason} at {elapsed:.1}s; no reconnect attempts left"
+            ));
+            return false;
+        }
+        if r.retry_at.is_none() && r.spawned.elapsed() >= STABLE_RUN {
+            r.backoff = RECONNECT_BACKOFF;
+        }
+        r.attempts += 1;
+        r.retry_at = Some(Instant::now() + r.backoff);
+        let msg = format!(
+            "{reason} at {elapsed:.1}s; reconnecting in {}s (attempt {}/{})",
+            r.backoff.as_secs(),
+            r.attempts,
+            r.max_attempts
+        );
+        r.backoff = (r.backoff * 2).min(MAX_RECONNECT_BACKOFF);
+        tracing::warn!("{msg}");
+        self.log(msg);
+        true
+    }
+
+    /// Starts ffmpeg again on the same devices, writing the next part file,
+    /// and hands its PCM to a fresh transcriber reader whose segment times
+    /// continue from the current elapsed time.
+    fn respawn(&mut self, resumed: bool) -> Result<PathBuf> {
+        let r = &self.respawn;
+        let path = part_path(&self.output, self.parts.len() + 2);
+        // The new ffmpeg reads the command file from its own time zero.
+        if let Some(cmd_path) = &self.mic_cmd_file {
+            let volume = if self.mic_muted { 0.0 } else { self.mic_volume };
+            write_mic_volume(cmd_path, volume, 0.0, volume)?;
+            self.mic_start_volume = volume;
+        }
+        let remaining = self
+            .limit()
+            .map(|d| d.saturating_sub(self.elapsed()).as_secs().max(1) as u32);
+        let monitor_names: Vec<&str> = self.monitors.iter().map(String::as_str).collect();
+        let opts = FfmpegOptions {
+            monitors: &monitor_names,
+            raw_input: self.raw_input.as_deref(),
+            mic: self.mic.as_deref(),
+            mic_cmd_path: self.mic_cmd_file.as_deref(),
+            duration: remaining,
+            want_transcript: self.transcriber.is_some(),
+            split_channels: r.split_channels,
+            sample_rate: self.sample_rate,
+            channels: self.channels,
+            segment_secs: r.segment_secs,
+            encoding: r.encoding,
+            sync_mode: r.sync_mode,
+            metadata: &r.metadata,
+            clip_threshold_db: r.clip_threshold_db,
+            playback: r.playback.as_deref(),
+            ..FfmpegOptions::new(&path)
+        };
+        *lock(&self.progress) = Progress::default();
+        let mut child = spawn_ffmpeg(
+            &SystemRunner,
+            &opts,
+            self.audio_level.clone(),
+            self.progress.clone(),
+            self.recent_logs.clone(),
+            r.debug,
+        )?;
+        let elapsed_ms = self.elapsed().as_millis().try_into().unwrap_or(0);
+        if let Some(t) = &mut self.transcriber
+            && let Some(stdout) = child.stdout.take()
+        {
+            t.base_offset_ms = Arc::new(AtomicI64::new(elapsed_ms));
+            t.reset = Arc::new(AtomicBool::new(false));
+            t.workers.push(start_transcriber(
+                stdout,
+                t.model.clone(),
+                self.language.clone(),
+                self.transcript.clone(),
+                t.active.clone(),
+                t.stop.clone(),
+                t.backend.clone(),
+                t.base_offset_ms.clone(),
+                t.reset.clone(),
+                t.whisper_threads,
+                self.subscribers.clone(),
+                self.speaker_labels.clone(),
+                t.min_confidence,
+                t.status.clone(),
+                t.windowing,
+                t.vad_threshold,
+                t.prompt.clone(),
+                self.recent_logs.clone(),
+            ));
+        }
+        self.ffmpeg_stdin = child.stdin.take();
+        self.child = child;
+        self.parts.push(Part {
+            path: path.clone(),
+            offset: self.elapsed(),
+            resumed,
+        });
+        self.save_session();
+        Ok(path)
+    }
+
+    /// Blocks until ffmpeg exits on its own.
+    pub fn wait(&mut self) -> Result<()> {
+        self.child.wait()?;
+        Ok(())
+    }
+
+    /// Stops ffmpeg and the transcriber and returns what was recorded.
+    pub fn stop(mut self) -> Recording {
+        self.shutdown();
+        let mut transcript: Vec<TransSegment> = lock(&self.transcript)
+            .iter()
+            .filter(|seg| !seg.low_confidence)
+            .cloned()
+            .collect();
+        // Notes typed while a window was being transcribed come before it.
+        transcript.sort_by_key(|seg| seg.start_ms);
+        let (parts, join_error) = self.join_resumed_parts();
+        // Stopped cleanly: what the session held is in the result now.
+        let _ = std::fs::remove_file(session_path(&self.output));
+        Recording {
+            output: self.output.clone(),
+            monitors: self.monitors.clone(),
+            raw_input: self.raw_input.clone(),
+            mic: self.mic.clone(),
+            sample_rate: self.sample_rate,
+            channels: self.channels,
+            elapsed: self.elapsed(),
+            markers: std::mem::take(&mut self.markers),
+            transcript,
+            parts,
+            paused: self.paused_total + self.paused_at.map_or(Duration::ZERO, |at| at.elapsed()),
+            join_error,
+            clips: self.levels().clips,
+            ffmpeg_error: self.ffmpeg_error.take(),
+        }
+    }
+
+    /// Joins each file with the parts resumed after it and returns the files
+    /// left after the first, with the first join error.
+    fn join_resumed_parts(&mut self) -> (Vec<PathBuf>, Option<String>) {
+        let mut runs: Vec<Vec<PathBuf>> = vec![vec![self.output.clone()]];
+        for part in std::mem::take(&mut self.parts) {
+            match runs.last_mut() {
+                Some(run) if part.resumed => run.push(part.path),
+                _ => runs.push(vec![part.path]),
+            }
+        }
+        let mut parts = Vec::new();
+        let mut error = None;
+        for (i, run) in runs.into_iter().enumerate() {
+            let kept = match join_pieces(&SystemRunner, &run, self.respawn.encoding.format) {
+                Ok(()) => run[..1].to_vec(),
+                Err(err) => {
+                    tracing::warn!("joining {} failed: {err:#}", run[0].display());
+                    error.get_or_insert(format!("{err:#}"));
+                    run
+                }
+            };
+            // The output itself is not a part.
+            parts.extend(kept.into_iter().skip(usize::from(i == 0)));
+        }
+        (parts, error)
+    }
+
+    fn shutdown(&mut self) {
+        match self.child.try_wait() {
+            // Stopped by `pause`, with the file finished.
+            _ if self.paused_at.is_some() => {}
+            Ok(Some(status)) => {
+                tracing::info!("ffmpeg had exited with {status}");
+                if !status.success() {
+                    self.ffmpeg_error = Some(format!("ffmpeg exited with {status}"));
+                }
+            }
+            _ => {
+                // Killing it would leave the file without its trailer: the
+                // last Ogg page, or the sample count and MD5 of FLAC.
+                finish_ffmpeg(&mut self.child);
+                tracing::info!("ffmpeg stopped after {:.1}s", self.elapsed().as_secs_f64());
+            }
+        }
+        if let Some(t) = &mut self.transcriber {
+            t.stop.store(true, Ordering::Relaxed);
+            for worker in t.workers.drain(..) {
+                let _ = worker.join();
+            }
+        }
+        self.watch_stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.mic_watch.take() {
+            let _ = thread.join();
+        }
+        if let Some(path) = self.mic_cmd_file.take()
+            && !self.keep_mic_cmd_file
+        {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+/// Lets ffmpeg finish the file on SIGINT, killing it if it takes too long.
+fn finish_ffmpeg(child: &mut Child) {
+    if let Ok(pid) = i32::try_from(child.id()) {
+        // SAFETY: kill(2) only sends a signal to ffmpeg, our own child.
+        unsafe { libc::kill(pid, libc::SIGINT) };
+    }
+    let deadline = Instant::now() + PAUSE_TIMEOUT;
+    while Instant::now() < deadline {
+        if !matches!(child.try_wait(), Ok(None)) {
+            return;
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    }
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+impl Drop for RecordingHandle {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn playback_refuses_a_recorded_sink() {
+        let defaults = Defaults {
+            sink: Some("speakers".into()),
+            source: None,
+            monitor: Some("speakers.monitor".into()),
+        };
+        let playing = Recorder::new().no_mic(true).monitor_playback(true);
+        let err = playing.resolve_with(&defaults).unwrap_err();
+        assert!(
+            format!("{err:#}").contains("would record it again"),
+            "{err:#}"
+        );
+        let devices = playing
+            .playback_sink(Some("headphones".into()))
+            .resolve_with(&defaults)
+            .unwrap();
+        assert_eq!(devices.monitors, ["speakers.monitor"]);
+        assert_eq!(devices.playback.as_deref(), Some("headphones"));
+        // Mic-only recordings tap no monitor, so the default sink is fine.
+        let mic_only = Recorder::new()
+            .mic_only(true)
+            .source("mic")
+            .monitor_playback(true);
+        let devices = mic_only.resolve_with(&defaults).unwrap();
+        assert_eq!(devices.playback.as_deref(), Some("speakers"));
+        assert!(mic_only.resolve_with(&Defaults::default()).is_err());
+    }
+}
@@ -0,0 +1,46 @@
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use rcrd::error::ErrorKind;
+use rcrd::process::SystemRunner;
+use rcrd::session::{load_session, recover, session_path};
+
+/// `rcrd recover`: finishes the recording `path` (its session file, or the
+/// audio file next to it) after rcrd was killed while recording.
+pub fn run_recover(path: &Path) -> Result<()> {
+    let session_file = if path.to_string_lossy().ends_with(".session.json") {
+        path.to_path_buf()
+    } else {
+        session_path(path)
+    };
+    let session = load_session(&session_file).context(ErrorKind::Usage)?;
+    println!(
+        "Recovering {} (started {}, {:.0}s recorded at the last save)",
+        session.output.display(),
+        session.started_at,
+        session.elapsed_secs
+    );
+    let recovered = recover(&SystemRunner, &session).context(ErrorKind::Ffmpeg)?;
+    for warning in &recovered.warnings {
+        eprintln!("Warning: {warning}");
+    }
+    if !recovered.warnings.is_empty() {
+        eprintln!(
+            "Kept {} to run rcrd recover again once this is fixed.",
+            session_file.display()
+        );
+    }
+    if session.segmented {
+        println!("Segments are independent files and are left as they are.");
+    }
+    for file in &recovered.files {
+        println!("Recovered {}", file.display());
+    }
+    if let Some(path) = &recovered.markers {
+        println!("Saved markers to {}", path.display());
+    }
+    if let Some(path) = &recovered.transcript {
+        println!("Saved transcript to {}", path.display());
+    }
+    Ok(())
+}
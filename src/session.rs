@@ -0,0 +1,223 @@
+//! Session state saved while recording (`<file>.session.json`), so that a
+//! recording whose rcrd was killed can be finished afterwards with
+//! `rcrd recover`: the files are remuxed to repair their containers and the
+//! markers and transcript are written out as on a normal stop.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{Context, Result, anyhow};
+use serde::{Deserialize, Serialize};
+
+use crate::Marker;
+use crate::concat::join_pieces;
+use crate::ffmpeg::AudioFormat;
+use crate::output::{load_marker_journal, marker_journal_path, save_markers};
+use crate::process::{ProcessRunner, tool};
+use crate::transcript::TransSegment;
+use crate::transcript::export::save_csv;
+
+/// Everything needed to finish a recording that didn't stop cleanly.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Session {
+    pub output: PathBuf,
+    /// Recording start, RFC 3339.
+    pub started_at: String,
+    pub format: AudioFormat,
+    /// Written as `--segment` pieces, which are independent files already.
+    pub segmented: bool,
+    /// Files ffmpeg continued in after the output, in order.
+    pub parts: Vec<SessionPart>,
+    /// Recording time when the state was saved, paused time excluded.
+    pub elapsed_secs: f64,
+    pub paused_secs: f64,
+    /// Markers at the last save; the marker journal, when present, is newer.
+    pub markers: Vec<Marker>,
+    pub transcript: Vec<TransSegment>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SessionPart {
+    pub path: PathBuf,
+    /// Recording time the part starts at; marker and transcript times
+    /// count from the start of the output.
+    pub offset_secs: f64,
+    /// Continued after a pause: joined onto the file before it.
+    pub resumed: bool,
+}
+
+/// Where the session of `outfile` is kept.
+pub fn session_path(outfile: &Path) -> PathBuf {
+    outfile.with_extension("session.json")
+}
+
+/// Writes the session next to its output, through a sibling renamed over
+/// it so a crash mid-write leaves the previous state.
+pub fn save_session(session: &Session) -> Result<PathBuf> {
+    let path = session_path(&session.output);
+    let tmp = path.with_extension("json.tmp");
+    let data = serde_json::to_vec(session)?;
+    std::fs::write(&tmp, data).with_context(|| format!("writing {}", tmp.display()))?;
+    std::fs::rename(&tmp, &path).with_context(|| format!("writing {}", path.display()))?;
+    Ok(path)
+}
+
+pub fn load_session(path: &Path) -> Result<Session> {
+    let data = std::fs::read_to_string(path)
+        .with_context(|| format!("reading session {}", path.display()))?;
+    serde_json::from_str(&data).with_context(|| format!("parsing session {}", path.display()))
+}
+
+/// Copies `input` into `output` without re-encoding, which writes the
+/// container afresh: the trailer, durations and seek data a killed ffmpeg
+/// never got to.
+pub fn build_remux_command(input: &Path, output: &Path, format: AudioFormat) -> Command {
+    let mut cmd = tool("ffmpeg");
+    cmd.args(["-hide_banner", "-nostdin", "-y", "-loglevel", "error", "-i"])
+        .arg(input);
+    cmd.args(["-map", "0", "-c", "copy", "-f", format.muxer()])
+        .arg(output);
+    cmd
+}
+
+/// Remuxes `file` in place; on failure it is left as it was.
+fn remux(runner: &dyn ProcessRunner, file: &Path, format: AudioFormat) -> Result<()> {
+    let tmp = file.with_extension(format!("recovering.{}", format.extension()));
+    let out = runner
+        .output(&mut build_remux_command(file, &tmp, format))
+        .context("failed to run ffmpeg")?;
+    if !out.status.success() {
+        let _ = std::fs::remove_file(&tmp);
+        return Err(anyhow!(
+            "remuxing {} exited with {}: {}",
+            file.display(),
+            out.status,
+            String::from_utf8_lossy(&out.stderr).trim()
+        ));
+    }
+    std::fs::rename(&tmp, file)
+        .with_context(|| format!("replacing {} with the remuxed file", file.display()))
+}
+
+/// What [`recover`] made of a session.
+#[derive(Debug, Default)]
+pub struct Recovered {
+    /// Audio files left, the output first.
+    pub files: Vec<PathBuf>,
+    pub markers: Option<PathBuf>,
+    pub transcript: Option<PathBuf>,
+    /// Files that couldn't be remuxed or joined; they are kept as found.
+    pub warnings: Vec<String>,
+}
+
+/// Finishes the recording `session` describes: remuxes every file found,
+/// joins the pieces of a paused recording, saves the markers (from the
+/// journal when there is one) and the transcript as CSV. The session file
+/// is removed only when nothing failed, so the run can be repeated once the
+/// problem is fixed.
+pub fn recover(runner: &dyn ProcessRunner, session: &Session) -> Result<Recovered> {
+    let mut recovered = Recovered::default();
+    // Each run is a file and the parts resumed after it.
+    let mut runs: Vec<Vec<PathBuf>> = vec![vec![session.output.clone()]];
+    for part in &session.parts {
+        match runs.last_mut() {
+            Some(run) if part.resumed => run.push(part.path.clone()),
+            _ => runs.push(vec![part.path.clone()]),
+        }
+    }
+    for run in &mut runs {
+        run.retain(|file| file.exists());
+    }
+    runs.retain(|run| !run.is_empty());
+    if runs.is_empty() && !session.segmented {
+        return Err(anyhow!(
+            "none of the audio files of {} are left",
+            session.output.display()
+        ));
+    }
+    for run in runs {
+        for file in &run {
+            if let Err(err) = remux(runner, file, session.format) {
+                recovered.warnings.push(format!("{err:#}"));
+            }
+        }
+        match join_pieces(runner, &run, session.format) {
+            Ok(()) => recovered.files.push(run[0].clone()),
+            Err(err) => {
+                recovered.warnings.push(format!("{err:#}"));
+                recovered.files.extend(run);
+            }
+        }
+    }
+
+    let journal = marker_journal_path(&session.output);
+    let markers = if journal.exists() {
+        load_marker_journal(&journal)?
+    } else {
+        session.markers.clone()
+    };
+    if !markers.is_empty() {
+        recovered.markers = Some(save_markers(&markers, &session.output)?);
+    }
+    recovered.transcript = save_csv(&session.transcript, &session.output)?;
+    if recovered.warnings.is_empty() {
+        let _ = std::fs::remove_file(session_path(&session.output));
+    }
+    Ok(recovered)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::mock::MockRunner;
+
+    #[test]
+    fn failed_remux_keeps_the_file_and_saves_the_sidecars() {
+        let dir = std::env::temp_dir().join(format!("rcrd-session-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let output = dir.join("call.ogg");
+        std::fs::write(&output, b"truncated ogg").unwrap();
+        let session = Session {
+            output: output.clone(),
+            started_at: "2024-05-06T09:30:00+02:00".into(),
+            format: AudioFormat::Opus,
+            segmented: false,
+            parts: vec![SessionPart {
+                path: dir.join("call-part2.ogg"),
+                offset_secs: 30.0,
+                resumed: true,
+            }],
+            elapsed_secs: 42.5,
+            paused_secs: 0.0,
+            markers: vec![Marker {
+                timestamp: 12.0,
+                wall_clock: String::new(),
+                note: "decision".into(),
+            }],
+            transcript: Vec::new(),
+        };
+        let path = save_session(&session).unwrap();
+        let loaded = load_session(&path).unwrap();
+        assert_eq!(loaded.parts, session.parts);
+        assert_eq!(loaded.markers[0].note, "decision");
+
+        let failing = MockRunner {
+            exit_code: 1,
+            ..MockRunner::default()
+        };
+        let recovered = recover(&failing, &session).unwrap();
+        // The missing part is skipped; the output is remuxed but not joined.
+        assert_eq!(failing.calls.lock().unwrap().len(), 1);
+        assert_eq!(recovered.files, [output.as_path()]);
+        assert_eq!(recovered.warnings.len(), 1);
+        assert_eq!(std::fs::read(&output).unwrap(), b"truncated ogg");
+        assert_eq!(recovered.markers, Some(output.with_extension("json")));
+        assert_eq!(recovered.transcript, None);
+        // Kept so recover can run again.
+        assert!(path.exists());
+
+        std::fs::remove_file(&output).unwrap();
+        assert!(recover(&failing, &session).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,26 @@
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+/// Locks `m`, recovering the guard if a thread panicked while holding it.
+///
+/// The shared state here (levels, logs, transcript) stays usable after a
+/// partial update, so carrying on beats freezing the UI on a poisoned lock.
+pub fn lock<T>(m: &Mutex<T>) -> MutexGuard<'_, T> {
+    m.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Like [`lock`], but notes the recovery in `logs` (once, as the poison flag
+/// is cleared).
+pub fn lock_logged<'a, T>(
+    m: &'a Mutex<T>,
+    name: &str,
+    logs: &Mutex<Vec<String>>,
+) -> MutexGuard<'a, T> {
+    match m.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => {
+            m.clear_poison();
+            lock(logs).push(format!("Recovered {name} after a worker thread panicked"));
+            poisoned.into_inner()
+        }
+    }
+}
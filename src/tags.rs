@@ -0,0 +1,181 @@
+//! Tags describing a recording (`--tag project=apollo`): kept in the
+//! metadata sidecar and written into the file itself, so media players list
+//! them too.
+
+use std::collections::BTreeMap;
+
+use anyhow::{Result, anyhow};
+use time::OffsetDateTime;
+
+use crate::output::format_rfc3339;
+
+/// Tag names (lowercase) to values.
+pub type Tags = BTreeMap<String, String>;
+
+/// Longest tag name accepted.
+const MAX_KEY_LEN: usize = 64;
+
+/// Parses `KEY=VALUE`. Keys are lowercased and must start with a letter,
+/// followed by letters, digits, `_` or `-`; they can't hold `=`, which
+/// would end the key in ffmpeg's `-metadata key=value`. Control characters
+/// in the value (newlines included) become spaces.
+pub fn parse_tag(spec: &str) -> Result<(String, String)> {
+    let (key, value) = spec
+        .split_once('=')
+        .ok_or_else(|| anyhow!("tag `{spec}` is not KEY=VALUE"))?;
+    let key = key.trim().to_lowercase();
+    let valid = key.len() <= MAX_KEY_LEN
+        && key.starts_with(|c: char| c.is_ascii_alphabetic())
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if !valid {
+        return Err(anyhow!(
+            "tag name `{key}` must start with a letter and hold only letters, digits, `_` or `-`"
+        ));
+    }
+    let value: String = value
+        .trim()
+        .chars()
+        .map(|c| if c.is_control() { ' ' } else { c })
+        .collect();
+    if value.is_empty() {
+        return Err(anyhow!("tag `{key}` has no value"));
+    }
+    Ok((key, value))
+}
+
+/// What every file is tagged with besides the user's tags.
+#[derive(Clone, Debug)]
+pub struct FileInfo<'a> {
+    /// The title when no tag gives one; the file name without extension.
+    pub title: &'a str,
+    /// When the audio in the file starts.
+    pub started_at: OffsetDateTime,
+    pub git_rev: Option<&'a str>,
+}
+
+/// The `-metadata` entries for a file: each tag under its own name, plus
+/// the fields players show unless a tag sets them: `title` from `project`
+/// or the file name, `artist` from `participants`, `date` and
+/// `creation_time` from the start, and a `comment` naming the rcrd version
+/// and listing every tag. These are ffmpeg's generic names, which each
+/// muxer maps to its container's own (Vorbis comments in Ogg and FLAC,
+/// ID3v2 frames in MP3).
+pub fn ffmpeg_metadata(tags: &Tags, info: &FileInfo) -> Tags {
+    let mut metadata = tags.clone();
+    let title = tags.get("project").map_or(info.title, String::as_str);
+    metadata
+        .entry("title".into())
+        .or_insert_with(|| title.to_string());
+    if let Some(participants) = tags.get("participants") {
+        metadata
+            .entry("artist".into())
+            .or_insert_with(|| participants.clone());
+    }
+    let start = info.started_at;
+    metadata.entry("date".into()).or_insert_with(|| {
+        format!(
+            "{:04}-{:02}-{:02}",
+            start.year(),
+            start.month() as u8,
+            start.day()
+        )
+    });
+    metadata
+        .entry("creation_time".into())
+        .or_insert_with(|| format_rfc3339(start));
+    metadata.entry("comment".into()).or_insert_with(|| {
+        let mut comment = format!("Recorded with rcrd {}", env!("CARGO_PKG_VERSION"));
+        if let Some(rev) = info.git_rev {
+            comment.push_str(&format!(" ({rev})"));
+        }
+        for (key, value) in tags {
+            comment.push_str(&format!("; {key}={value}"));
+        }
+        comment
+    });
+    metadata
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_and_validates_tags() {
+        assert_eq!(
+            parse_tag(" Project = Apollo 11 ").unwrap(),
+            ("project".into(), "Apollo 11".into())
+        );
+        // Only the first `=` separates; ffmpeg splits the same way.
+        assert_eq!(
+            parse_tag("formula=a=b").unwrap(),
+            ("formula".into(), "a=b".into())
+        );
+        assert_eq!(parse_tag("note=one\ntwo").unwrap().1, "one two");
+        for bad in [
+            "project",
+            "=x",
+            "1st=x",
+            "with space=x",
+            "dot.ted=x",
+            "empty=  ",
+        ] {
+            assert!(parse_tag(bad).is_err(), "{bad}");
+        }
+        assert!(parse_tag(&format!("{}=x", "k".repeat(65))).is_err());
+    }
+
+    fn info() -> FileInfo<'static> {
+        FileInfo {
+            title: "rcrd-call-2024-05-06-09-30-00",
+            // 2024-05-06 09:30:00 +02:00
+            started_at: OffsetDateTime::from_unix_timestamp(1_714_980_600)
+                .unwrap()
+                .to_offset(time::UtcOffset::from_hms(2, 0, 0).unwrap()),
+            git_rev: Some("abc1234"),
+        }
+    }
+
+    #[test]
+    fn fills_the_fields_players_show() {
+        let version = env!("CARGO_PKG_VERSION");
+        let bare = ffmpeg_metadata(&Tags::new(), &info());
+        assert_eq!(
+            bare.keys().collect::<Vec<_>>(),
+            ["comment", "creation_time", "date", "title"]
+        );
+        assert_eq!(bare["title"], "rcrd-call-2024-05-06-09-30-00");
+        assert_eq!(bare["date"], "2024-05-06");
+        assert_eq!(bare["creation_time"], "2024-05-06T09:30:00+02:00");
+        assert_eq!(
+            bare["comment"],
+            format!("Recorded with rcrd {version} (abc1234)")
+        );
+
+        let tags: Tags = [
+            ("project".to_string(), "apollo".to_string()),
+            ("participants".to_string(), "Ana, Bo".to_string()),
+        ]
+        .into_iter()
+        .collect();
+        let metadata = ffmpeg_metadata(&tags, &info());
+        assert_eq!(metadata["title"], "apollo");
+        assert_eq!(metadata["artist"], "Ana, Bo");
+        assert_eq!(
+            metadata["comment"],
+            format!("Recorded with rcrd {version} (abc1234); participants=Ana, Bo; project=apollo")
+        );
+        assert_eq!(metadata["project"], "apollo");
+
+        let mut titled = tags.clone();
+        titled.insert("title".into(), "Kickoff".into());
+        titled.insert("comment".into(), "mine".into());
+        let metadata = ffmpeg_metadata(&titled, &info());
+        assert_eq!(
+            (&*metadata["title"], &*metadata["comment"]),
+            ("Kickoff", "mine")
+        );
+    }
+}
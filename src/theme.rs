@@ -0,0 +1,252 @@
+//! TUI colors by role: the built-in themes, the `theme` config value, and
+//! monochrome rendering for `NO_COLOR` and `--no-color`.
+
+use std::str::FromStr;
+
+use anyhow::{Result, anyhow};
+use ratatui::style::{Color, Modifier, Style};
+use rcrd::config::ThemeSetting;
+
+/// Color per semantic role; `None` leaves the terminal's default. Badges
+/// and banners fall back to reverse video when their color is unset.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Theme {
+    pub title: Option<Color>,
+    pub recording: Option<Color>,
+    pub on_air: Option<Color>,
+    pub muted: Option<Color>,
+    pub alert: Option<Color>,
+    pub warning: Option<Color>,
+    pub ok: Option<Color>,
+    pub text: Option<Color>,
+    pub transcript: Option<Color>,
+    pub log: Option<Color>,
+    pub faint: Option<Color>,
+    /// Remote and local speaker with split channels.
+    pub speakers: [Option<Color>; 2],
+    /// Cycled through when a transcript mixes languages.
+    pub languages: [Option<Color>; 4],
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            title: Some(Color::Cyan),
+            recording: Some(Color::Red),
+            on_air: Some(Color::Green),
+            muted: Some(Color::Red),
+            alert: Some(Color::Red),
+            warning: Some(Color::Yellow),
+            ok: Some(Color::Green),
+            text: Some(Color::White),
+            transcript: Some(Color::Gray),
+            log: Some(Color::Gray),
+            faint: Some(Color::DarkGray),
+            speakers: [Some(Color::Cyan), Some(Color::Green)],
+            languages: [
+                Some(Color::Gray),
+                Some(Color::Yellow),
+                Some(Color::Magenta),
+                Some(Color::Blue),
+            ],
+        }
+    }
+}
+
+impl Theme {
+    /// No colors at all; emphasis comes from bold, dim and reverse video.
+    pub fn mono() -> Self {
+        Theme {
+            title: None,
+            recording: None,
+            on_air: None,
+            muted: None,
+            alert: None,
+            warning: None,
+            ok: None,
+            text: None,
+            transcript: None,
+            log: None,
+            faint: None,
+            speakers: [None; 2],
+            languages: [None; 4],
+        }
+    }
+
+    /// Solarized accents, for terminals using the Solarized palette or not.
+    pub fn solarized() -> Self {
+        let base01 = Some(Color::Rgb(0x58, 0x6e, 0x75));
+        let base0 = Some(Color::Rgb(0x83, 0x94, 0x96));
+        let yellow = Some(Color::Rgb(0xb5, 0x89, 0x00));
+        let red = Some(Color::Rgb(0xdc, 0x32, 0x2f));
+        let cyan = Some(Color::Rgb(0x2a, 0xa1, 0x98));
+        let green = Some(Color::Rgb(0x85, 0x99, 0x00));
+        Theme {
+            title: Some(Color::Rgb(0x26, 0x8b, 0xd2)),
+            recording: red,
+            on_air: green,
+            muted: Some(Color::Rgb(0xcb, 0x4b, 0x16)),
+            alert: red,
+            warning: yellow,
+            ok: green,
+            text: Some(Color::Rgb(0x93, 0xa1, 0xa1)),
+            transcript: base0,
+            log: base0,
+            faint: base01,
+            speakers: [cyan, green],
+            languages: [
+                base0,
+                yellow,
+                Some(Color::Rgb(0xd3, 0x36, 0x82)),
+                Some(Color::Rgb(0x6c, 0x71, 0xc4)),
+            ],
+        }
+    }
+
+    /// A built-in theme by name.
+    pub fn named(name: &str) -> Result<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "default" => Ok(Theme::default()),
+            "solarized" => Ok(Theme::solarized()),
+            "mono" | "monochrome" => Ok(Theme::mono()),
+            _ => Err(anyhow!(
+                "unknown theme `{name}` (use default, solarized or mono)"
+            )),
+        }
+    }
+
+    /// The theme described by the config's `theme` value.
+    pub fn from_setting(setting: &ThemeSetting) -> Result<Self> {
+        let roles = match setting {
+            ThemeSetting::Named(name) => return Theme::named(name),
+            ThemeSetting::Custom(roles) => roles,
+        };
+        let mut theme = match roles.get("base") {
+            Some(base) => Theme::named(base)?,
+            None => Theme::default(),
+        };
+        for (role, value) in roles.iter().filter(|(role, _)| *role != "base") {
+            let color = parse_color(value)
+                .map_err(|_| anyhow!("theme: `{value}` is not a color (for `{role}`)"))?;
+            *theme.role_mut(role)? = color;
+        }
+        Ok(theme)
+    }
+
+    fn role_mut(&mut self, role: &str) -> Result<&mut Option<Color>> {
+        Ok(
+            match role.trim().to_lowercase().replace('_', "-").as_str() {
+                "title" => &mut self.title,
+                "recording" => &mut self.recording,
+                "on-air" => &mut self.on_air,
+                "muted" => &mut self.muted,
+                "alert" => &mut self.alert,
+                "warning" => &mut self.warning,
+                "ok" => &mut self.ok,
+                "text" => &mut self.text,
+                "transcript" => &mut self.transcript,
+                "log" => &mut self.log,
+                "faint" => &mut self.faint,
+                "remote" => &mut self.speakers[0],
+                "local" => &mut self.speakers[1],
+                _ => return Err(anyhow!("theme: unknown role `{role}`")),
+            },
+        )
+    }
+
+    /// The configured theme (or the default one), replaced by
+    /// [`mono`](Self::mono) with `--no-color` or a non-empty `NO_COLOR`
+    /// (<https://no-color.org>).
+    pub fn detect(no_color: bool, setting: Option<&ThemeSetting>) -> Result<Self> {
+        let env = std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+        if no_color || env {
+            return Ok(Theme::mono());
+        }
+        setting.map_or_else(|| Ok(Theme::default()), Theme::from_setting)
+    }
+
+    /// Foreground-only style for `color`.
+    pub fn fg(color: Option<Color>) -> Style {
+        match color {
+            Some(color) => Style::default().fg(color),
+            None => Style::default(),
+        }
+    }
+
+    /// `fg` text on a `bg` label, e.g. the mic state.
+    pub fn badge(bg: Option<Color>, fg: Color) -> Style {
+        match bg {
+            Some(bg) => Style::default().bg(bg).fg(fg),
+            None => Style::default().add_modifier(Modifier::REVERSED),
+        }
+    }
+
+    /// A bold banner replacing the title.
+    pub fn banner(bg: Option<Color>, fg: Color) -> Style {
+        Theme::badge(bg, fg).add_modifier(Modifier::BOLD)
+    }
+}
+
+/// A color name (`red`, `light-blue`, `dark gray`), `#rrggbb`, a 256-color
+/// index, or `none`/`default` for the terminal's own color.
+fn parse_color(value: &str) -> Result<Option<Color>> {
+    match value.trim().to_lowercase().as_str() {
+        "none" | "default" => Ok(None),
+        value => Ok(Some(
+            Color::from_str(value).map_err(|_| anyhow!("bad color"))?,
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn no_color_keeps_emphasis_without_colors() {
+        let solarized = ThemeSetting::Named("solarized".into());
+        let theme = Theme::detect(true, Some(&solarized)).unwrap();
+        assert_eq!(theme, Theme::mono());
+        assert_eq!(Theme::fg(theme.recording), Style::default());
+        let badge = Theme::badge(theme.on_air, Color::Black);
+        assert_eq!((badge.fg, badge.bg), (None, None));
+        assert!(badge.add_modifier.contains(Modifier::REVERSED));
+        let banner = Theme::banner(Theme::default().alert, Color::White);
+        assert_eq!(
+            (banner.fg, banner.bg),
+            (Some(Color::White), Some(Color::Red))
+        );
+        assert!(banner.add_modifier.contains(Modifier::BOLD));
+    }
+
+    #[test]
+    fn config_roles_override_a_base_theme() {
+        let setting: ThemeSetting = serde_json::from_str(
+            r##"{ "base": "solarized", "title": " Light-Blue ", "on_air": "#00FF00", "log": "none", "muted": "203" }"##,
+        )
+        .unwrap();
+        let theme = Theme::from_setting(&setting).unwrap();
+        assert_eq!(theme.title, Some(Color::LightBlue));
+        assert_eq!(theme.on_air, Some(Color::Rgb(0, 255, 0)));
+        assert_eq!(theme.log, None);
+        assert_eq!(theme.muted, Some(Color::Indexed(203)));
+        assert_eq!(theme.faint, Theme::solarized().faint);
+    }
+
+    #[test]
+    fn bad_theme_values_are_errors() {
+        let named = |name: &str| Theme::from_setting(&ThemeSetting::Named(name.into()));
+        assert_eq!(named("Mono").unwrap(), Theme::mono());
+        assert!(named("neon").is_err());
+        let custom = |role: &str, color: &str| {
+            let roles = [(role.to_string(), color.to_string())]
+                .into_iter()
+                .collect();
+            Theme::from_setting(&ThemeSetting::Custom(roles))
+        };
+        assert!(custom("title", "blurple").is_err());
+        assert!(custom("title", "#12345").is_err());
+        assert!(custom("background", "red").is_err());
+        assert!(custom("base", "neon").is_err());
+    }
+}
@@ -0,0 +1,1011 @@
+use std::fs::File;
+use std::io::{BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicI64, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+use anyhow::{Context, Result, anyhow};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::error::ErrorKind;
+use crate::ffmpeg::{PCM_TAP_RATE, build_decode_command};
+use crate::sync::lock;
+
+pub mod export;
+
+/// Sample rate whisper.cpp expects, which ffmpeg's PCM tap delivers.
+const WHISPER_RATE: usize = PCM_TAP_RATE as usize;
+/// Default step and length of the audio windows handed to whisper.
+pub const DEFAULT_STEP_MS: u32 = 8_000;
+pub const DEFAULT_LENGTH_MS: u32 = 8_000;
+/// whisper pads or cuts every input to 30 s, so longer windows only cost time.
+const MAX_LENGTH_MS: u32 = 30_000;
+
+/// Language asking whisper to detect it for every window
+/// (`--language-per-segment`); each segment is tagged with what it found.
+pub const AUTO_LANGUAGE: &str = "auto";
+
+/// Longest initial prompt handed to whisper, in characters. whisper reads
+/// at most 224 tokens of prompt (half its text context) and drops the
+/// rest; at roughly four characters a token this stays within it.
+pub const MAX_PROMPT_CHARS: usize = 800;
+
+/// Cleans up an initial prompt (`--prompt`, or a glossary read from
+/// `--prompt-file`): runs of whitespace, line breaks included, become single
+/// spaces, and anything past [`MAX_PROMPT_CHARS`] is cut at the last word
+/// that fits. `None` when nothing is left.
+pub fn prepare_prompt(prompt: &str) -> Option<String> {
+    let mut out = String::new();
+    for word in prompt.split_whitespace() {
+        let len = out.chars().count() + word.chars().count() + usize::from(!out.is_empty());
+        if len > MAX_PROMPT_CHARS {
+            break;
+        }
+        if !out.is_empty() {
+            out.push(' ');
+        }
+        out.push_str(word);
+    }
+    (!out.is_empty()).then_some(out)
+}
+
+/// How the live audio is cut into whisper runs: every `step_ms` of new audio,
+/// the last `length_ms` are transcribed. A length above the step re-reads the
+/// tail of the previous window as context, which helps accuracy at word
+/// boundaries; segments starting in that context were already emitted and are
+/// dropped. Smaller steps mean lower latency but more whisper runs, so more
+/// CPU/GPU load.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Windowing {
+    step_ms: u32,
+    length_ms: u32,
+}
+
+impl Default for Windowing {
+    fn default() -> Self {
+        Windowing {
+            step_ms: DEFAULT_STEP_MS,
+            length_ms: DEFAULT_LENGTH_MS,
+        }
+    }
+}
+
+impl Windowing {
+    pub fn new(step_ms: u32, length_ms: u32) -> Result<Self> {
+        if step_ms < 500 {
+            return Err(anyhow!("transcription step must be at least 500 ms"));
+        }
+        if length_ms < step_ms {
+            return Err(anyhow!(
+                "transcription length ({length_ms} ms) must be at least the step ({step_ms} ms)"
+            ));
+        }
+        if length_ms > MAX_LENGTH_MS {
+            return Err(anyhow!(
+                "transcription length must be at most {MAX_LENGTH_MS} ms"
+            ));
+        }
+        Ok(Windowing { step_ms, length_ms })
+    }
+
+    pub fn step_ms(&self) -> u32 {
+        self.step_ms
+    }
+
+    pub fn length_ms(&self) -> u32 {
+        self.length_ms
+    }
+}
+
+/// Whether whisper has produced anything yet, shown in the transcript pane.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TranscriberStatus {
+    /// No window has been transcribed yet (the model loads on the first one).
+    Loading,
+    /// The last window was transcribed.
+    Ready,
+    /// The last window failed, e.g. a corrupt model or an unusable backend.
+    Error(String),
+    /// The last window was quieter than the VAD threshold and not transcribed.
+    Silence,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TransSegment {
+    pub start_ms: i64,
+    pub end_ms: i64,
+    pub text: String,
+    /// Mean token probability reported by whisper (0..1), when available.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub confidence: Option<f32>,
+    /// Below `--min-confidence`: shown live but left out of saved transcripts.
+    #[serde(skip)]
+    pub low_confidence: bool,
+    /// Speaker label with split channels; the text also carries it as `[label]`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub speaker: Option<String>,
+    /// Language whisper was asked to transcribe this segment in.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lang: Option<String>,
+    /// A note typed during the recording (`a`) rather than transcribed speech.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub manual: bool,
+}
+
+impl TransSegment {
+    /// A transcribed segment with no confidence, speaker or language.
+    pub fn new(start_ms: i64, end_ms: i64, text: impl Into<String>) -> Self {
+        TransSegment {
+            start_ms,
+            end_ms,
+            text: text.into(),
+            confidence: None,
+            low_confidence: false,
+            speaker: None,
+            lang: None,
+            manual: false,
+        }
+    }
+}
+
+struct Chunk {
+    start_ms: i64,
+    /// Segments starting before this were emitted by the previous window.
+    new_from_ms: i64,
+    samples: Vec<i16>,
+    speaker: Option<String>,
+}
+
+/// Reads 16 kHz s16le PCM from `input` (ffmpeg's stdout, live or decoding
+/// a file), mono, or stereo with `speakers` set, and runs whisper-cli over windows cut as `windowing`
+/// says while `active` is set. What is left over when the stream ends is transcribed as a final,
+/// shorter window. The pipe is always drained so ffmpeg never
+/// blocks, even when transcription is paused. Finished segments are also
+/// sent to every channel in `subscribers`; closed channels are dropped.
+///
+/// `status` reflects the outcome of the latest whisper run.
+///
+/// With `vad_threshold` set (dBFS), windows whose new audio has a lower RMS
+/// level are skipped instead of transcribed: silence costs a whole whisper
+/// run and tends to come back as hallucinated text.
+///
+/// Segments whose confidence is below `min_confidence` are kept in
+/// `transcript` flagged `low_confidence` but are not sent to subscribers.
+///
+/// If the whisper worker dies (a panic), it is started again up to
+/// [`MAX_RESTARTS`] times while `stop` is unset; each restart is noted in
+/// `logs`.
+///
+/// `prompt` is passed to every whisper run as its initial prompt, biasing
+/// recognition toward the names and terms in it; see [`prepare_prompt`].
+///
+/// With `speakers` set, the left and right channels (remote and mic when
+/// recording with split channels) are transcribed separately, each segment is
+/// prefixed with its `[label]`, and the results are merged by timestamp.
+#[allow(clippy::too_many_arguments)]
+pub fn start_transcriber<R: Read + Send + 'static>(
+    mut input: R,
+    model: PathBuf,
+    language: Arc<Mutex<String>>,
+    transcript: Arc<Mutex<Vec<TransSegment>>>,
+    active: Arc<AtomicBool>,
+    stop: Arc<AtomicBool>,
+    backend: String,
+    base_offset_ms: Arc<AtomicI64>,
+    reset: Arc<AtomicBool>,
+    threads: usize,
+    subscribers: Arc<Mutex<Vec<Sender<TransSegment>>>>,
+    speakers: Option<[String; 2]>,
+    min_confidence: Option<f32>,
+    status: Arc<Mutex<TranscriberStatus>>,
+    windowing: Windowing,
+    vad_threshold: Option<f32>,
+    prompt: Option<String>,
+    logs: Arc<Mutex<Vec<String>>>,
+) -> JoinHandle<()> {
+    tracing::info!(
+        "transcriber starting: model={} backend={backend} threads={threads} step={}ms length={}ms",
+        model.display(),
+        windowing.step_ms,
+        windowing.length_ms
+    );
+    thread::spawn(move || {
+        let worker = Worker {
+            model,
+            backend,
+            language,
+            prompt,
+            threads,
+            min_confidence,
+            status: status.clone(),
+            transcript,
+            subscribers,
+        };
+        let mut whisper = Supervisor::new(worker, logs, stop.clone());
+        let vad_status = status;
+
+        let ms_to_samples = |ms: u32| WHISPER_RATE * ms as usize / 1000;
+        let step_samples = ms_to_samples(windowing.step_ms);
+        let context_samples = ms_to_samples(windowing.length_ms - windowing.step_ms);
+        // One lane for the mono tap, or one per channel when labelling speakers.
+        let lanes = if speakers.is_some() { 2 } else { 1 };
+        let frame_bytes = lanes * 2;
+        let mut raw = vec![0u8; frame_bytes * 4096];
+        let mut pending: Vec<u8> = Vec::new();
+        let capacity = step_samples + context_samples;
+        let mut samples: Vec<Vec<i16>> = vec![Vec::with_capacity(capacity); lanes];
+        // Time of the first buffered sample, and how many of them are context.
+        let mut window_start_ms: i64 = 0;
+        let mut context = 0;
+
+        loop {
+            if stop.load(Ordering::Relaxed) {
+                break;
+            }
+            let n = match input.read(&mut raw) {
+                Ok(0) | Err(_) => break,
+                Ok(n) => n,
+            };
+            if reset.swap(false, Ordering::Relaxed) {
+                samples.iter_mut().for_each(Vec::clear);
+                pending.clear();
+                window_start_ms = 0;
+                context = 0;
+            }
+            if !active.load(Ordering::Relaxed) {
+                continue;
+            }
+
+            // Deinterleave whole frames into the lanes; a partial one waits.
+            pending.extend_from_slice(&raw[..n]);
+            let usable = pending.len() / frame_bytes * frame_bytes;
+            for frame in pending[..usable].chunks_exact(frame_bytes) {
+                for (lane, b) in samples.iter_mut().zip(frame.chunks_exact(2)) {
+                    lane.push(i16::from_le_bytes([b[0], b[1]]));
+                }
+            }
+            pending.drain(..usable);
+
+            if samples[0].len() - context >= step_samples {
+                let start_ms = base_offset_ms.load(Ordering::Relaxed) + window_start_ms;
+                let new_from_ms = start_ms + samples_to_ms(context);
+                let voiced = voiced_lanes(&samples, context, vad_threshold);
+                if !voiced.contains(&true) {
+                    *lock(&vad_status) = TranscriberStatus::Silence;
+                }
+                for (i, lane) in samples.iter_mut().enumerate() {
+                    if !voiced[i] {
+                        continue;
+                    }
+                    let chunk = Chunk {
+                        start_ms,
+                        new_from_ms,
+                        samples: lane.clone(),
+                        speaker: speakers.as_ref().map(|s| s[i].clone()),
+                    };
+                    if !whisper.send(chunk) {
+                        break;
+                    }
+                }
+                // Keep the tail as the next window's context.
+                let drop = samples[0].len().saturating_sub(context_samples);
+                samples.iter_mut().for_each(|lane| {
+                    lane.drain(..drop);
+                });
+                window_start_ms += samples_to_ms(drop);
+                context = samples[0].len();
+            }
+        }
+        if active.load(Ordering::Relaxed) && samples[0].len() > context {
+            let start_ms = base_offset_ms.load(Ordering::Relaxed) + window_start_ms;
+            let new_from_ms = start_ms + samples_to_ms(context);
+            let voiced = voiced_lanes(&samples, context, vad_threshold);
+            if !voiced.contains(&true) {
+                *lock(&vad_status) = TranscriberStatus::Silence;
+            }
+            for (i, lane) in samples.into_iter().enumerate() {
+                if !voiced[i] {
+                    continue;
+                }
+                whisper.send(Chunk {
+                    start_ms,
+                    new_from_ms,
+                    samples: lane,
+                    speaker: speakers.as_ref().map(|s| s[i].clone()),
+                });
+            }
+        }
+        whisper.finish();
+        tracing::info!("transcriber finished");
+    })
+}
+
+/// Times the whisper worker is restarted after dying before transcription
+/// gives up, so a crash on every window doesn't loop forever.
+const MAX_RESTARTS: u32 = 3;
+
+/// What the whisper worker needs, kept so it can be started again.
+#[derive(Clone)]
+struct Worker {
+    model: PathBuf,
+    backend: String,
+    language: Arc<Mutex<String>>,
+    prompt: Option<String>,
+    threads: usize,
+    min_confidence: Option<f32>,
+    status: Arc<Mutex<TranscriberStatus>>,
+    transcript: Arc<Mutex<Vec<TransSegment>>>,
+    subscribers: Arc<Mutex<Vec<Sender<TransSegment>>>>,
+}
+
+impl Worker {
+    /// Runs whisper over every chunk sent to the returned channel.
+    fn spawn(self) -> (Sender<Chunk>, JoinHandle<()>) {
+        let (tx, rx) = mpsc::channel::<Chunk>();
+        let thread = thread::spawn(move || {
+            for chunk in rx {
+                self.transcribe(&chunk);
+            }
+        });
+        (tx, thread)
+    }
+
+    fn transcribe(&self, chunk: &Chunk) {
+        let lang = lock(&self.language).clone();
+        let mut segments = match run_whisper(
+            &self.model,
+            &self.backend,
+            &lang,
+            self.prompt.as_deref(),
+            self.threads,
+            chunk,
+        ) {
+            Ok(mut segments) => {
+                tracing::debug!(
+                    "transcribed window at {}ms: {} segments",
+                    chunk.start_ms,
+                    segments.len()
+                );
+                *lock(&self.status) = TranscriberStatus::Ready;
+                segments.retain(|seg| seg.start_ms >= chunk.new_from_ms);
+                segments
+            }
+            Err(err) => {
+                tracing::error!("whisper failed on window at {}ms: {err:#}", chunk.start_ms);
+                *lock(&self.status) = TranscriberStatus::Error(format!("{err:#}"));
+                return;
+            }
+        };
+        for seg in &mut segments {
+            if let Some(label) = &chunk.speaker {
+                seg.text = format!("[{label}] {}", seg.text);
+            }
+            seg.speaker = chunk.speaker.clone();
+            // With `auto` the segment keeps the language whisper detected.
+            if lang != AUTO_LANGUAGE {
+                seg.lang = Some(lang.clone());
+            }
+            seg.low_confidence = self
+                .min_confidence
+                .zip(seg.confidence)
+                .is_some_and(|(min, conf)| conf < min);
+        }
+        lock(&self.subscribers).retain(|tx| {
+            segments
+                .iter()
+                .filter(|seg| !seg.low_confidence)
+                .all(|seg| tx.send(seg.clone()).is_ok())
+        });
+        let mut t = lock(&self.transcript);
+        t.extend(segments);
+        if chunk.speaker.is_some() {
+            t.sort_by_key(|seg| seg.start_ms);
+        }
+    }
+}
+
+/// Hands chunks to the whisper worker, starting a fresh one (which loads
+/// the model again) when it has died, up to [`MAX_RESTARTS`] times.
+struct Supervisor {
+    worker: Worker,
+    tx: Option<Sender<Chunk>>,
+    thread: Option<JoinHandle<()>>,
+    restarts: u32,
+    logs: Arc<Mutex<Vec<String>>>,
+    stop: Arc<AtomicBool>,
+}
+
+impl Supervisor {
+    fn new(worker: Worker, logs: Arc<Mutex<Vec<String>>>, stop: Arc<AtomicBool>) -> Self {
+        let (tx, thread) = worker.clone().spawn();
+        Self {
+            worker,
+            tx: Some(tx),
+            thread: Some(thread),
+            restarts: 0,
+            logs,
+            stop,
+        }
+    }
+
+    /// Queues `chunk`; `false` once the worker is gone for good.
+    fn send(&mut self, mut chunk: Chunk) -> bool {
+        loop {
+            let Some(tx) = &self.tx else {
+                return false;
+            };
+            match tx.send(chunk) {
+                Ok(()) => return true,
+                Err(mpsc::SendError(unsent)) => chunk = unsent,
+            }
+            if !self.restart() {
+                return false;
+            }
+        }
+    }
+
+    /// Replaces the dead worker unless recording is stopping or it has died
+    /// too often.
+    fn restart(&mut self) -> bool {
+        self.tx = None;
+        let reason = match self.thread.take().map(JoinHandle::join) {
+            Some(Err(panic)) => panic
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| panic.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "panicked".into()),
+            _ => "exited".into(),
+        };
+        if self.stop.load(Ordering::Relaxed) {
+            return false;
+        }
+        tracing::error!("whisper worker died: {reason}");
+        if self.restarts >= MAX_RESTARTS {
+            let msg = format!(
+                "Transcriber died ({reason}) after {MAX_RESTARTS} restarts; transcription stopped"
+            );
+            *lock(&self.worker.status) = TranscriberStatus::Error(msg.clone());
+            lock(&self.logs).push(msg);
+            return false;
+        }
+        self.restarts += 1;
+        *lock(&self.worker.status) = TranscriberStatus::Loading;
+        let (tx, thread) = self.worker.clone().spawn();
+        self.tx = Some(tx);
+        self.thread = Some(thread);
+        lock(&self.logs).push(format!(
+            "Transcriber restarted after it died ({reason}), {} of {MAX_RESTARTS}",
+            self.restarts
+        ));
+        true
+    }
+
+    /// Waits for the queued chunks to be transcribed.
+    fn finish(mut self) {
+        self.tx = None;
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Transcribes an existing audio file: ffmpeg decodes it to the same PCM
+/// format as the live tap and it goes through [`start_transcriber`].
+pub fn transcribe_file(
+    file: &Path,
+    model: PathBuf,
+    language: &str,
+    backend: String,
+    threads: usize,
+    prompt: Option<String>,
+) -> Result<Vec<TransSegment>> {
+    let mut child = build_decode_command(file)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .context("failed to spawn ffmpeg")
+        .context(ErrorKind::Ffmpeg)?;
+    let stdout = child.stdout.take().context("ffmpeg stdout not captured")?;
+    let transcript = Arc::new(Mutex::new(Vec::new()));
+    let status = Arc::new(Mutex::new(TranscriberStatus::Loading));
+    let thread = start_transcriber(
+        stdout,
+        model,
+        Arc::new(Mutex::new(language.to_string())),
+        transcript.clone(),
+        Arc::new(AtomicBool::new(true)),
+        Arc::new(AtomicBool::new(false)),
+        backend,
+        Arc::new(AtomicI64::new(0)),
+        Arc::new(AtomicBool::new(false)),
+        threads,
+        Arc::new(Mutex::new(Vec::new())),
+        None,
+        None,
+        status.clone(),
+        Windowing::default(),
+        None,
+        prompt,
+        Arc::new(Mutex::new(Vec::new())),
+    );
+    let _ = thread.join();
+    let exit = child.wait()?;
+    if !exit.success() {
+        return Err(
+            anyhow!("ffmpeg could not decode {} ({exit})", file.display())
+                .context(ErrorKind::Ffmpeg),
+        );
+    }
+    let segments = std::mem::take(&mut *lock(&transcript));
+    if segments.is_empty()
+        && let TranscriberStatus::Error(err) = &*lock(&status)
+    {
+        return Err(anyhow!("{err}").context(ErrorKind::Transcriber));
+    }
+    Ok(segments)
+}
+
+/// The backend to actually use for `requested`: `vulkan` becomes `openblas`
+/// when no GPU is usable, or is an error with `strict`.
+pub fn resolve_backend(requested: &str, strict: bool) -> Result<String> {
+    if requested != "vulkan" || vulkan_available() {
+        return Ok(requested.to_string());
+    }
+    if strict {
+        return Err(anyhow!("vulkan backend requested but no usable GPU found"));
+    }
+    Ok("openblas".into())
+}
+
+/// Whether a Vulkan-capable GPU looks usable for whisper's `vulkan` backend.
+/// Uses `vulkaninfo` when installed (a CPU-only `llvmpipe` device doesn't
+/// count) and otherwise falls back to checking for a DRM render node.
+pub fn vulkan_available() -> bool {
+    if let Ok(output) = Command::new("vulkaninfo").arg("--summary").output() {
+        let summary = String::from_utf8_lossy(&output.stdout);
+        return output.status.success()
+            && summary.lines().any(|l| {
+                l.contains("deviceType")
+                    && (l.contains("DISCRETE_GPU") || l.contains("INTEGRATED_GPU"))
+            });
+    }
+    std::fs::read_dir("/dev/dri").is_ok_and(|entries| {
+        entries
+            .flatten()
+            .any(|e| e.file_name().to_string_lossy().starts_with("renderD"))
+    })
+}
+
+/// Joins consecutive segments separated by at most `max_gap_ms` of silence
+/// into one, spanning both and concatenating their text. Segments from
+/// different speakers (`[label]` prefixes) are never merged.
+pub fn merge_segments(segments: &[TransSegment], max_gap_ms: i64) -> Vec<TransSegment> {
+    let mut merged: Vec<TransSegment> = Vec::with_capacity(segments.len());
+    for seg in segments {
+        if let Some(last) = merged.last_mut()
+            && seg.start_ms - last.end_ms <= max_gap_ms
+            && !last.manual
+            && !seg.manual
+            && speaker_label(&last.text) == speaker_label(&seg.text)
+        {
+            let text = match speaker_label(&seg.text) {
+                Some(label) => seg.text[label.len()..].trim_start(),
+                None => seg.text.as_str(),
+            };
+            last.text = format!("{} {}", last.text, text);
+            last.end_ms = last.end_ms.max(seg.end_ms);
+            last.confidence = match (last.confidence, seg.confidence) {
+                (Some(a), Some(b)) => Some(a.min(b)),
+                (a, b) => a.or(b),
+            };
+            last.low_confidence |= seg.low_confidence;
+            continue;
+        }
+        merged.push(seg.clone());
+    }
+    merged
+}
+
+/// Whether each lane's audio after the first `context` samples is loud enough
+/// to be worth transcribing.
+fn voiced_lanes(lanes: &[Vec<i16>], context: usize, threshold: Option<f32>) -> Vec<bool> {
+    lanes
+        .iter()
+        .map(|lane| threshold.is_none_or(|db| rms_dbfs(&lane[context.min(lane.len())..]) >= db))
+        .collect()
+}
+
+/// RMS level of `samples` in dBFS; silence is `-inf`.
+fn rms_dbfs(samples: &[i16]) -> f32 {
+    if samples.is_empty() {
+        return f32::NEG_INFINITY;
+    }
+    let sum: f64 = samples.iter().map(|&s| (s as f64).powi(2)).sum();
+    let rms = (sum / samples.len() as f64).sqrt() / i16::MAX as f64;
+    20.0 * rms.log10() as f32
+}
+
+fn samples_to_ms(samples: usize) -> i64 {
+    (samples * 1000 / WHISPER_RATE) as i64
+}
+
+/// The `[label]` prefix added for split-channel transcripts, if any.
+fn speaker_label(text: &str) -> Option<&str> {
+    let end = text.find("] ")?;
+    text.starts_with('[').then(|| &text[..=end])
+}
+
+fn run_whisper(
+    model: &Path,
+    backend: &str,
+    lang: &str,
+    prompt: Option<&str>,
+    threads: usize,
+    chunk: &Chunk,
+) -> Result<Vec<TransSegment>> {
+    let stem = std::env::temp_dir().join(format!("rcrd-chunk-{}", std::process::id()));
+    let wav = stem.with_extension("wav");
+    let json = stem.with_extension("json");
+    write_wav(&wav, &chunk.samples)?;
+
+    // `-ojf` writes per-token probabilities to `<stem>.json`, used for confidence.
+    let mut cmd = Command::new("whisper-cli");
+    cmd.arg("-m")
+        .arg(model)
+        .args(["-l", lang, "-t", &threads.to_string(), "-np", "-ojf", "-of"])
+        .arg(&stem)
+        .arg("-f")
+        .arg(&wav);
+    if let Some(prompt) = prompt {
+        cmd.args(["--prompt", prompt]);
+    }
+    if backend != "vulkan" {
+        cmd.arg("-ng");
+    }
+    let output = cmd.output().context("failed to run whisper-cli")?;
+    let _ = std::fs::remove_file(&wav);
+    let full = std::fs::read_to_string(&json).ok();
+    let _ = std::fs::remove_file(&json);
+    if !output.status.success() {
+        // whisper-cli logs its progress on stderr too; the reason is last.
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(match stderr.lines().rev().find(|l| !l.trim().is_empty()) {
+            Some(reason) => anyhow!(
+                "whisper-cli exited with {}: {}",
+                output.status,
+                reason.trim()
+            ),
+            None => anyhow!("whisper-cli exited with {}", output.status),
+        });
+    }
+
+    match full.map(|data| parse_whisper_json(&data, chunk.start_ms)) {
+        Some(Ok(segments)) => Ok(segments),
+        _ => {
+            let mut segments =
+                parse_whisper_stdout(&String::from_utf8_lossy(&output.stdout), chunk.start_ms)?;
+            let detected = detected_language(&String::from_utf8_lossy(&output.stderr));
+            for seg in &mut segments {
+                seg.lang.clone_from(&detected);
+            }
+            Ok(segments)
+        }
+    }
+}
+
+/// The language whisper-cli logs having detected with `-l auto`:
+/// `whisper_full_with_state: auto-detected language: fr (p = 0.97)`.
+fn detected_language(stderr: &str) -> Option<String> {
+    stderr.lines().find_map(|line| {
+        let (_, rest) = line.split_once("auto-detected language:")?;
+        let lang = rest.split_whitespace().next()?;
+        Some(lang.to_string())
+    })
+}
+
+/// Parses `[hh:mm:ss.mmm --> hh:mm:ss.mmm] text` lines; no confidence is available.
+fn parse_whisper_stdout(stdout: &str, offset_ms: i64) -> Result<Vec<TransSegment>> {
+    let line_re =
+        Regex::new(r"^\[(\d+):(\d+):(\d+)\.(\d+) --> (\d+):(\d+):(\d+)\.(\d+)\]\s*(.*)$")?;
+    let mut segments = Vec::new();
+    for line in stdout.lines() {
+        let Some(caps) = line_re.captures(line.trim()) else {
+            continue;
+        };
+        let text = caps[9].trim();
+        if text.is_empty() {
+            continue;
+        }
+        let ms = |i: usize| -> i64 {
+            let h: i64 = caps[i].parse().unwrap_or(0);
+            let m: i64 = caps[i + 1].parse().unwrap_or(0);
+            let s: i64 = caps[i + 2].parse().unwrap_or(0);
+            let frac: i64 = caps[i + 3].parse().unwrap_or(0);
+            ((h * 60 + m) * 60 + s) * 1000 + frac
+        };
+        segments.push(TransSegment::new(
+            offset_ms + ms(1),
+            offset_ms + ms(5),
+            text,
+        ));
+    }
+    Ok(segments)
+}
+
+#[derive(Deserialize)]
+struct WhisperJson {
+    /// Holds the detected language with `-l auto`.
+    #[serde(default)]
+    result: Option<WhisperJsonResult>,
+    transcription: Vec<WhisperJsonSegment>,
+}
+
+#[derive(Deserialize)]
+struct WhisperJsonResult {
+    language: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct WhisperJsonSegment {
+    offsets: WhisperJsonOffsets,
+    text: String,
+    #[serde(default)]
+    tokens: Vec<WhisperJsonToken>,
+}
+
+#[derive(Deserialize)]
+struct WhisperJsonOffsets {
+    from: i64,
+    to: i64,
+}
+
+#[derive(Deserialize)]
+struct WhisperJsonToken {
+    text: String,
+    p: f32,
+}
+
+/// Parses whisper-cli's `-ojf` output, averaging the probability of the
+/// non-special tokens (`[_BEG_]`, `[_TT_…]`) of each segment. Segments are
+/// tagged with the language whisper reports transcribing in.
+fn parse_whisper_json(data: &str, offset_ms: i64) -> Result<Vec<TransSegment>> {
+    let parsed: WhisperJson = serde_json::from_str(data)?;
+    let lang = parsed.result.and_then(|result| result.language);
+    let segments = parsed
+        .transcription
+        .into_iter()
+        .filter(|seg| !seg.text.trim().is_empty())
+        .map(|seg| {
+            let probs: Vec<f32> = seg
+                .tokens
+                .iter()
+                .filter(|tok| !tok.text.starts_with("[_"))
+                .map(|tok| tok.p)
+                .collect();
+            let confidence =
+                (!probs.is_empty()).then(|| probs.iter().sum::<f32>() / probs.len() as f32);
+            TransSegment {
+                confidence,
+                lang: lang.clone(),
+                ..TransSegment::new(
+                    offset_ms + seg.offsets.from,
+                    offset_ms + seg.offsets.to,
+                    seg.text.trim(),
+                )
+            }
+        })
+        .collect();
+    Ok(segments)
+}
+
+fn write_wav(path: &Path, samples: &[i16]) -> Result<()> {
+    let mut w = BufWriter::new(File::create(path)?);
+    let data_len = (samples.len() * 2) as u32;
+    let rate = WHISPER_RATE as u32;
+    w.write_all(b"RIFF")?;
+    w.write_all(&(36 + data_len).to_le_bytes())?;
+    w.write_all(b"WAVEfmt ")?;
+    w.write_all(&16u32.to_le_bytes())?;
+    w.write_all(&1u16.to_le_bytes())?; // PCM
+    w.write_all(&1u16.to_le_bytes())?; // mono
+    w.write_all(&rate.to_le_bytes())?;
+    w.write_all(&(rate * 2).to_le_bytes())?;
+    w.write_all(&2u16.to_le_bytes())?;
+    w.write_all(&16u16.to_le_bytes())?;
+    w.write_all(b"data")?;
+    w.write_all(&data_len.to_le_bytes())?;
+    for s in samples {
+        w.write_all(&s.to_le_bytes())?;
+    }
+    w.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dead_worker_is_restarted_a_few_times() {
+        let worker = Worker {
+            model: PathBuf::from("missing.bin"),
+            backend: "cpu".into(),
+            language: Arc::new(Mutex::new("en".into())),
+            prompt: None,
+            threads: 1,
+            min_confidence: None,
+            status: Arc::new(Mutex::new(TranscriberStatus::Ready)),
+            transcript: Arc::new(Mutex::new(Vec::new())),
+            subscribers: Arc::new(Mutex::new(Vec::new())),
+        };
+        let logs = Arc::new(Mutex::new(Vec::new()));
+        let mut whisper = Supervisor::new(worker, logs.clone(), Arc::new(AtomicBool::new(false)));
+        let chunk = || Chunk {
+            start_ms: 0,
+            new_from_ms: 0,
+            samples: Vec::new(),
+            speaker: None,
+        };
+        let kill = |whisper: &mut Supervisor| {
+            let (tx, _) = mpsc::channel();
+            whisper.tx = Some(tx);
+            whisper.thread = Some(thread::spawn(|| panic!("boom")));
+        };
+        kill(&mut whisper);
+        assert!(whisper.send(chunk()));
+        assert_eq!(
+            lock(&logs).as_slice(),
+            ["Transcriber restarted after it died (boom), 1 of 3"]
+        );
+        whisper.restarts = MAX_RESTARTS;
+        kill(&mut whisper);
+        assert!(!whisper.send(chunk()));
+        assert!(lock(&logs)[1].contains("after 3 restarts; transcription stopped"));
+        assert!(matches!(
+            &*lock(&whisper.worker.status),
+            TranscriberStatus::Error(_)
+        ));
+        whisper.finish();
+    }
+
+    #[test]
+    fn prompts_are_flattened_and_cut_at_a_word() {
+        assert_eq!(
+            prepare_prompt("  Kubernetes,\n  Ana Lima;\tOKR ").as_deref(),
+            Some("Kubernetes, Ana Lima; OKR")
+        );
+        assert_eq!(prepare_prompt(" \n "), None);
+        let glossary = "terminology ".repeat(100);
+        let cut = prepare_prompt(&glossary).unwrap();
+        assert!(cut.len() <= MAX_PROMPT_CHARS && cut.len() > MAX_PROMPT_CHARS - 12);
+        assert!(cut.ends_with("terminology"));
+    }
+
+    #[test]
+    fn vad_skips_lanes_below_the_threshold() {
+        let tone: Vec<i16> = (0..1600)
+            .map(|i| if i % 2 == 0 { 3277 } else { -3277 })
+            .collect();
+        assert!((rms_dbfs(&tone) + 20.0).abs() < 0.01);
+        assert_eq!(rms_dbfs(&[0; 160]), f32::NEG_INFINITY);
+
+        // Loud context followed by silence is still silence.
+        let mut left = tone.clone();
+        left.extend([0; 1600]);
+        let right = [vec![0; 1600], tone].concat();
+        let lanes = [left, right];
+        assert_eq!(voiced_lanes(&lanes, 1600, Some(-40.0)), [false, true]);
+        assert_eq!(voiced_lanes(&lanes, 0, Some(-40.0)), [true, true]);
+        assert_eq!(voiced_lanes(&lanes, 1600, None), [true, true]);
+    }
+
+    #[test]
+    fn windowing_needs_a_length_of_at_least_the_step() {
+        assert_eq!(Windowing::new(8_000, 8_000).unwrap(), Windowing::default());
+        let w = Windowing::new(2_000, 6_000).unwrap();
+        assert_eq!((w.step_ms(), w.length_ms()), (2_000, 6_000));
+        assert!(Windowing::new(4_000, 3_000).is_err());
+        assert!(Windowing::new(100, 8_000).is_err());
+        assert!(Windowing::new(8_000, 60_000).is_err());
+    }
+
+    #[test]
+    fn merges_close_segments() {
+        let segs = [
+            TransSegment::new(0, 1_000, "So"),
+            TransSegment::new(1_200, 2_000, "about that."),
+            TransSegment::new(5_000, 6_000, "Next item."),
+        ];
+        let merged = merge_segments(&segs, 500);
+        assert_eq!(merged.len(), 2);
+        assert_eq!((merged[0].start_ms, merged[0].end_ms), (0, 2_000));
+        assert_eq!(merged[0].text, "So about that.");
+        assert_eq!(merged[1].text, "Next item.");
+    }
+
+    #[test]
+    fn keeps_speakers_apart() {
+        let segs = [
+            TransSegment::new(0, 1_000, "[them] Hi"),
+            TransSegment::new(1_000, 2_000, "[them] there"),
+            TransSegment::new(2_000, 3_000, "[me] Hello"),
+        ];
+        let merged = merge_segments(&segs, 500);
+        assert_eq!(merged.len(), 2);
+        assert_eq!(merged[0].text, "[them] Hi there");
+        assert_eq!(merged[1].text, "[me] Hello");
+    }
+
+    #[test]
+    fn keeps_typed_notes_apart() {
+        let note = TransSegment {
+            manual: true,
+            ..TransSegment::new(1_100, 1_100, "[decision made]")
+        };
+        let segs = [
+            TransSegment::new(0, 1_000, "So"),
+            note,
+            TransSegment::new(1_200, 2_000, "agreed."),
+        ];
+        let merged = merge_segments(&segs, 500);
+        assert_eq!(merged.len(), 3);
+        assert!(merged[1].manual);
+        let json = serde_json::to_string(&merged[1]).unwrap();
+        assert!(json.contains(r#""manual":true"#), "{json}");
+        assert!(
+            !serde_json::to_string(&merged[0])
+                .unwrap()
+                .contains("manual")
+        );
+    }
+
+    #[test]
+    fn parses_stdout_lines() {
+        let out =
+            "\n[00:00:01.500 --> 00:00:03.000]   Hello there.\n[00:00:03.000 --> 00:00:04.000]\n";
+        let segs = parse_whisper_stdout(out, 8_000).unwrap();
+        assert_eq!(segs.len(), 1);
+        assert_eq!((segs[0].start_ms, segs[0].end_ms), (9_500, 11_000));
+        assert_eq!(segs[0].text, "Hello there.");
+        assert_eq!(segs[0].confidence, None);
+    }
+
+    #[test]
+    fn parses_full_json_confidence() {
+        let json = r#"{"transcription": [
+            {"timestamps": {"from": "00:00:00,000", "to": "00:00:02,000"},
+             "offsets": {"from": 0, "to": 2000}, "text": " Hi all",
+             "tokens": [{"text": "[_BEG_]", "p": 0.1}, {"text": " Hi", "p": 0.9},
+                        {"text": " all", "p": 0.7}]},
+            {"offsets": {"from": 2000, "to": 2500}, "text": " ", "tokens": []}
+        ]}"#;
+        let segs = parse_whisper_json(json, 1_000).unwrap();
+        assert_eq!(segs.len(), 1);
+        assert_eq!((segs[0].start_ms, segs[0].end_ms), (1_000, 3_000));
+        assert_eq!(segs[0].text, "Hi all");
+        assert!((segs[0].confidence.unwrap() - 0.8).abs() < 1e-6);
+        assert_eq!(segs[0].lang, None);
+    }
+
+    #[test]
+    fn reads_the_detected_language() {
+        let json = r#"{"result": {"language": "fr"}, "transcription": [
+            {"offsets": {"from": 0, "to": 900}, "text": " Bonjour"}
+        ]}"#;
+        let segs = parse_whisper_json(json, 0).unwrap();
+        assert_eq!(segs[0].lang.as_deref(), Some("fr"));
+        assert_eq!(
+            detected_language(
+                "whisper_init_state: kv self size = 6.29 MB\n\
+                 whisper_full_with_state: auto-detected language: de (p = 0.912)\n"
+            )
+            .as_deref(),
+            Some("de")
+        );
+        assert_eq!(detected_language("no detection here"), None);
+    }
+}
@@ -0,0 +1,516 @@
+//! Transcript sidecars: CSV, SRT/WebVTT subtitles and self-describing JSON.
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Receiver;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+
+use super::TransSegment;
+use crate::sync::lock;
+
+/// Transcript output format, shared by recording and `rcrd transcribe`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    Csv,
+    Srt,
+    Vtt,
+    Json,
+}
+
+impl Format {
+    /// Extension the sidecar replaces the recording's own with.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Csv => "csv",
+            Format::Srt => "srt",
+            Format::Vtt => "vtt",
+            Format::Json => "transcript.json",
+        }
+    }
+
+    /// The format a file name asks for: `.srt`, `.vtt`, `.json`/`.jsonl`,
+    /// and CSV for anything else.
+    pub fn for_path(path: &Path) -> Format {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("srt") => Format::Srt,
+            Some("vtt") => Format::Vtt,
+            Some("json" | "jsonl") => Format::Json,
+            _ => Format::Csv,
+        }
+    }
+}
+
+/// What the JSON export records about the recording besides its segments.
+#[derive(Clone, Debug, Default)]
+pub struct TranscriptInfo {
+    /// RFC 3339 wall-clock time the recording started, when known.
+    pub started_at: Option<String>,
+    pub language: Option<String>,
+}
+
+#[derive(Serialize)]
+struct TranscriptJson<'a> {
+    recording: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    started_at: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    language: Option<&'a str>,
+    segments: &'a [TransSegment],
+}
+
+/// Writes `transcript` next to `outfile` in `format`; `None` when there is
+/// nothing to save.
+pub fn save(
+    format: Format,
+    transcript: &[TransSegment],
+    outfile: &Path,
+    info: &TranscriptInfo,
+) -> Result<Option<PathBuf>> {
+    match format {
+        Format::Csv => save_csv(transcript, outfile),
+        Format::Srt => save_subtitles(transcript, outfile, false),
+        Format::Vtt => save_subtitles(transcript, outfile, true),
+        Format::Json => save_json(transcript, outfile, info),
+    }
+}
+
+/// Shortest cue written; one squeezed to nothing by [`monotonic`] keeps this.
+const MIN_CUE_MS: i64 = 1;
+
+/// `seg` moved to start no earlier than `prev_end_ms` and to end after it
+/// starts, or `None` when it needs no change.
+fn clamp_after(seg: &TransSegment, prev_end_ms: i64) -> Option<TransSegment> {
+    let start_ms = seg.start_ms.max(prev_end_ms);
+    let end_ms = seg.end_ms.max(start_ms + MIN_CUE_MS);
+    (start_ms != seg.start_ms || end_ms != seg.end_ms).then(|| TransSegment {
+        start_ms,
+        end_ms,
+        ..seg.clone()
+    })
+}
+
+/// `transcript` with times subtitle players accept: every segment starts
+/// at or after the end of the one before and ends after it starts. whisper
+/// windows and split speakers can overlap; the text is left alone.
+pub fn monotonic(transcript: &[TransSegment]) -> Vec<TransSegment> {
+    let mut prev_end_ms = 0;
+    let mut corrected = 0;
+    let segments = transcript
+        .iter()
+        .map(|seg| {
+            let seg = match clamp_after(seg, prev_end_ms) {
+                Some(clamped) => {
+                    corrected += 1;
+                    clamped
+                }
+                None => seg.clone(),
+            };
+            prev_end_ms = seg.end_ms;
+            seg
+        })
+        .collect();
+    if corrected > 0 {
+        tracing::warn!(
+            "moved {corrected} overlapping transcript segments to follow the one before"
+        );
+    }
+    segments
+}
+
+/// Writes `<file>.srt`, or `<file>.vtt` with `vtt` set, with times made
+/// [`monotonic`].
+pub fn save_subtitles(
+    transcript: &[TransSegment],
+    outfile: &Path,
+    vtt: bool,
+) -> Result<Option<PathBuf>> {
+    if transcript.is_empty() {
+        return Ok(None);
+    }
+    let path = outfile.with_extension(if vtt { "vtt" } else { "srt" });
+    let mut w = File::create(&path)?;
+    if vtt {
+        writeln!(w, "WEBVTT\n")?;
+    }
+    for (i, seg) in monotonic(transcript).iter().enumerate() {
+        write!(w, "{}", subtitle_cue(seg, i + 1, vtt))?;
+    }
+    Ok(Some(path))
+}
+
+/// One cue, numbered `index` in SRT, followed by its blank line.
+fn subtitle_cue(seg: &TransSegment, index: usize, vtt: bool) -> String {
+    let mut start = format_timecode(seg.start_ms);
+    let mut end = format_timecode(seg.end_ms);
+    let mut cue = String::new();
+    if !vtt {
+        start = start.replace('.', ",");
+        end = end.replace('.', ",");
+        cue = format!("{index}\n");
+    }
+    cue + &format!("{start} --> {end}\n{}\n\n", seg.text)
+}
+
+/// Writes `<file>.csv` (`start,end,text`); `None` when there is nothing to save.
+/// The text is always quoted, so commas, quotes and line breaks inside a
+/// segment stay within its field (RFC 4180).
+pub fn save_csv(transcript: &[TransSegment], outfile: &Path) -> Result<Option<PathBuf>> {
+    if transcript.is_empty() {
+        return Ok(None);
+    }
+    let csv_path = outfile.with_extension("csv");
+    let mut w = File::create(&csv_path)?;
+    writeln!(w, "{CSV_HEADER}")?;
+    for seg in transcript {
+        write!(w, "{}", csv_row(seg))?;
+    }
+    Ok(Some(csv_path))
+}
+
+const CSV_HEADER: &str = "start,end,text";
+
+fn csv_row(seg: &TransSegment) -> String {
+    let start = format_timecode(seg.start_ms);
+    let end = format_timecode(seg.end_ms);
+    let text = seg.text.replace('"', "\"\"");
+    format!("{start},{end},\"{text}\"\n")
+}
+
+/// Writes `<file>.transcript.json`: every segment with its timing, confidence,
+/// speaker and language, plus the recording it belongs to.
+pub fn save_json(
+    transcript: &[TransSegment],
+    outfile: &Path,
+    info: &TranscriptInfo,
+) -> Result<Option<PathBuf>> {
+    if transcript.is_empty() {
+        return Ok(None);
+    }
+    let path = outfile.with_extension(Format::Json.extension());
+    let f =
+        File::create(&path).with_context(|| format!("creating transcript {}", path.display()))?;
+    let doc = TranscriptJson {
+        recording: outfile
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+        started_at: info.started_at.as_deref(),
+        language: info.language.as_deref(),
+        segments: transcript,
+    };
+    let mut w = BufWriter::new(f);
+    serde_json::to_writer_pretty(&mut w, &doc)?;
+    writeln!(w)?;
+    w.flush()?;
+    Ok(Some(path))
+}
+
+/// A transcript file that grows as segments are produced
+/// (`--transcript-live`). The header goes out when it is created and each
+/// segment in a single write, flushed at once, so a `tail -f` reader only
+/// ever sees whole entries. JSON is written as JSON lines, one segment per
+/// line, as a document would only be valid once closed.
+pub struct LiveTranscript {
+    file: File,
+    format: Format,
+    /// Segments written so far, which numbers SRT cues.
+    written: usize,
+    /// End of the last cue; subtitles start after it, as in [`monotonic`].
+    last_end_ms: i64,
+}
+
+impl LiveTranscript {
+    /// Creates (or truncates) `path` and writes the header of `format`.
+    pub fn create(path: &Path, format: Format) -> Result<Self> {
+        let mut file = File::create(path)
+            .with_context(|| format!("creating live transcript {}", path.display()))?;
+        let header = match format {
+            Format::Csv => format!("{CSV_HEADER}\n"),
+            Format::Vtt => "WEBVTT\n\n".into(),
+            Format::Srt | Format::Json => String::new(),
+        };
+        file.write_all(header.as_bytes())?;
+        file.flush()?;
+        Ok(Self {
+            file,
+            format,
+            written: 0,
+            last_end_ms: 0,
+        })
+    }
+
+    pub fn append(&mut self, seg: &TransSegment) -> Result<()> {
+        let clamped;
+        let seg = match clamp_after(seg, self.last_end_ms) {
+            Some(moved) if matches!(self.format, Format::Srt | Format::Vtt) => {
+                tracing::warn!(
+                    "live transcript: moved segment at {}ms to follow the one before",
+                    seg.start_ms
+                );
+                clamped = moved;
+                &clamped
+            }
+            _ => seg,
+        };
+        self.last_end_ms = seg.end_ms;
+        let entry = match self.format {
+            Format::Csv => csv_row(seg),
+            Format::Srt => subtitle_cue(seg, self.written + 1, false),
+            Format::Vtt => subtitle_cue(seg, self.written + 1, true),
+            Format::Json => serde_json::to_string(seg)? + "\n",
+        };
+        self.file.write_all(entry.as_bytes())?;
+        self.file.flush()?;
+        self.written += 1;
+        Ok(())
+    }
+}
+
+/// Appends each segment received on `rx` to `live` from a background
+/// thread, leaving out typed notes unless `notes` is set. The first failed
+/// write goes to `recent_logs` and ends it.
+pub fn start_live_transcript(
+    mut live: LiveTranscript,
+    rx: Receiver<TransSegment>,
+    notes: bool,
+    recent_logs: Arc<Mutex<Vec<String>>>,
+) {
+    thread::spawn(move || {
+        for seg in rx.iter().filter(|seg| notes || !seg.manual) {
+            if let Err(err) = live.append(&seg) {
+                lock(&recent_logs).push(format!("Live transcript failed: {err:#}"));
+                break;
+            }
+        }
+    });
+}
+
+/// `hh:mm:ss.mmm`
+pub fn format_timecode(ms: i64) -> String {
+    let h = ms / 3_600_000;
+    let m = (ms / 60_000) % 60;
+    let s = (ms / 1000) % 60;
+    let ms = ms % 1000;
+    format!("{:02}:{:02}:{:02}.{:03}", h, m, s, ms)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Minimal RFC 4180 reader: quoted fields may hold `""`, commas and newlines.
+    fn read_csv(data: &str) -> Vec<Vec<String>> {
+        let mut rows = Vec::new();
+        let mut row = Vec::new();
+        let mut field = String::new();
+        let mut quoted = false;
+        let mut chars = data.chars().peekable();
+        while let Some(c) = chars.next() {
+            match (quoted, c) {
+                (true, '"') if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                (true, '"') => quoted = false,
+                (true, c) => field.push(c),
+                (false, '"') => quoted = true,
+                (false, ',') => row.push(std::mem::take(&mut field)),
+                (false, '\n') => {
+                    row.push(std::mem::take(&mut field));
+                    rows.push(std::mem::take(&mut row));
+                }
+                (false, c) => field.push(c),
+            }
+        }
+        rows
+    }
+
+    #[test]
+    fn transcript_csv_round_trips_quotes_commas_and_newlines() {
+        let dir = std::env::temp_dir().join(format!("rcrd-csv-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("call.ogg");
+        let texts = [
+            "plain",
+            "she said \"hi\", then left",
+            "first line\nsecond line",
+            "\"quoted\"\r\nwith CRLF, too",
+        ];
+        let transcript: Vec<_> = texts
+            .iter()
+            .enumerate()
+            .map(|(i, text)| TransSegment::new(i as i64 * 1000, i as i64 * 1000 + 500, *text))
+            .collect();
+        let path = save_csv(&transcript, &file).unwrap().unwrap();
+        let rows = read_csv(&std::fs::read_to_string(&path).unwrap());
+        assert_eq!(rows.len(), texts.len() + 1);
+        assert_eq!(rows[0], ["start", "end", "text"]);
+        for (row, text) in rows[1..].iter().zip(texts) {
+            assert_eq!(row.len(), 3);
+            assert_eq!(row[2], text);
+        }
+        assert_eq!(rows[3][0], "00:00:02.000");
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn writes_srt_and_vtt() {
+        let dir = std::env::temp_dir().join(format!("rcrd-subs-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("call.ogg");
+        let transcript = [TransSegment::new(1_500, 3_723_004, "Hello")];
+        let srt = save(Format::Srt, &transcript, &file, &TranscriptInfo::default())
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(srt).unwrap(),
+            "1\n00:00:01,500 --> 01:02:03,004\nHello\n\n"
+        );
+        let vtt = save(Format::Vtt, &transcript, &file, &TranscriptInfo::default())
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(vtt).unwrap(),
+            "WEBVTT\n\n00:00:01.500 --> 01:02:03.004\nHello\n\n"
+        );
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn subtitles_never_overlap() {
+        let transcript = [
+            TransSegment::new(0, 2_000, "one"),
+            // Starts before "one" ends.
+            TransSegment::new(1_500, 3_000, "two"),
+            // Swallowed by "two" entirely, then ends before it starts.
+            TransSegment::new(2_000, 2_500, "three"),
+            TransSegment::new(2_800, 2_700, "four"),
+            TransSegment::new(5_000, 6_000, "five"),
+        ];
+        let times: Vec<_> = monotonic(&transcript)
+            .iter()
+            .map(|seg| (seg.start_ms, seg.end_ms, seg.text.clone()))
+            .collect();
+        assert_eq!(
+            times,
+            [
+                (0, 2_000, "one".into()),
+                (2_000, 3_000, "two".into()),
+                (3_000, 3_001, "three".into()),
+                (3_001, 3_002, "four".into()),
+                (5_000, 6_000, "five".into()),
+            ]
+        );
+
+        let dir = std::env::temp_dir().join(format!("rcrd-overlap-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("live.srt");
+        let mut live = LiveTranscript::create(&path, Format::Srt).unwrap();
+        for seg in &transcript[..2] {
+            live.append(seg).unwrap();
+        }
+        assert!(
+            std::fs::read_to_string(&path)
+                .unwrap()
+                .ends_with("2\n00:00:02,000 --> 00:00:03,000\ntwo\n\n")
+        );
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn live_transcript_holds_whole_entries_after_every_segment() {
+        let dir = std::env::temp_dir().join(format!("rcrd-live-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let segments = [
+            TransSegment::new(0, 1_200, "one, \"two\""),
+            TransSegment::new(1_500, 2_000, "three"),
+        ];
+        for (name, expected) in [
+            (
+                "live.csv",
+                [
+                    "start,end,text\n",
+                    "00:00:00.000,00:00:01.200,\"one, \"\"two\"\"\"\n",
+                ],
+            ),
+            (
+                "live.srt",
+                ["", "1\n00:00:00,000 --> 00:00:01,200\none, \"two\"\n\n"],
+            ),
+            (
+                "live.vtt",
+                [
+                    "WEBVTT\n\n",
+                    "00:00:00.000 --> 00:00:01.200\none, \"two\"\n\n",
+                ],
+            ),
+        ] {
+            let path = dir.join(name);
+            let mut live = LiveTranscript::create(&path, Format::for_path(&path)).unwrap();
+            assert_eq!(std::fs::read_to_string(&path).unwrap(), expected[0]);
+            live.append(&segments[0]).unwrap();
+            assert_eq!(std::fs::read_to_string(&path).unwrap(), expected.concat());
+            live.append(&segments[1]).unwrap();
+            let data = std::fs::read_to_string(&path).unwrap();
+            if name == "live.csv" {
+                assert_eq!(read_csv(&data).len(), 3);
+            } else {
+                assert_eq!(
+                    data.contains("2\n00:00:01,500"),
+                    name == "live.srt",
+                    "{data}"
+                );
+            }
+        }
+
+        let path = dir.join("live.jsonl");
+        let mut live = LiveTranscript::create(&path, Format::for_path(&path)).unwrap();
+        for seg in &segments {
+            live.append(seg).unwrap();
+        }
+        let data = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<serde_json::Value> = data
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["text"], "one, \"two\"");
+        assert_eq!(lines[1]["start_ms"], 1_500);
+        assert_eq!(Format::for_path(Path::new("captions.txt")), Format::Csv);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn json_export_keeps_segment_metadata() {
+        let dir = std::env::temp_dir().join(format!("rcrd-json-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("call.ogg");
+        let mut seg = TransSegment::new(250, 1_750, "[Remote] bonjour");
+        seg.confidence = Some(0.82);
+        seg.speaker = Some("Remote".into());
+        seg.lang = Some("fr".into());
+        let info = TranscriptInfo {
+            started_at: Some("2024-05-01T09:30:00+02:00".into()),
+            language: Some("fr".into()),
+        };
+        let path = save(Format::Json, &[seg], &file, &info).unwrap().unwrap();
+        assert_eq!(path, dir.join("call.transcript.json"));
+        let doc: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(doc["recording"], "call.ogg");
+        assert_eq!(doc["started_at"], "2024-05-01T09:30:00+02:00");
+        assert_eq!(doc["language"], "fr");
+        let seg = &doc["segments"][0];
+        assert_eq!(seg["start_ms"], 250);
+        assert_eq!(seg["end_ms"], 1_750);
+        assert_eq!(seg["speaker"], "Remote");
+        assert_eq!(seg["lang"], "fr");
+        assert!((seg["confidence"].as_f64().unwrap() - 0.82).abs() < 1e-6);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,307 @@
+//! `--trim-silence`: cuts the dead air off both ends of a finished recording.
+//!
+//! A `silencedetect` pass finds where the audio first and last rises above
+//! the threshold, then the kept span is re-encoded in place. Knowing the cut
+//! exactly lets markers and transcript times be shifted to match.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{Context, Result, anyhow};
+use serde::Serialize;
+
+use crate::ffmpeg::Encoding;
+use crate::process::{ProcessRunner, tool};
+
+/// How silence is recognised and how the trimmed file is encoded.
+#[derive(Clone, Debug)]
+pub struct TrimOptions {
+    /// Level below which audio counts as silence, in dB.
+    pub threshold_db: f32,
+    /// Shortest run of silence worth cutting, in seconds.
+    pub min_silence_secs: f64,
+    pub sample_rate: u32,
+    pub channels: u8,
+    /// The recording's own, so the trimmed file matches it.
+    pub encoding: Encoding,
+    /// Keep the untrimmed file as `<name>.untrimmed.<ext>`.
+    pub keep_original: bool,
+}
+
+/// The span of the original file that was kept, in seconds.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct Trim {
+    pub start: f64,
+    pub end: f64,
+}
+
+impl Trim {
+    /// `secs` in the original file, as a time in the trimmed one.
+    pub fn shift_secs(&self, secs: f64) -> f64 {
+        (secs - self.start).clamp(0.0, self.end - self.start)
+    }
+
+    pub fn shift_ms(&self, ms: i64) -> i64 {
+        (self.shift_secs(ms as f64 / 1000.0) * 1000.0).round() as i64
+    }
+}
+
+/// Where the original is moved with [`TrimOptions::keep_original`].
+pub fn untrimmed_path(file: &Path) -> PathBuf {
+    let ext = file.extension().map(|e| e.to_string_lossy().into_owned());
+    file.with_extension(match ext {
+        Some(ext) => format!("untrimmed.{ext}"),
+        None => "untrimmed".into(),
+    })
+}
+
+/// Trims `file` in place. `None` when there was no silence to cut at either
+/// end, or nothing but silence (the file is then left alone).
+pub fn trim_silence(
+    runner: &dyn ProcessRunner,
+    file: &Path,
+    opts: &TrimOptions,
+) -> Result<Option<Trim>> {
+    let out = runner
+        .output(&mut build_detect_command(file, opts))
+        .context("failed to run ffmpeg silencedetect")?;
+    if !out.status.success() {
+        return Err(anyhow!("ffmpeg silencedetect exited with {}", out.status));
+    }
+    let Some(trim) = parse_silence(&String::from_utf8_lossy(&out.stderr)) else {
+        return Ok(None);
+    };
+
+    let tmp = file.with_extension(format!("trimming.{}", opts.encoding.format.extension()));
+    let out = runner
+        .output(&mut build_trim_command(file, &tmp, trim, opts))
+        .context("failed to run ffmpeg")?;
+    if !out.status.success() {
+        let _ = std::fs::remove_file(&tmp);
+        return Err(anyhow!("trimming with ffmpeg exited with {}", out.status));
+    }
+    if opts.keep_original {
+        let original = untrimmed_path(file);
+        std::fs::rename(file, &original)
+            .with_context(|| format!("keeping the original as {}", original.display()))?;
+    }
+    std::fs::rename(&tmp, file)
+        .with_context(|| format!("replacing {} with the trimmed file", file.display()))?;
+    Ok(Some(trim))
+}
+
+pub fn build_detect_command(file: &Path, opts: &TrimOptions) -> Command {
+    let mut cmd = tool("ffmpeg");
+    cmd.args(["-hide_banner", "-nostdin", "-i"])
+        .arg(file)
+        .args([
+            "-af",
+            &silencedetect_filter(opts.threshold_db, opts.min_silence_secs),
+            "-f",
+            "null",
+            "-",
+        ]);
+    cmd
+}
+
+/// `silencedetect` reporting runs below `threshold_db` lasting `min_secs`.
+pub fn silencedetect_filter(threshold_db: f32, min_secs: f64) -> String {
+    format!("silencedetect=noise={threshold_db}dB:duration={min_secs}")
+}
+
+pub fn build_trim_command(input: &Path, output: &Path, trim: Trim, opts: &TrimOptions) -> Command {
+    let mut cmd = tool("ffmpeg");
+    cmd.args(["-hide_banner", "-nostdin", "-y", "-loglevel", "error"]);
+    cmd.args(["-ss", &format!("{:.3}", trim.start), "-i"])
+        .arg(input);
+    cmd.args(["-t", &format!("{:.3}", trim.end - trim.start)]);
+    cmd.args(["-map_metadata", "0"]);
+    cmd.args([
+        "-ac",
+        &opts.channels.to_string(),
+        "-ar",
+        &opts.sample_rate.to_string(),
+    ]);
+    opts.encoding.add_args(&mut cmd);
+    cmd.args(["-f", opts.encoding.format.muxer()]).arg(output);
+    cmd
+}
+
+/// Silence at the ends of a file, in seconds.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EdgeSilence {
+    pub leading: f64,
+    pub trailing: f64,
+    /// Length of the decoded audio.
+    pub duration: f64,
+}
+
+impl EdgeSilence {
+    /// Nothing rose above the threshold at all.
+    pub fn all_silent(&self) -> bool {
+        self.leading >= self.duration
+    }
+}
+
+/// Reads `silencedetect`'s report (ffmpeg's stderr) and returns the span
+/// between the leading and trailing silence.
+fn parse_silence(stderr: &str) -> Option<Trim> {
+    let edges = parse_edge_silence(stderr)?;
+    let (start, end) = (edges.leading, edges.duration - edges.trailing);
+    let trimmed = start > 0.0 || end < edges.duration;
+    (!edges.all_silent() && end > start && trimmed).then_some(Trim { start, end })
+}
+
+/// The leading and trailing silence in `silencedetect`'s report; `None`
+/// when the report holds no progress line to tell the duration.
+pub fn parse_edge_silence(stderr: &str) -> Option<EdgeSilence> {
+    let mut silences: Vec<(f64, Option<f64>)> = Vec::new();
+    let mut duration = None;
+    let value_after = |line: &str, key: &str| -> Option<f64> {
+        let rest = &line[line.find(key)? + key.len()..];
+        rest.split_whitespace().next()?.parse().ok()
+    };
+    // Progress updates end in `\r`, so split on that too.
+    for line in stderr.split(['\n', '\r']) {
+        if let Some(start) = value_after(line, "silence_start:") {
+            silences.push((start, None));
+        } else if let Some(end) = value_after(line, "silence_end:") {
+            if let Some(last) = silences.last_mut() {
+                last.1 = Some(end);
+            }
+        } else if let Some(time) = line.find("time=").map(|i| &line[i + 5..]) {
+            // The last progress line tells how much audio was decoded.
+            duration = time
+                .split_whitespace()
+                .next()
+                .and_then(parse_clock)
+                .or(duration);
+        }
+    }
+    let duration = duration?;
+    let leading = match silences.first() {
+        Some(&(start, end)) if start <= 0.01 => end.unwrap_or(duration),
+        _ => 0.0,
+    };
+    let trailing = match silences.last() {
+        Some(&(start, None)) => duration - start,
+        Some(&(start, Some(end))) if end >= duration - 0.05 => duration - start,
+        _ => 0.0,
+    };
+    Some(EdgeSilence {
+        leading,
+        trailing,
+        duration,
+    })
+}
+
+/// `hh:mm:ss.xx` as seconds.
+fn parse_clock(s: &str) -> Option<f64> {
+    let mut parts = s.split(':').map(|p| p.parse::<f64>().ok());
+    let (h, m, sec) = (parts.next()??, parts.next()??, parts.next()??);
+    Some(h * 3600.0 + m * 60.0 + sec)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REPORT: &str = "\
+Input #0, ogg, from 'call.ogg':
+  Duration: 00:00:42.50, start: 0.000000, bitrate: 90 kb/s
+[silencedetect @ 0x5581] silence_start: 0
+[silencedetect @ 0x5581] silence_end: 3.204 | silence_duration: 3.204
+size=N/A time=00:00:20.00 bitrate=N/A speed= 400x\r\
+[silencedetect @ 0x5581] silence_start: 18.5
+[silencedetect @ 0x5581] silence_end: 19.9 | silence_duration: 1.4
+[silencedetect @ 0x5581] silence_start: 39.75
+size=N/A time=00:00:42.50 bitrate=N/A speed= 410x
+";
+
+    fn opts() -> TrimOptions {
+        TrimOptions {
+            threshold_db: -50.0,
+            min_silence_secs: 1.0,
+            sample_rate: 48_000,
+            channels: 2,
+            encoding: Encoding::default(),
+            keep_original: false,
+        }
+    }
+
+    #[test]
+    fn finds_leading_and_trailing_silence_only() {
+        let trim = parse_silence(REPORT).unwrap();
+        assert_eq!(
+            trim,
+            Trim {
+                start: 3.204,
+                end: 39.75
+            }
+        );
+        // Newer ffmpeg also closes the trailing silence at the end of the file.
+        let closed = REPORT.replace(
+            "silence_start: 39.75\n",
+            "silence_start: 39.75\n[silencedetect] silence_end: 42.5 | silence_duration: 2.75\n",
+        );
+        assert_eq!(parse_silence(&closed), Some(trim));
+    }
+
+    #[test]
+    fn nothing_to_trim_without_edge_silence_or_with_only_silence() {
+        let speech_only = "size=N/A time=00:00:10.00 bitrate=N/A\n\
+            [silencedetect @ 0x1] silence_start: 4\n\
+            [silencedetect @ 0x1] silence_end: 6 | silence_duration: 2\n";
+        assert_eq!(parse_silence(speech_only), None);
+        let silent =
+            "[silencedetect @ 0x1] silence_start: 0\nsize=N/A time=00:00:10.00 bitrate=N/A\n";
+        assert_eq!(parse_silence(silent), None);
+        assert!(parse_edge_silence(silent).unwrap().all_silent());
+        assert_eq!(
+            parse_edge_silence(REPORT).unwrap(),
+            EdgeSilence {
+                leading: 3.204,
+                trailing: 2.75,
+                duration: 42.5
+            }
+        );
+    }
+
+    #[test]
+    fn shifts_times_into_the_trimmed_file() {
+        let trim = Trim {
+            start: 3.0,
+            end: 40.0,
+        };
+        assert_eq!(trim.shift_secs(10.5), 7.5);
+        assert_eq!(trim.shift_secs(1.0), 0.0);
+        assert_eq!(trim.shift_secs(41.0), 37.0);
+        assert_eq!(trim.shift_ms(3_250), 250);
+    }
+
+    #[test]
+    fn trim_command_cuts_and_reencodes_like_the_recording() {
+        let cmd = build_trim_command(
+            Path::new("call.ogg"),
+            Path::new("call.trimming.ogg"),
+            Trim {
+                start: 3.204,
+                end: 39.75,
+            },
+            &opts(),
+        );
+        let args = crate::process::argv(&cmd).join(" ");
+        assert!(args.contains("-ss 3.204 -i call.ogg -t 36.546"), "{args}");
+        assert!(
+            args.contains("-c:a libopus -b:a 128k -application voip"),
+            "{args}"
+        );
+        assert!(args.ends_with("-f ogg call.trimming.ogg"), "{args}");
+        assert_eq!(
+            untrimmed_path(Path::new("a/call.ogg")),
+            Path::new("a/call.untrimmed.ogg")
+        );
+        let detect = crate::process::argv(&build_detect_command(Path::new("call.ogg"), &opts()));
+        assert!(detect.contains(&"silencedetect=noise=-50dB:duration=1".to_string()));
+    }
+}
@@ -1,62 +1,225 @@
+use std::collections::VecDeque;
 use std::io;
-use std::path::PathBuf;
 use std::process::Child;
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 
 use anyhow::Result;
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyModifiers},
+    event::{
+        self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEventKind, KeyModifiers,
+        KeyboardEnhancementFlags, PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+    },
     execute,
-    terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
+    terminal::{
+        EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode,
+        supports_keyboard_enhancement,
+    },
 };
 use ratatui::{
     Terminal,
     backend::{Backend, CrosstermBackend},
-    layout::{Constraint, Direction, Layout},
+    buffer::Buffer,
+    layout::{Alignment, Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
     text::{Line, Span, Text},
-    widgets::{Block, Borders, Paragraph},
+    widgets::{Block, Borders, Clear, Paragraph, Sparkline, Widget, Wrap},
 };
 
-use crate::Marker;
-use crate::ffmpeg::{Levels, write_mic_volume};
-use crate::transcript::TransSegment;
+use rcrd::RecordingHandle;
+use rcrd::disk::format_bytes;
+use rcrd::ffmpeg::{Levels, PEAK_HOLD_RANGE_DB};
+use rcrd::playback::Player;
+use rcrd::sync::{lock, lock_logged};
+use rcrd::transcript::export::save_csv;
+use rcrd::transcript::{TransSegment, TranscriberStatus};
+
+use crate::theme::Theme;
 
 pub struct RecorderState {
-    pub start_time: Instant,
-    pub duration: Option<Duration>,
-    pub mic_muted: bool,
-    pub mic_cmd_file: Option<PathBuf>,
+    pub handle: RecordingHandle,
     pub running: bool,
-    pub output_file: PathBuf,
-    pub monitor_source: String,
-    pub mic_source: Option<String>,
     pub git_rev: Option<String>,
-    pub audio_level: Arc<Mutex<Levels>>,
-    pub markers: Vec<Marker>,
-    pub recent_logs: Arc<Mutex<Vec<String>>>,
-    pub transcript: Arc<Mutex<Vec<TransSegment>>>,
-    pub transcription_active: bool,
-    pub transcription_flag: Arc<AtomicBool>,
-    pub transcription_stop: Arc<AtomicBool>,
-    pub transcription_reset: Arc<AtomicBool>,
-    pub base_offset_ms: Arc<std::sync::atomic::AtomicI64>,
-    pub language: Arc<Mutex<String>>,
-    pub whisper_model: Option<PathBuf>,
-}
-
-pub fn run_app(mut state: RecorderState, child: &mut Child) -> Result<RecorderState> {
+    /// Whether the transcriber processes audio (`T`); pausing it leaves a gap
+    /// in the transcript, unlike hiding the pane.
+    pub transcribe_enabled: bool,
+    /// Whether the transcript pane is shown (`t`); the transcriber keeps
+    /// collecting segments while it is hidden.
+    pub transcript_visible: bool,
+    /// Set once `T` is used; until then showing the pane starts transcribing.
+    pub transcribe_toggled: bool,
+    /// Scroll and search state of the transcript pane, kept while it is hidden.
+    pub transcript_view: TranscriptView,
+    /// Warn when the monitor is still digitally silent after this long.
+    pub silence_check: Option<Duration>,
+    pub monitor_heard: bool,
+    /// Note being typed for the last marker (`e`); keys go here while set.
+    pub editing_note: Option<String>,
+    /// Note being typed into the transcript (`a`); keys go here while set.
+    pub editing_transcript_note: Option<String>,
+    /// `d` was pressed; the next key confirms (`y`) or cancels the discard.
+    pub confirm_discard: bool,
+    /// Stop without keeping anything: the caller deletes the file and sidecars.
+    pub discard: bool,
+    pub marker_clock: MarkerClock,
+    /// Preview started with `p`; replaced on the next press, killed on exit.
+    pub player: Option<Child>,
+    /// Maximum redraws per second.
+    pub fps: u32,
+    pub layout: LayoutMode,
+    /// Space mutes the mic while held (`--ptt`).
+    pub ptt: bool,
+    pub theme: Theme,
+    /// With a duration, show a large countdown over the last this many
+    /// seconds; zero disables it.
+    pub countdown: Duration,
+    /// Clips counted in [`Levels::clips`] when last looked at.
+    pub clips_seen: u32,
+    /// When the latest clip was noticed; lights the CLIP badge for [`CLIP_HOLD`].
+    pub last_clip: Option<Instant>,
+    /// Recent peaks drawn in the History pane.
+    pub level_history: LevelHistory,
+}
+
+/// How long the CLIP badge stays lit after a clip.
+const CLIP_HOLD: Duration = Duration::from_secs(2);
+
+/// How much `+` and `-` change the mic volume by.
+const MIC_VOLUME_STEP: f32 = 0.05;
+
+/// Time each bar of the level history covers.
+const HISTORY_STEP: Duration = Duration::from_millis(500);
+
+/// Bars kept, enough for a wide terminal: four minutes at [`HISTORY_STEP`].
+const HISTORY_LEN: usize = 480;
+
+/// The highest peak of each [`HISTORY_STEP`], newest last, as a height in
+/// dB above the bottom of the [`PEAK_HOLD_RANGE_DB`] range.
+#[derive(Debug, Default)]
+pub struct LevelHistory {
+    bars: VecDeque<u64>,
+    /// Highest peak since the last bar, in dBFS.
+    pending: Option<f32>,
+    since: Duration,
+    /// Bars pushed so far, so the screen is redrawn for each new one.
+    pushed: u64,
+}
+
+impl LevelHistory {
+    /// Takes the peak seen on a UI tick `dt` after the previous one.
+    fn push(&mut self, db: f32, dt: Duration) {
+        self.pending = Some(self.pending.map_or(db, |p| p.max(db)));
+        self.since += dt;
+        if self.since < HISTORY_STEP {
+            return;
+        }
+        self.since = Duration::ZERO;
+        let db = self.pending.take().unwrap_or(f32::NEG_INFINITY);
+        let height = if db.is_finite() {
+            (db + PEAK_HOLD_RANGE_DB)
+                .clamp(0.0, PEAK_HOLD_RANGE_DB)
+                .round() as u64
+        } else {
+            0
+        };
+        if self.bars.len() == HISTORY_LEN {
+            self.bars.pop_front();
+        }
+        self.bars.push_back(height);
+        self.pushed += 1;
+    }
+
+    /// The newest `n` bars, oldest first.
+    fn recent(&self, n: usize) -> Vec<u64> {
+        self.bars
+            .iter()
+            .skip(self.bars.len().saturating_sub(n))
+            .copied()
+            .collect()
+    }
+}
+
+/// Where the transcript pane is scrolled to and what it searches for.
+#[derive(Debug, Default)]
+pub struct TranscriptView {
+    /// Segments scrolled back from the newest; 0 follows new text.
+    pub scroll: usize,
+    /// Search being typed after `/`; keys go here while set.
+    pub editing_search: Option<String>,
+    /// Last search; matching segments are highlighted and `n` jumps between them.
+    pub search: Option<String>,
+}
+
+impl TranscriptView {
+    /// Index range of the `rows` segments shown out of `len`.
+    fn visible(&self, len: usize, rows: usize) -> std::ops::Range<usize> {
+        let end = len - self.scroll.min(len.saturating_sub(1));
+        end.saturating_sub(rows)..end
+    }
+
+    fn matches(&self, seg: &TransSegment) -> bool {
+        self.search
+            .as_deref()
+            .filter(|q| !q.is_empty())
+            .is_some_and(|q| seg.text.to_lowercase().contains(&q.to_lowercase()))
+    }
+
+    /// Scrolls to the newest match at or above the bottom line (strictly
+    /// above with `skip_current`); returns false when there is none.
+    fn jump_to_match(&mut self, t: &[TransSegment], skip_current: bool) -> bool {
+        let Some(bottom) = self.visible(t.len(), 1).next() else {
+            return false;
+        };
+        let last = if skip_current { bottom } else { bottom + 1 };
+        match t[..last].iter().rposition(|seg| self.matches(seg)) {
+            Some(idx) => {
+                self.scroll = t.len() - 1 - idx;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// How marker times are shown in the status line.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum MarkerClock {
+    /// Time since the recording started.
+    Relative,
+    /// Wall-clock time of day.
+    Wall,
+}
+
+pub fn run_app(mut state: RecorderState) -> Result<RecorderState> {
     enable_raw_mode()?;
     let mut stdout = io::stdout();
     execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
+    // Key releases are only reported under the kitty keyboard protocol;
+    // elsewhere push-to-talk degrades to a toggle.
+    let hold = state.ptt && supports_keyboard_enhancement().unwrap_or(false);
+    if hold {
+        execute!(
+            stdout,
+            PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES)
+        )?;
+        state.handle.log("Push-to-talk: hold Space to mute the mic");
+    } else if state.ptt {
+        state
+            .handle
+            .log("Push-to-talk: terminal reports no key releases, Space toggles mute");
+    }
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
-    let result = run_loop(&mut terminal, &mut state, child);
+    let result = run_loop(&mut terminal, &mut state, hold);
+    if let Some(mut player) = state.player.take() {
+        let _ = player.kill();
+        let _ = player.wait();
+    }
 
     // Restore terminal even if run_loop fails
+    if hold {
+        let _ = execute!(terminal.backend_mut(), PopKeyboardEnhancementFlags);
+    }
     let _ = disable_raw_mode();
     let _ = execute!(
         terminal.backend_mut(),
@@ -68,127 +231,633 @@ pub fn run_app(mut state: RecorderState, child: &mut Child) -> Result<RecorderSt
     result.map(|_| state)
 }
 
+/// Everything the screen shows that can change without a key press; the
+/// frame is only redrawn when this differs from the last drawn one.
+#[derive(PartialEq)]
+struct ViewKey {
+    secs: u64,
+    levels: Levels,
+    transcript: usize,
+    logs: usize,
+    mic_lost: bool,
+    monitor_heard: bool,
+    clipping: bool,
+    transcriber: Option<TranscriberStatus>,
+    history: u64,
+}
+
+impl ViewKey {
+    fn of(state: &RecorderState) -> Self {
+        Self {
+            secs: state.handle.elapsed().as_secs(),
+            levels: state.handle.levels(),
+            transcript: lock(&state.handle.transcript()).len(),
+            logs: lock(&state.handle.logs()).len(),
+            mic_lost: state.handle.mic_lost(),
+            monitor_heard: state.monitor_heard,
+            clipping: clipping(state),
+            transcriber: state.handle.transcriber_status(),
+            history: state.level_history.pushed,
+        }
+    }
+}
+
 fn run_loop<B: Backend>(
     terminal: &mut Terminal<B>,
     state: &mut RecorderState,
-    child: &mut Child,
+    hold: bool,
 ) -> Result<()> {
+    let frame = Duration::from_secs_f64(1.0 / state.fps.max(1) as f64);
+    let mut dirty = true;
+    let mut last_key = None;
+    let mut next_frame = Instant::now();
+    let mut last_tick = next_frame;
+    // Mute state to restore when the push-to-talk key is released.
+    let mut ptt_held: Option<bool> = None;
     loop {
-        terminal.draw(|f| ui(f, state))?;
+        let now = Instant::now();
+        if now >= next_frame {
+            next_frame = now + frame;
+            state.handle.decay_levels(now - last_tick);
+            let levels = state.handle.levels();
+            state
+                .level_history
+                .push(levels.peak_l.max(levels.peak_r), now - last_tick);
+            last_tick = now;
+            let clips = levels.clips;
+            if clips > state.clips_seen {
+                state.clips_seen = clips;
+                state.last_clip = Some(now);
+            }
+            let key = ViewKey::of(state);
+            if dirty || last_key.as_ref() != Some(&key) {
+                terminal.draw(|f| ui(f, state))?;
+                last_key = Some(key);
+                dirty = false;
+            }
+        }
 
-        if event::poll(Duration::from_millis(50))? {
-            if let Event::Key(key) = event::read()? {
+        let timeout = next_frame.saturating_duration_since(Instant::now());
+        let event = if event::poll(timeout)? {
+            // Any event (keys, resizes) warrants a redraw.
+            dirty = true;
+            Some(event::read()?)
+        } else {
+            None
+        };
+        if let Some(Event::Resize(..)) = event {
+            // Drop the old buffer so nothing from the previous size lingers;
+            // the next draw recomputes the layout for the new area.
+            terminal.autoresize()?;
+            terminal.clear()?;
+            next_frame = Instant::now();
+        }
+        if let Some(Event::Key(key)) = event {
+            tracing::debug!("key {:?} {:?} {:?}", key.kind, key.code, key.modifiers);
+            // Only reported with keyboard enhancement, i.e. in hold-to-mute mode.
+            if key.kind != KeyEventKind::Press {
+                if key.kind == KeyEventKind::Release
+                    && key.code == KeyCode::Char(' ')
+                    && let Some(was_muted) = ptt_held.take()
+                {
+                    let _ = state.handle.set_mic_muted(was_muted);
+                }
+                continue;
+            }
+            if let Some(query) = &mut state.transcript_view.editing_search {
                 match key.code {
-                    KeyCode::Char('q') | KeyCode::Esc => {
-                        state.running = false;
-                        state.transcription_stop.store(true, Ordering::Relaxed);
+                    KeyCode::Enter => {
+                        let view = &mut state.transcript_view;
+                        view.search = view.editing_search.take();
+                        let transcript = state.handle.transcript();
+                        if !view.jump_to_match(&lock(&transcript), false) {
+                            state.handle.log("No transcript line matches");
+                        }
                     }
-                    KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                        state.running = false;
-                        state.transcription_stop.store(true, Ordering::Relaxed);
+                    KeyCode::Esc => state.transcript_view.editing_search = None,
+                    KeyCode::Backspace => {
+                        query.pop();
                     }
-                    KeyCode::Char('m') => {
-                        if let Some(cmd_path) = &state.mic_cmd_file {
-                            state.mic_muted = !state.mic_muted;
-                            let vol = if state.mic_muted { 0.0 } else { 1.0 };
-                            let _ = write_mic_volume(cmd_path, vol);
-                        }
+                    KeyCode::Char(c) => query.push(c),
+                    _ => {}
+                }
+                continue;
+            }
+            if state.confirm_discard {
+                state.confirm_discard = false;
+                if let KeyCode::Char('y' | 'Y') = key.code {
+                    state.discard = true;
+                    state.running = false;
+                } else {
+                    state.handle.log("Discard cancelled");
+                }
+                continue;
+            }
+            if let Some(note) = &mut state.editing_note {
+                match key.code {
+                    KeyCode::Enter => {
+                        let note = state.editing_note.take().unwrap_or_default();
+                        state.handle.set_last_marker_note(note);
                     }
-                    KeyCode::Char('b') => {
-                        let elapsed = state.start_time.elapsed().as_secs_f64();
-                        state.markers.push(Marker {
-                            timestamp: elapsed,
-                            note: format!("Marker #{}", state.markers.len() + 1),
-                        });
+                    KeyCode::Esc => state.editing_note = None,
+                    KeyCode::Backspace => {
+                        note.pop();
                     }
-                    KeyCode::Char('t') => {
-                        if state.whisper_model.is_some() {
-                            state.transcription_active = !state.transcription_active;
-                            state
-                                .transcription_flag
-                                .store(state.transcription_active, Ordering::Relaxed);
-                            if state.transcription_active {
-                                let elapsed_ms = state
-                                    .start_time
-                                    .elapsed()
-                                    .as_millis()
-                                    .try_into()
-                                    .unwrap_or(0);
-                                state
-                                    .base_offset_ms
-                                    .store(elapsed_ms, std::sync::atomic::Ordering::Relaxed);
-                                state.transcription_reset.store(true, Ordering::Relaxed);
-                            }
-                        } else if let Ok(mut logs) = state.recent_logs.lock() {
-                            logs.push("Transcription model not configured".into());
+                    KeyCode::Char(c) => note.push(c),
+                    _ => {}
+                }
+                continue;
+            }
+            if let Some(note) = &mut state.editing_transcript_note {
+                match key.code {
+                    KeyCode::Enter => {
+                        let note = state.editing_transcript_note.take().unwrap_or_default();
+                        if !note.trim().is_empty() {
+                            state.handle.add_note(note.trim());
                         }
                     }
-                    KeyCode::Char('l') => {
-                        if let Ok(mut lang) = state.language.lock() {
-                            *lang = if *lang == "en" {
-                                "fr".into()
-                            } else {
-                                "en".into()
-                            };
-                            if let Ok(mut logs) = state.recent_logs.lock() {
-                                logs.push(format!("Language set to {}", *lang));
-                            }
-                        }
+                    KeyCode::Esc => state.editing_transcript_note = None,
+                    KeyCode::Backspace => {
+                        note.pop();
                     }
+                    KeyCode::Char(c) => note.push(c),
                     _ => {}
                 }
+                continue;
             }
+            match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => {
+                    state.running = false;
+                }
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    state.running = false;
+                }
+                KeyCode::Char('d') => state.confirm_discard = true,
+                KeyCode::Char('m') => {
+                    let muted = !state.handle.mic_muted();
+                    let _ = state.handle.set_mic_muted(muted);
+                }
+                KeyCode::Char(c @ ('+' | '=' | '-')) => {
+                    let step = if c == '-' {
+                        -MIC_VOLUME_STEP
+                    } else {
+                        MIC_VOLUME_STEP
+                    };
+                    // Rounded so repeated steps land on whole percents.
+                    let volume = ((state.handle.mic_volume() + step) * 100.0).round() / 100.0;
+                    if let Err(err) = state.handle.set_mic_volume(volume) {
+                        state.handle.log(format!("Mic volume: {err:#}"));
+                    }
+                }
+                KeyCode::Char(' ') if state.ptt => {
+                    if hold {
+                        ptt_held.get_or_insert(state.handle.mic_muted());
+                        let _ = state.handle.set_mic_muted(true);
+                    } else {
+                        let muted = !state.handle.mic_muted();
+                        let _ = state.handle.set_mic_muted(muted);
+                    }
+                }
+                KeyCode::Char('b') => {
+                    let note = format!("Marker #{}", state.handle.markers().len() + 1);
+                    state.handle.add_marker(note);
+                }
+                KeyCode::Char('x') => {
+                    if let Some(marker) = state.handle.pop_marker() {
+                        state.handle.log(format!(
+                            "Removed marker at {:.1}s ({})",
+                            marker.timestamp, marker.note
+                        ));
+                    }
+                }
+                KeyCode::Char('e') => {
+                    if let Some(marker) = state.handle.markers().last() {
+                        state.editing_note = Some(marker.note.clone());
+                    }
+                }
+                KeyCode::Char('a') => state.editing_transcript_note = Some(String::new()),
+                KeyCode::Char('p') => play_last_marker(state),
+                KeyCode::Char('P') => {
+                    let res = if state.handle.is_paused() {
+                        state.handle.resume()
+                    } else {
+                        state.handle.pause()
+                    };
+                    if let Err(err) = res {
+                        state.handle.log(format!("Pause: {err:#}"));
+                    }
+                }
+                KeyCode::Char('s') => save_transcript_checkpoint(state),
+                KeyCode::Char('t') => {
+                    // Hiding the pane leaves the transcriber running so the
+                    // history is complete when it is shown again. Showing it
+                    // the first time starts transcribing, so it isn't empty.
+                    if state.transcript_visible {
+                        state.transcript_visible = false;
+                    } else if state.handle.transcriber_status().is_none() {
+                        log_transcription_unavailable(state);
+                    } else {
+                        if !state.transcribe_enabled && !state.transcribe_toggled {
+                            state.transcribe_enabled = state.handle.set_transcribing(true);
+                        }
+                        state.transcript_visible = true;
+                    }
+                }
+                KeyCode::Char('T') => {
+                    let on = !state.transcribe_enabled;
+                    if state.handle.set_transcribing(on) {
+                        state.transcribe_enabled = on;
+                        state.transcribe_toggled = true;
+                        state.handle.log(if on {
+                            "Transcription resumed"
+                        } else {
+                            "Transcription paused; press T to resume"
+                        });
+                    } else {
+                        log_transcription_unavailable(state);
+                    }
+                }
+                KeyCode::Up => scroll_transcript(state, 1),
+                KeyCode::Down => scroll_transcript(state, -1),
+                KeyCode::PageUp => scroll_transcript(state, 10),
+                KeyCode::PageDown => scroll_transcript(state, -10),
+                KeyCode::End => state.transcript_view.scroll = 0,
+                KeyCode::Char('/') if state.transcript_visible => {
+                    state.transcript_view.editing_search = Some(String::new());
+                }
+                KeyCode::Char('n') => {
+                    let transcript = state.handle.transcript();
+                    let t = lock(&transcript);
+                    if state.transcript_view.search.is_some()
+                        && !state.transcript_view.jump_to_match(&t, true)
+                    {
+                        drop(t);
+                        state.handle.log("No earlier match");
+                    }
+                }
+                KeyCode::Char('l') => {
+                    let lang = if state.handle.language() == "en" {
+                        "fr"
+                    } else {
+                        "en"
+                    };
+                    state.handle.set_language(lang);
+                    state.handle.log(format!("Language set to {lang}"));
+                }
+                _ => {}
+            }
+        }
+
+        if !state.monitor_heard && state.handle.levels().monitor_peak.is_finite() {
+            state.monitor_heard = true;
         }
 
         // Check if ffmpeg is still running
-        match child.try_wait() {
-            Ok(Some(_status)) => {
-                state.running = false;
-            }
-            Ok(None) => {}
-            Err(e) => return Err(e.into()),
+        if state.handle.is_finished()? {
+            state.running = false;
         }
 
-        if let Some(duration) = state.duration {
-            if state.start_time.elapsed() >= duration {
-                state.running = false;
-            }
+        if let Some(duration) = state.handle.duration()
+            && state.handle.elapsed() >= duration
+        {
+            state.running = false;
         }
 
         if !state.running {
             break;
         }
     }
-    state.transcription_stop.store(true, Ordering::Relaxed);
     Ok(())
 }
 
+/// Moves the transcript pane `by` segments back (negative: forward),
+/// stopping at the oldest segment and at the live tail.
+fn scroll_transcript(state: &mut RecorderState, by: isize) {
+    let len = lock(&state.handle.transcript()).len();
+    let view = &mut state.transcript_view;
+    view.scroll = view
+        .scroll
+        .saturating_add_signed(by)
+        .min(len.saturating_sub(1));
+}
+
+/// Writes what has been transcribed so far to the transcript CSV sidecar.
+fn save_transcript_checkpoint(state: &RecorderState) {
+    let snapshot: Vec<TransSegment> = lock(&state.handle.transcript())
+        .iter()
+        .filter(|seg| !seg.low_confidence)
+        .cloned()
+        .collect();
+    match save_csv(&snapshot, state.handle.output()) {
+        Ok(Some(path)) => state.handle.log(format!(
+            "Saved {} transcript segments to {}",
+            snapshot.len(),
+            path.display()
+        )),
+        Ok(None) => state.handle.log("Nothing transcribed yet"),
+        Err(err) => state
+            .handle
+            .log(format!("Saving transcript failed: {err:#}")),
+    }
+}
+
+/// Plays the file written so far from the last marker (or the start).
+fn play_last_marker(state: &mut RecorderState) {
+    let Some(player) = Player::detect() else {
+        state
+            .handle
+            .log("No player found (install ffplay or pw-play)");
+        return;
+    };
+    if let Some(mut old) = state.player.take() {
+        let _ = old.kill();
+        let _ = old.wait();
+    }
+    let start = state.handle.markers().last().map(|m| m.timestamp);
+    if start.is_some() && !player.can_seek() {
+        state
+            .handle
+            .log("pw-play cannot seek; playing from the start");
+    }
+    match player.spawn(state.handle.output(), start) {
+        Ok(child) => state.player = Some(child),
+        Err(err) => state.handle.log(format!("Playback failed: {err:#}")),
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Pane {
+    Header,
+    Info,
+    Status,
+    /// Sparkline of recent peaks.
+    History,
+    Timeline,
+    Controls,
+    /// Logs / Transcript.
+    Bottom,
+}
+
+impl Pane {
+    const ALL: [Pane; 7] = [
+        Pane::Header,
+        Pane::Info,
+        Pane::Status,
+        Pane::History,
+        Pane::Timeline,
+        Pane::Controls,
+        Pane::Bottom,
+    ];
+    /// Panes given up, in order, when the terminal is too short for all of them.
+    const DROP_ORDER: [Pane; 5] = [
+        Pane::History,
+        Pane::Bottom,
+        Pane::Controls,
+        Pane::Timeline,
+        Pane::Info,
+    ];
+
+    fn height(self) -> u16 {
+        match self {
+            Pane::Info => 8,
+            Pane::Bottom => 4,
+            _ => 3,
+        }
+    }
+
+    fn constraint(self) -> Constraint {
+        match self {
+            Pane::Bottom => Constraint::Min(self.height()),
+            _ => Constraint::Length(self.height()),
+        }
+    }
+}
+
+/// Panes that fit in `height` rows, dropping the lowest priority ones first.
+fn visible_panes(height: u16) -> Vec<Pane> {
+    let mut panes = Pane::ALL.to_vec();
+    for pane in Pane::DROP_ORDER {
+        if panes.iter().map(|p| p.height()).sum::<u16>() <= height {
+            break;
+        }
+        panes.retain(|p| *p != pane);
+    }
+    panes
+}
+
+/// Below this many rows the compact layout is used even without `--compact`.
+const COMPACT_HEIGHT: u16 = 14;
+
+/// Which screen layout `ui` draws.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LayoutMode {
+    Full,
+    /// One status line, one key line, then the transcript or log.
+    Compact,
+}
+
 fn ui(f: &mut ratatui::Frame, state: &RecorderState) {
+    if state.layout == LayoutMode::Compact || f.size().height < COMPACT_HEIGHT {
+        ui_compact(f, state);
+    } else {
+        ui_full(f, state);
+    }
+    if let Some(duration) = state.handle.duration()
+        && let Some(secs) = countdown_secs(state.handle.elapsed(), duration, state.countdown)
+    {
+        render_countdown(f, state, secs);
+    }
+}
+
+/// Whole seconds left (rounded up) once the recording is within `window`
+/// of `duration`; `None` before that and after it ends.
+fn countdown_secs(elapsed: Duration, duration: Duration, window: Duration) -> Option<u64> {
+    let left = duration
+        .checked_sub(elapsed)
+        .filter(|left| !left.is_zero())?;
+    (left <= window).then(|| left.as_secs() + u64::from(left.subsec_nanos() > 0))
+}
+
+/// The big centered number of [`countdown_secs`], or a single line when the
+/// terminal is too small for the digits.
+fn render_countdown(f: &mut ratatui::Frame, state: &RecorderState, secs: u64) {
+    let style = Theme::banner(state.theme.alert, Color::White);
+    let area = f.size();
+    let digits = big_digits(secs);
+    let width = digits[0].chars().count() as u16 + 4;
+    let height = digits.len() as u16 + 2;
+    if area.width < width || area.height < height {
+        let text = format!(" Stopping in {secs}s ");
+        let rect = centered(area, text.chars().count() as u16, 1);
+        f.render_widget(Clear, rect);
+        f.render_widget(Paragraph::new(text).style(style), rect);
+        return;
+    }
+    let rect = centered(area, width, height);
+    f.render_widget(Clear, rect);
+    f.render_widget(
+        Paragraph::new(digits.join("\n"))
+            .alignment(Alignment::Center)
+            .style(style)
+            .block(
+                Block::default()
+                    .title(" Stopping in ")
+                    .borders(Borders::ALL),
+            ),
+        rect,
+    );
+}
+
+/// Wraps `paragraph` to `area` (inside its borders when `bordered`) and
+/// scrolls it so the last line stays in view. ratatui measures graphemes by
+/// display width, so wide CJK glyphs and emoji take two columns and
+/// combining marks none.
+fn wrapped_tail(paragraph: Paragraph<'_>, area: Rect, bordered: bool) -> Paragraph<'_> {
+    let border = if bordered { 2 } else { 0 };
+    let paragraph = paragraph.wrap(Wrap { trim: false });
+    let rows = paragraph.line_count(area.width.saturating_sub(border));
+    let overflow = rows.saturating_sub(area.height.saturating_sub(border).into());
+    paragraph.scroll((overflow.try_into().unwrap_or(u16::MAX), 0))
+}
+
+/// A `width` x `height` rectangle in the middle of `area`, clipped to it.
+fn centered(area: Rect, width: u16, height: u16) -> Rect {
+    let width = width.min(area.width);
+    let height = height.min(area.height);
+    Rect::new(
+        area.x + (area.width - width) / 2,
+        area.y + (area.height - height) / 2,
+        width,
+        height,
+    )
+}
+
+/// `n` drawn five rows high with block characters.
+fn big_digits(n: u64) -> Vec<String> {
+    const FONT: [[&str; 5]; 10] = [
+        ["███", "█ █", "█ █", "█ █", "███"],
+        [" █ ", "██ ", " █ ", " █ ", "███"],
+        ["███", "  █", "███", "█  ", "███"],
+        ["███", "  █", "███", "  █", "███"],
+        ["█ █", "█ █", "███", "  █", "  █"],
+        ["███", "█  ", "███", "  █", "███"],
+        ["███", "█  ", "███", "█ █", "███"],
+        ["███", "  █", "  █", "  █", "  █"],
+        ["███", "█ █", "███", "█ █", "███"],
+        ["███", "█ █", "███", "  █", "███"],
+    ];
+    let digits: Vec<usize> = n.to_string().bytes().map(|b| (b - b'0') as usize).collect();
+    (0..5)
+        .map(|row| {
+            digits
+                .iter()
+                // Every cell is doubled so the digits aren't too narrow.
+                .map(|&d| {
+                    FONT[d][row]
+                        .chars()
+                        .flat_map(|c| [c, c])
+                        .collect::<String>()
+                })
+                .collect::<Vec<_>>()
+                .join("  ")
+        })
+        .collect()
+}
+
+fn ui_compact(f: &mut ratatui::Frame, state: &RecorderState) {
     let chunks = Layout::default()
         .direction(Direction::Vertical)
-        .constraints(
-            [
-                Constraint::Length(3), // Header
-                Constraint::Length(5), // Info
-                Constraint::Length(3), // Status
-                Constraint::Length(3), // Controls
-                Constraint::Min(4),    // Logs / Transcript
-            ]
-            .as_ref(),
-        )
+        .constraints([
+            Constraint::Length(1),
+            Constraint::Length(1),
+            Constraint::Min(0),
+        ])
         .split(f.size());
 
-    let title = Paragraph::new(" rcrd - Audio Recorder ")
-        .style(
-            Style::default()
-                .fg(Color::Cyan)
-                .add_modifier(Modifier::BOLD),
-        )
-        .block(Block::default().borders(Borders::ALL));
-    f.render_widget(title, chunks[0]);
+    let mut spans = mini_status(state);
+    if let Some(warning) = header_warning(state) {
+        spans.push(Span::raw("  "));
+        spans.push(Span::styled(warning.0, warning.1));
+    }
+    f.render_widget(Paragraph::new(Line::from(spans)), chunks[0]);
 
-    let elapsed = state.start_time.elapsed();
-    let duration_text = if let Some(d) = state.duration {
+    let keys = match &state.editing_note {
+        _ if state.confirm_discard => {
+            " Discard this recording and delete its files? y = yes, any other key = no".into()
+        }
+        Some(note) => format!(" Marker note: {note}_  (Enter = save, Esc = cancel)"),
+        None if state.editing_transcript_note.is_some() => format!(
+            " Transcript note: {}_  (Enter = add, Esc = cancel)",
+            state.editing_transcript_note.as_deref().unwrap_or_default()
+        ),
+        None => {
+            " q quit  d discard  m mute  +/- vol  b marker  x/e delete/edit  a note  p play  P pause  s save  t/T transcript  l lang"
+                .into()
+        }
+    };
+    f.render_widget(
+        Paragraph::new(keys).style(Theme::fg(state.theme.log)),
+        chunks[1],
+    );
+
+    let rows = chunks[2].height as usize;
+    let lines = if state.transcript_visible {
+        let transcript = state.handle.transcript();
+        let t = lock(&transcript);
+        t[state.transcript_view.visible(t.len(), rows)]
+            .iter()
+            .map(|seg| Line::raw(format!("{} {}", format_ms(seg.start_ms), seg.text)))
+            .collect::<Vec<_>>()
+    } else {
+        let logs = state.handle.logs();
+        let logs = lock(&logs);
+        logs.iter()
+            .skip(logs.len().saturating_sub(rows))
+            .map(|line| Line::raw(line.clone()))
+            .collect()
+    };
+    f.render_widget(
+        wrapped_tail(Paragraph::new(lines), chunks[2], false).style(Theme::fg(state.theme.log)),
+        chunks[2],
+    );
+}
+
+/// Banner text and style replacing the title when something needs attention.
+fn header_warning(state: &RecorderState) -> Option<(&'static str, Style)> {
+    let monitor_silent = state
+        .silence_check
+        .is_some_and(|window| !state.monitor_heard && state.handle.elapsed() >= window);
+    if state.handle.mic_lost() {
+        Some((
+            " Mic input disappeared - reconnect it; nothing is recorded from it ",
+            Theme::banner(state.theme.alert, Color::White),
+        ))
+    } else if state.handle.low_space().is_some() {
+        Some((
+            " Disk almost full - recording stops before it fills ",
+            Theme::banner(state.theme.alert, Color::White),
+        ))
+    } else if monitor_silent {
+        Some((
+            " No audio from the monitor yet - is --sink the right device? ",
+            Theme::banner(state.theme.warning, Color::Black),
+        ))
+    } else {
+        None
+    }
+}
+
+fn header_title(state: &RecorderState) -> Paragraph<'static> {
+    match header_warning(state) {
+        Some((text, style)) => Paragraph::new(text).style(style),
+        None => Paragraph::new(" rcrd - Audio Recorder ")
+            .style(Theme::fg(state.theme.title).add_modifier(Modifier::BOLD)),
+    }
+}
+
+fn duration_text(state: &RecorderState) -> String {
+    let elapsed = state.handle.elapsed();
+    if let Some(d) = state.handle.duration() {
         format!(
             "{:02}:{:02}:{:02} / {:02}:{:02}:{:02}",
             elapsed.as_secs() / 3600,
@@ -205,114 +874,640 @@ fn ui(f: &mut ratatui::Frame, state: &RecorderState) {
             (elapsed.as_secs() / 60) % 60,
             elapsed.as_secs() % 60
         )
+    }
+}
+
+/// Logs why `t` or `T` can't transcribe.
+fn log_transcription_unavailable(state: &RecorderState) {
+    if let Some(err) = state.handle.transcriber_error() {
+        state
+            .handle
+            .log(format!("Transcription unavailable: {err}"));
+    } else {
+        state.handle.log("Transcription model not configured");
+    }
+}
+
+/// Transcript pane title suffix telling whether whisper is producing output.
+fn transcriber_badge(state: &RecorderState) -> Span<'static> {
+    if !state.transcribe_enabled {
+        return Span::styled("paused (T = resume) ", Theme::fg(state.theme.warning));
+    }
+    match state.handle.transcriber_status() {
+        Some(TranscriberStatus::Loading) => {
+            Span::styled("loading… ", Theme::fg(state.theme.warning))
+        }
+        Some(TranscriberStatus::Ready) => Span::styled("model loaded ", Theme::fg(state.theme.ok)),
+        Some(TranscriberStatus::Error(err)) => Span::styled(
+            format!("transcriber error: {err} "),
+            Theme::fg(state.theme.alert).add_modifier(Modifier::BOLD),
+        ),
+        Some(TranscriberStatus::Silence) => {
+            Span::styled("VAD: skipping silence ", Theme::fg(state.theme.faint))
+        }
+        None => Span::raw(""),
+    }
+}
+
+/// Whether a clip happened within the last [`CLIP_HOLD`].
+fn clipping(state: &RecorderState) -> bool {
+    state.last_clip.is_some_and(|at| at.elapsed() < CLIP_HOLD)
+}
+
+/// `60% -4.4 dB`: the mic volume as set and as gain.
+fn mic_level(volume: f32) -> String {
+    if volume > 0.0 {
+        format!("{:.0}% {:+.1} dB", volume * 100.0, 20.0 * volume.log10())
+    } else {
+        "0%".into()
+    }
+}
+
+fn mic_badge(state: &RecorderState) -> Span<'static> {
+    if state.handle.mic().is_some() {
+        if state.handle.mic_lost() {
+            Span::styled(" LOST ", Theme::badge(state.theme.alert, Color::White))
+        } else if state.handle.mic_muted() {
+            Span::styled(" MUTED ", Theme::badge(state.theme.muted, Color::Black))
+        } else if state.handle.mic_volume() != 1.0 {
+            Span::styled(
+                format!(" ON AIR {} ", mic_level(state.handle.mic_volume())),
+                Theme::badge(state.theme.on_air, Color::Black),
+            )
+        } else {
+            Span::styled(" ON AIR ", Theme::badge(state.theme.on_air, Color::Black))
+        }
+    } else {
+        Span::raw(" N/A ")
+    }
+}
+
+/// The tapped monitors, or the raw ffmpeg input standing in for them.
+fn sink_info(state: &RecorderState) -> String {
+    match (state.handle.raw_input(), state.handle.monitors()) {
+        (Some(raw), _) => format!("RAW INPUT: {}", raw.join(" ")),
+        (None, []) => "(not tapped, mic only)".into(),
+        (None, monitors) => monitors.join(" + "),
+    }
+}
+
+/// ` (1.2 MB, 00:05:12 written, 128 kb/s)`: what ffmpeg reports having written to
+/// the current file, once it has.
+fn written_info(state: &RecorderState) -> String {
+    let progress = state.handle.progress();
+    let mut parts = Vec::new();
+    parts.extend(progress.size_bytes.map(format_bytes));
+    parts.extend(progress.time.map(|t| {
+        let secs = t.as_secs();
+        format!(
+            "{:02}:{:02}:{:02} written",
+            secs / 3600,
+            secs / 60 % 60,
+            secs % 60
+        )
+    }));
+    parts.extend(progress.bitrate_kbps.map(|kbps| format!("{kbps:.0} kb/s")));
+    if parts.is_empty() {
+        String::new()
+    } else {
+        format!(" ({})", parts.join(", "))
+    }
+}
+
+/// Model file, backend and language, or that no model is set.
+fn transcription_info(state: &RecorderState) -> String {
+    match (state.handle.model(), state.handle.backend()) {
+        (Some(model), Some(backend)) => format!(
+            "{}, {backend}, {}, {}",
+            model.file_name().unwrap_or_default().to_string_lossy(),
+            state.handle.language(),
+            match (state.transcribe_enabled, state.transcript_visible) {
+                (false, _) => "paused",
+                (true, true) => "transcribing",
+                (true, false) => "transcribing, pane hidden",
+            }
+        ),
+        _ => match state.handle.transcriber_error() {
+            Some(_) => "transcription: off (model failed to load)".into(),
+            None => "transcription: off".into(),
+        },
+    }
+}
+
+/// The recording state kept on screen in both layouts, whichever of the
+/// transcript or the log is shown: REC light, time, mic badge, level meter
+/// and markers.
+fn mini_status(state: &RecorderState) -> Vec<Span<'static>> {
+    let levels = state.handle.levels();
+    let markers = state.handle.markers();
+    let light = if state.handle.is_paused() {
+        Span::styled(
+            " ‖ PAUSED ",
+            Theme::fg(state.theme.warning).add_modifier(Modifier::BOLD),
+        )
+    } else {
+        Span::styled(
+            " ● REC ",
+            Theme::fg(state.theme.recording).add_modifier(Modifier::BOLD),
+        )
+    };
+    vec![
+        light,
+        Span::raw(duration_text(state)),
+        Span::raw("  Mic:"),
+        mic_badge(state),
+        Span::raw("  "),
+        Span::styled(
+            level_meter(
+                levels.peak_l.max(levels.peak_r),
+                levels.peak_hold_l.max(levels.peak_hold_r),
+                METER_WIDTH,
+            ),
+            Theme::fg(state.theme.ok),
+        ),
+        if clipping(state) {
+            Span::styled(" CLIP ", Theme::banner(state.theme.alert, Color::White))
+        } else {
+            Span::raw("")
+        },
+        Span::raw(format!(" {}", level_text(state))),
+        Span::raw(format!("  Markers: {}", markers.len())),
+        Span::raw(
+            markers
+                .last()
+                .map(|m| format!(" (last {})", marker_time(state, m.timestamp)))
+                .unwrap_or_default(),
+        ),
+    ]
+}
+
+/// Cells in the status line's level meter.
+const METER_WIDTH: usize = 10;
+
+/// `width` cells filled in proportion to `db` over the -60..0 dBFS range,
+/// with a thin mark in the cell of the held peak `hold` past the fill.
+fn level_meter(db: f32, hold: f32, width: usize) -> String {
+    let cells = |db: f32| {
+        let fraction = if db.is_finite() {
+            ((db + PEAK_HOLD_RANGE_DB) / PEAK_HOLD_RANGE_DB).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        (fraction * width as f32).round() as usize
+    };
+    let filled = cells(db);
+    let held = cells(hold);
+    (0..width)
+        .map(|i| match i {
+            _ if i < filled => '▮',
+            _ if i + 1 == held => '┃',
+            _ => '▯',
+        })
+        .collect()
+}
+
+fn level_text(state: &RecorderState) -> String {
+    let levels = state.handle.levels();
+    format!(
+        "{} / {} dB",
+        format_db(levels.peak_l),
+        format_db(levels.peak_r)
+    )
+}
+
+/// Remote and local speakers get their own colors; without speakers,
+/// languages do once the transcript mixes more than one. Typed notes stand
+/// out in italics.
+fn segment_style(state: &RecorderState, seg: &TransSegment, multilingual: bool) -> Style {
+    let theme = &state.theme;
+    if seg.manual {
+        return Theme::fg(theme.warning).add_modifier(Modifier::ITALIC);
+    }
+    let color = if let (Some(speaker), Some(labels)) = (&seg.speaker, state.handle.speaker_labels())
+    {
+        if *speaker == labels[0] {
+            theme.speakers[0]
+        } else {
+            theme.speakers[1]
+        }
+    } else if let Some(lang) = seg.lang.as_deref().filter(|_| multilingual) {
+        let hash = lang
+            .bytes()
+            .fold(0usize, |h, b| h.wrapping_mul(31) + b as usize);
+        theme.languages[hash % theme.languages.len()]
+    } else {
+        theme.transcript
+    };
+    Theme::fg(color)
+}
+
+fn format_ms(ms: i64) -> String {
+    let h = ms / 3_600_000;
+    let m = (ms / 60_000) % 60;
+    let s = (ms / 1000) % 60;
+    format!("{:02}:{:02}:{:02}.{:03}", h, m, s, ms % 1000)
+}
+
+fn ui_full(f: &mut ratatui::Frame, state: &RecorderState) {
+    let panes = visible_panes(f.size().height);
+    let rects = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(panes.iter().map(|p| p.constraint()))
+        .split(f.size());
+    // Hidden panes get an empty area, which renders nothing.
+    let area_of = |pane: Pane| {
+        panes
+            .iter()
+            .position(|p| *p == pane)
+            .map(|i| rects[i])
+            .unwrap_or_default()
     };
+    let chunks = Pane::ALL.map(area_of);
+
+    let title = header_title(state).block(Block::default().borders(Borders::ALL));
+    f.render_widget(title, chunks[0]);
 
     let info_text = format!(
-        "File: {}
+        "File: {}{}
 Sink: {}
 Mic : {}
+Rate: {} Hz, {}
+ASR : {}
 Rev : {}",
         state
-            .output_file
+            .handle
+            .output()
             .file_name()
             .unwrap_or_default()
             .to_string_lossy(),
-        state.monitor_source,
-        state.mic_source.as_deref().unwrap_or("(disabled)"),
+        written_info(state),
+        sink_info(state),
+        state.handle.mic().unwrap_or("(disabled)"),
+        state.handle.sample_rate(),
+        if state.handle.channels() == 1 {
+            "mono"
+        } else {
+            "stereo"
+        },
+        transcription_info(state),
         state.git_rev.as_deref().unwrap_or("unknown")
     );
     let info_block = Block::default().title(" Info ").borders(Borders::ALL);
     let info = Paragraph::new(info_text)
         .block(info_block)
-        .style(Style::default().fg(Color::White));
+        .style(Theme::fg(state.theme.text));
     f.render_widget(info, chunks[1]);
 
-    let mic_status = if state.mic_source.is_some() {
-        if state.mic_muted {
-            Span::styled(" MUTED ", Style::default().bg(Color::Red).fg(Color::Black))
-        } else {
-            Span::styled(
-                " ON AIR ",
-                Style::default().bg(Color::Green).fg(Color::Black),
+    // The Status pane is never dropped, so this stays visible next to the
+    // transcript as well as the log.
+    let status_block = Block::default().title(" Status ").borders(Borders::ALL);
+    let status_p = Paragraph::new(Line::from(mini_status(state))).block(status_block);
+    f.render_widget(status_p, chunks[2]);
+
+    let history_block = Block::default()
+        .title(format!(
+            " Level history ({}s per bar) ",
+            HISTORY_STEP.as_secs_f32()
+        ))
+        .borders(Borders::ALL);
+    let width = history_block.inner(chunks[3]).width as usize;
+    let bars = state.level_history.recent(width);
+    let history = Sparkline::default()
+        .block(history_block)
+        .data(&bars)
+        .max(PEAK_HOLD_RANGE_DB as u64)
+        .style(Theme::fg(state.theme.ok));
+    f.render_widget(history, chunks[3]);
+
+    let timeline_block = Block::default().title(" Timeline ").borders(Borders::ALL);
+    let timeline_area = timeline_block.inner(chunks[4]);
+    f.render_widget(timeline_block, chunks[4]);
+    let markers = state.handle.markers();
+    f.render_widget(
+        Timeline {
+            elapsed: state.handle.elapsed().as_secs_f64(),
+            total: state.handle.duration().map(|d| d.as_secs_f64()),
+            markers: markers.iter().map(|m| m.timestamp).collect(),
+            theme: &state.theme,
+        },
+        timeline_area,
+    );
+
+    let controls = if state.confirm_discard {
+        Paragraph::new(
+            "Stop and delete the recording, markers and transcript?\ny = Discard   any other key = Keep recording",
+        )
+        .style(Theme::fg(state.theme.alert))
+        .block(Block::default().title(" Discard ").borders(Borders::ALL))
+    } else if let Some(note) = &state.editing_note {
+        Paragraph::new(format!("Marker note: {note}_\nEnter = Save   Esc = Cancel"))
+            .style(Theme::fg(state.theme.warning))
+            .block(
+                Block::default()
+                    .title(" Edit marker ")
+                    .borders(Borders::ALL),
             )
-        }
+    } else if let Some(note) = &state.editing_transcript_note {
+        Paragraph::new(format!(
+            "Transcript note: {note}_\nEnter = Add   Esc = Cancel"
+        ))
+        .style(Theme::fg(state.theme.warning))
+        .block(
+            Block::default()
+                .title(" Add transcript note ")
+                .borders(Borders::ALL),
+        )
     } else {
-        Span::raw(" N/A ")
+        Paragraph::new(
+            "Controls: q / Esc / Ctrl+C = Quit   d = Discard   m = Mute/Unmute mic   +/- = Mic volume   b = Add marker   x = Delete last marker   e = Edit last marker   a = Add transcript note   p = Play from last marker   P = Pause/Resume   s = Save transcript now   t = Show/Hide transcript   T = Pause/Resume transcription   Up/Down/PgUp/PgDn/End = Scroll   / n = Search   l = Toggle lang (en/fr)\n\
+             Files: output OGG in cwd; markers .json beside it\n\
+             Devices: monitor from default sink, mic from default source (or --no-mic)",
+        )
+        .style(Theme::fg(state.theme.log))
+        .block(Block::default().title(" Controls ").borders(Borders::ALL))
     };
+    f.render_widget(controls, chunks[5]);
 
-    let status_line = Line::from(vec![
-        Span::raw(" Status: "),
-        Span::styled(
-            "RECORDING",
-            Style::default()
-                .fg(Color::Red)
-                .add_modifier(Modifier::RAPID_BLINK),
-        ),
-        Span::raw(" | Mic: "),
-        mic_status,
-        Span::raw(" | Time: "),
-        Span::raw(duration_text),
-        Span::raw(format!(" | Markers: {}", state.markers.len())),
-    ]);
-
-    let status_block = Block::default().borders(Borders::ALL);
-    let status_p = Paragraph::new(status_line).block(status_block);
-    f.render_widget(status_p, chunks[2]);
-
-    let controls = Paragraph::new(
-        "Controls: q / Esc / Ctrl+C = Quit   m = Mute/Unmute mic   b = Add marker   t = Toggle live transcript   l = Toggle lang (en/fr)\n\
-         Files: output OGG in cwd; markers .json beside it\n\
-         Devices: monitor from default sink, mic from default source (or --no-mic)",
-    )
-    .style(Style::default().fg(Color::Gray))
-    .block(Block::default().title(" Controls ").borders(Borders::ALL));
-    f.render_widget(controls, chunks[3]);
-
-    if state.transcription_active && state.whisper_model.is_some() {
-        let lines = if let Ok(t) = state.transcript.lock() {
-            let len = t.len();
-            let start = len.saturating_sub(10);
-            t.iter()
-                .skip(start)
-                .map(|seg| {
-                    let h = seg.start_ms / 3_600_000;
-                    let m = (seg.start_ms / 60_000) % 60;
-                    let s = (seg.start_ms / 1000) % 60;
-                    let ms = seg.start_ms % 1000;
-                    format!("{:02}:{:02}:{:02}.{:03} {}", h, m, s, ms, seg.text)
-                })
-                .collect::<Vec<_>>()
-        } else {
-            Vec::new()
-        };
+    if state.transcript_visible {
+        let transcript = state.handle.transcript();
+        let logs = state.handle.logs();
+        let t = lock_logged(&transcript, "transcript", &logs);
+        let view = &state.transcript_view;
+        let multilingual = t
+            .iter()
+            .filter_map(|seg| seg.lang.as_deref())
+            .any(|lang| Some(lang) != t.first().and_then(|seg| seg.lang.as_deref()));
+        let lines = t[view.visible(t.len(), 10)]
+            .iter()
+            .map(|seg| {
+                let line = format!("{} {}", format_ms(seg.start_ms), seg.text);
+                if view.matches(seg) {
+                    Line::styled(
+                        line,
+                        segment_style(state, seg, multilingual).add_modifier(Modifier::REVERSED),
+                    )
+                } else if seg.low_confidence {
+                    Line::styled(
+                        line,
+                        Theme::fg(state.theme.faint).add_modifier(Modifier::DIM),
+                    )
+                } else {
+                    Line::styled(line, segment_style(state, seg, multilingual))
+                }
+            })
+            .collect::<Vec<_>>();
+        drop(t);
         let txt = if lines.is_empty() {
-            "Transcription running…".to_string()
+            Text::raw(if state.transcribe_enabled {
+                "Transcription running…"
+            } else {
+                "Transcription paused; press T to resume"
+            })
         } else {
-            lines.join("\n")
+            Text::from(lines)
         };
-        let transcript = Paragraph::new(Text::raw(txt))
-            .style(Style::default().fg(Color::Gray))
-            .block(
-                Block::default()
-                    .title(" Live Transcript ")
-                    .borders(Borders::ALL),
-            );
-        f.render_widget(transcript, chunks[4]);
-    } else {
-        let log_lines = if let Ok(logs) = state.recent_logs.lock() {
-            let len = logs.len();
-            let start = len.saturating_sub(10);
-            logs.iter().skip(start).cloned().collect::<Vec<_>>()
+        let title = if let Some(query) = &view.editing_search {
+            Line::from(format!(" Search: {query}_ "))
+        } else if view.scroll > 0 {
+            Line::from(vec![
+                Span::raw(format!(
+                    " Live Transcript (-{}, End = follow) · ",
+                    view.scroll
+                )),
+                transcriber_badge(state),
+            ])
         } else {
-            Vec::new()
+            Line::from(vec![
+                Span::raw(" Live Transcript · "),
+                transcriber_badge(state),
+            ])
         };
+        let transcript = wrapped_tail(Paragraph::new(txt), chunks[6], true)
+            .style(Theme::fg(state.theme.transcript))
+            .block(Block::default().title(title).borders(Borders::ALL));
+        f.render_widget(transcript, chunks[6]);
+    } else {
+        let logs = state.handle.logs();
+        let logs = lock(&logs);
+        let start = logs.len().saturating_sub(10);
+        let log_lines = logs.iter().skip(start).cloned().collect::<Vec<_>>();
+        drop(logs);
         let help = Paragraph::new(Text::raw(log_lines.join("\n")))
-            .style(Style::default().fg(Color::Gray))
+            .style(Theme::fg(state.theme.log))
             .block(
                 Block::default()
                     .title(" FFmpeg Log (recent) ")
                     .borders(Borders::ALL),
             );
-        f.render_widget(help, chunks[4]);
+        f.render_widget(help, chunks[6]);
+    }
+}
+
+/// One-line bar spanning the recording, with a tick per marker and the playhead.
+/// Scaled to `total` when a duration is set, otherwise to `elapsed`.
+struct Timeline<'a> {
+    elapsed: f64,
+    total: Option<f64>,
+    markers: Vec<f64>,
+    theme: &'a Theme,
+}
+
+impl Widget for Timeline<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        if area.width == 0 || area.height == 0 {
+            return;
+        }
+        let span = self.total.unwrap_or(self.elapsed);
+        let y = area.y;
+        let played = timeline_column(self.elapsed, span, area.width);
+        for col in 0..area.width {
+            let (ch, color) = if col <= played {
+                ('━', self.theme.title)
+            } else {
+                ('─', self.theme.faint)
+            };
+            buf.get_mut(area.x + col, y)
+                .set_char(ch)
+                .set_style(Theme::fg(color));
+        }
+        for ts in self.markers {
+            let col = timeline_column(ts, span, area.width);
+            buf.get_mut(area.x + col, y)
+                .set_char('┃')
+                .set_style(Theme::fg(self.theme.warning));
+        }
+        buf.get_mut(area.x + played, y)
+            .set_char('●')
+            .set_style(Theme::fg(self.theme.recording));
+    }
+}
+
+/// Column of `ts` seconds on a bar of `width` cells covering `span` seconds.
+fn timeline_column(ts: f64, span: f64, width: u16) -> u16 {
+    let last = width.saturating_sub(1);
+    if span <= 0.0 {
+        return 0;
+    }
+    ((ts / span).clamp(0.0, 1.0) * last as f64).round() as u16
+}
+
+fn marker_time(state: &RecorderState, timestamp: f64) -> String {
+    let offset = Duration::from_secs_f64(timestamp.max(0.0));
+    match state.marker_clock {
+        MarkerClock::Relative => {
+            let secs = offset.as_secs();
+            format!(
+                "{:02}:{:02}:{:02}",
+                secs / 3600,
+                (secs / 60) % 60,
+                secs % 60
+            )
+        }
+        MarkerClock::Wall => {
+            let tm = state.handle.started_at() + offset;
+            format!("{:02}:{:02}:{:02}", tm.hour(), tm.minute(), tm.second())
+        }
+    }
+}
+
+fn format_db(db: f32) -> String {
+    if db.is_finite() {
+        format!("{:.0}", db)
+    } else {
+        "-inf".into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn small_terminals_drop_low_priority_panes() {
+        assert_eq!(visible_panes(40), Pane::ALL);
+        assert_eq!(visible_panes(27), Pane::ALL);
+        assert!(!visible_panes(26).contains(&Pane::History));
+        assert!(visible_panes(24).contains(&Pane::Bottom));
+        assert!(!visible_panes(23).contains(&Pane::Bottom));
+        assert_eq!(
+            visible_panes(17),
+            [Pane::Header, Pane::Info, Pane::Status, Pane::Timeline]
+        );
+        assert_eq!(visible_panes(14), [Pane::Header, Pane::Info, Pane::Status]);
+        assert_eq!(visible_panes(3), [Pane::Header, Pane::Status]);
+    }
+
+    #[test]
+    fn level_history_keeps_the_loudest_peak_of_each_bar() {
+        let mut history = LevelHistory::default();
+        let tick = HISTORY_STEP / 2;
+        history.push(-30.0, tick);
+        history.push(-50.0, tick);
+        history.push(f32::NEG_INFINITY, tick);
+        history.push(f32::NEG_INFINITY, tick);
+        history.push(6.0, HISTORY_STEP);
+        assert_eq!(history.recent(10), [30, 0, 60]);
+        assert_eq!(history.recent(2), [0, 60]);
+        for _ in 0..HISTORY_LEN {
+            history.push(-60.0, HISTORY_STEP);
+        }
+        assert_eq!(history.recent(usize::MAX).len(), HISTORY_LEN);
+        assert_eq!(history.pushed, 3 + HISTORY_LEN as u64);
+    }
+
+    #[test]
+    fn transcript_view_scrolls_and_searches() {
+        let t: Vec<_> = ["alpha", "beta", "alpha two", "gamma", "delta"]
+            .into_iter()
+            .map(|text| TransSegment::new(0, 0, text))
+            .collect();
+        let mut view = TranscriptView::default();
+        assert_eq!(view.visible(t.len(), 2), 3..5);
+        view.scroll = 99;
+        assert_eq!(view.visible(t.len(), 2), 0..1);
+
+        view.scroll = 0;
+        view.search = Some("ALPHA".into());
+        assert!(view.jump_to_match(&t, false));
+        assert_eq!(view.scroll, 2);
+        assert!(view.jump_to_match(&t, true));
+        assert_eq!(view.scroll, 4);
+        assert!(!view.jump_to_match(&t, true));
+        assert!(view.visible(0, 10).is_empty());
+    }
+
+    #[test]
+    fn countdown_shows_only_in_the_last_seconds() {
+        let secs = Duration::from_secs;
+        let window = secs(10);
+        assert_eq!(countdown_secs(secs(49), secs(60), window), None);
+        assert_eq!(countdown_secs(secs(50), secs(60), window), Some(10));
+        assert_eq!(
+            countdown_secs(Duration::from_millis(58_200), secs(60), window),
+            Some(2)
+        );
+        assert_eq!(countdown_secs(secs(60), secs(60), window), None);
+        assert_eq!(countdown_secs(secs(61), secs(60), window), None);
+        assert_eq!(countdown_secs(secs(59), secs(60), Duration::ZERO), None);
+    }
+
+    #[test]
+    fn wrapped_transcript_keeps_wide_and_combining_text_in_its_columns() {
+        let lines = vec![
+            Line::raw("00:00:01 会議は終わりました"),
+            Line::raw("00:00:02 e\u{301}te\u{301} 🎉 fin"),
+        ];
+        let area = Rect::new(0, 0, 10, 3);
+        let mut buf = Buffer::empty(area);
+        wrapped_tail(Paragraph::new(lines), area, false).render(area, &mut buf);
+        let rows: Vec<String> = (0..area.height)
+            .map(|y| {
+                (0..area.width)
+                    .map(|x| buf.get(x, y).symbol())
+                    .collect::<String>()
+            })
+            .collect();
+        // The newest line ends at the bottom; older rows scrolled off the top.
+        assert_eq!(rows[0], "り ま し た   ", "{rows:?}");
+        assert_eq!(rows[1].trim_end(), "00:00:02", "{rows:?}");
+        // Combining accents share their base's cell, wide glyphs span two.
+        assert_eq!(buf.get(0, 2).symbol(), "e\u{301}");
+        assert_eq!(buf.get(4, 2).symbol(), "🎉");
+        assert_eq!(buf.get(5, 2).symbol(), " ");
+        assert_eq!(buf.get(7, 2).symbol(), "f");
+    }
+
+    #[test]
+    fn big_digits_are_five_rows_high() {
+        let ten = big_digits(10);
+        assert_eq!(ten.len(), 5);
+        assert_eq!(ten[0], "  ██    ██████");
+        assert_eq!(ten[4], "██████  ██████");
+        assert!(ten.iter().all(|row| row.chars().count() == 14));
+    }
+
+    #[test]
+    fn level_meter_fills_with_the_level() {
+        let none = f32::NEG_INFINITY;
+        assert_eq!(level_meter(none, none, 4), "▯▯▯▯");
+        assert_eq!(level_meter(-90.0, none, 4), "▯▯▯▯");
+        assert_eq!(level_meter(-30.0, none, 4), "▮▮▯▯");
+        assert_eq!(level_meter(0.0, none, 4), "▮▮▮▮");
+        assert_eq!(level_meter(3.0, none, 4), "▮▮▮▮");
+        // The held peak marks its cell past the live level.
+        assert_eq!(level_meter(-45.0, -3.0, 4), "▮▯▯┃");
+        assert_eq!(level_meter(none, -30.0, 4), "▯┃▯▯");
+        assert_eq!(level_meter(-3.0, -3.0, 4), "▮▮▮▮");
+    }
+
+    #[test]
+    fn timeline_scales_to_width() {
+        assert_eq!(timeline_column(0.0, 60.0, 41), 0);
+        assert_eq!(timeline_column(30.0, 60.0, 41), 20);
+        assert_eq!(timeline_column(60.0, 60.0, 41), 40);
+        // Past the end (or before a duration is known) stays on the bar.
+        assert_eq!(timeline_column(90.0, 60.0, 41), 40);
+        assert_eq!(timeline_column(5.0, 0.0, 41), 0);
+        assert_eq!(timeline_column(5.0, 10.0, 0), 0);
     }
 }
@@ -0,0 +1,94 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::sync::mpsc::Receiver;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use anyhow::{Context, Result, anyhow};
+use serde::Serialize;
+
+use crate::sync::lock;
+use crate::transcript::TransSegment;
+
+/// Spawns a background poster that sends each segment received on `rx` to
+/// `url` as `{start_ms,end_ms,text}` JSON. Delivery goes through `curl` so
+/// transcription never waits on the network; failures go to `recent_logs`.
+pub fn start_webhook(
+    url: String,
+    rx: Receiver<TransSegment>,
+    recent_logs: Arc<Mutex<Vec<String>>>,
+) {
+    thread::spawn(move || {
+        for seg in rx {
+            if let Err(err) = post_segment(&url, &seg) {
+                lock(&recent_logs).push(format!("Webhook failed: {err:#}"));
+            }
+        }
+    });
+}
+
+/// What is posted for a segment; the rest of [`TransSegment`] stays local.
+#[derive(Serialize)]
+struct Payload<'a> {
+    start_ms: i64,
+    end_ms: i64,
+    text: &'a str,
+}
+
+fn payload(seg: &TransSegment) -> Result<Vec<u8>> {
+    Ok(serde_json::to_vec(&Payload {
+        start_ms: seg.start_ms,
+        end_ms: seg.end_ms,
+        text: &seg.text,
+    })?)
+}
+
+fn post_segment(url: &str, seg: &TransSegment) -> Result<()> {
+    let body = payload(seg)?;
+    let mut child = Command::new("curl")
+        .args(["-sS", "-f", "-o", "/dev/null", "-m", "5"])
+        .args([
+            "-H",
+            "Content-Type: application/json",
+            "--data-binary",
+            "@-",
+        ])
+        .arg(url)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("failed to run curl")?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(&body)?;
+    }
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        let msg = String::from_utf8_lossy(&output.stderr);
+        return Err(anyhow!(
+            "curl exited with {}: {}",
+            output.status,
+            msg.trim()
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn posts_only_times_and_text() {
+        let seg = TransSegment {
+            confidence: Some(0.9),
+            speaker: Some("Remote".into()),
+            lang: Some("en".into()),
+            ..TransSegment::new(1200, 3400, "[Remote] hello")
+        };
+        assert_eq!(
+            String::from_utf8(payload(&seg).unwrap()).unwrap(),
+            r#"{"start_ms":1200,"end_ms":3400,"text":"[Remote] hello"}"#
+        );
+    }
+}